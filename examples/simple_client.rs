@@ -54,6 +54,8 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
     sftp.mkdir("/tmp/dir").await?;
 
     println!("> Create a symlink");
+    // OpenSSH swaps link and target on the wire:
+    // this creates the link `/tmp/dir/link` that points to `/tmp/dummy.txt`
     sftp.symlink("/tmp/dummy.txt", "/tmp/dir/link").await?;
 
     println!("> Open a file for reading and writing");
@@ -0,0 +1,41 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Decode arbitrary bytes as SFTP messages.
+//!
+//! Decoding must never panic, and the messages that are decoded successfully
+//! must be encoded back into frames that decode to the same message.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rusftp::message::Message;
+
+fuzz_target!(|data: &[u8]| {
+    // The input may be truncated, or have an inconsistent frame length
+    _ = Message::decode(data);
+
+    if let Ok((id, message)) = Message::decode_raw(data) {
+        let encoded = message
+            .encode(id)
+            .expect("Decoded messages can be encoded");
+        let (decoded_id, decoded) =
+            Message::decode(&encoded).expect("Encoded messages can be decoded");
+
+        assert_eq!(decoded_id, id);
+        assert_eq!(decoded, message);
+    }
+});
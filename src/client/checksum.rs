@@ -0,0 +1,232 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::Deserialize;
+use sha1::Digest;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::client::{SftpClient, SftpFuture, SftpReply, SftpRequest, StatusCode};
+use crate::message::{Extended, ExtendedReply, Path};
+
+/// Hash algorithm used to verify the content of a remote file.
+///
+/// See [`SftpClient::check_file`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+    /// MD5 (16 bytes)
+    Md5,
+    /// SHA-1 (20 bytes)
+    Sha1,
+    /// SHA-224 (28 bytes)
+    Sha224,
+    /// SHA-256 (32 bytes)
+    #[default]
+    Sha256,
+    /// SHA-384 (48 bytes)
+    Sha384,
+    /// SHA-512 (64 bytes)
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    /// Name of the algorithm in the `check-file` extension.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Sha224 => "sha224",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha384 => "sha384",
+            ChecksumAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Options of [`SftpClient::upload_verified`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VerifyOptions {
+    /// Hash algorithm used to compare the local and the remote content
+    pub algorithm: ChecksumAlgorithm,
+    /// Remove the remote file if the checksums do not match
+    pub remove_on_mismatch: bool,
+    /// Download the remote file to compute its checksum locally,
+    /// if the server does not support the `check-file` extension
+    pub download_fallback: bool,
+}
+
+impl SftpClient {
+    /// Compute the checksum of a remote file on the server.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn check_file(&self, path: impl Into<Path>, algorithm: ChecksumAlgorithm) -> Result<Bytes, Error>;
+    /// ```
+    ///
+    /// The checksum of the whole file is computed with the `check-file-name` extension.
+    /// Servers that do not support the extension reply with an `OpUnsupported` error.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the remote file
+    /// * `algorithm` - Hash algorithm to use
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn check_file(
+        &self,
+        path: impl Into<Path>,
+        algorithm: ChecksumAlgorithm,
+    ) -> SftpFuture<Bytes, ChecksumAlgorithm> {
+        let path = self.resolve_path(path);
+        let name = algorithm.name().as_bytes();
+
+        let mut data = BytesMut::with_capacity(4 + path.len() + 4 + name.len() + 8 + 8 + 4);
+        data.put_u32(path.len() as u32);
+        data.put_slice(path.as_bytes());
+        data.put_u32(name.len() as u32);
+        data.put_slice(name);
+        data.put_u64(0); // start offset
+        data.put_u64(0); // length: until the end of the file
+        data.put_u32(0); // block size: single hash for the whole range
+
+        self.request_with(
+            Extended {
+                request: Bytes::from_static(b"check-file-name"),
+                data: data.freeze(),
+            }
+            .to_request_message(),
+            algorithm,
+            |algorithm, msg| {
+                let reply: CheckFileReply = ExtendedReply::from_reply_message(msg)?.decode()?;
+                if reply.algorithm != algorithm.name().as_bytes() {
+                    return Err(StatusCode::BadMessage
+                        .to_status("Unexpected check-file hash algorithm")
+                        .into());
+                }
+                Ok(reply.hash)
+            },
+        )
+    }
+}
+
+/// Reply of the `check-file` extension
+#[derive(Debug, Deserialize)]
+struct CheckFileReply {
+    #[allow(dead_code)]
+    name: Bytes,
+    algorithm: Bytes,
+    #[serde(rename = "hash_implicit_length")]
+    hash: Bytes,
+}
+
+/// Incremental hash computation for any [`ChecksumAlgorithm`].
+pub(super) enum Hasher {
+    Md5(md5::Context),
+    Sha1(sha1::Sha1),
+    Sha224(sha2::Sha224),
+    Sha256(sha2::Sha256),
+    Sha384(sha2::Sha384),
+    Sha512(sha2::Sha512),
+}
+
+impl Hasher {
+    pub(super) fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            ChecksumAlgorithm::Sha1 => Hasher::Sha1(Default::default()),
+            ChecksumAlgorithm::Sha224 => Hasher::Sha224(Default::default()),
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Default::default()),
+            ChecksumAlgorithm::Sha384 => Hasher::Sha384(Default::default()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(Default::default()),
+        }
+    }
+
+    pub(super) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.consume(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha224(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha384(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    pub(super) fn finalize(self) -> Bytes {
+        match self {
+            Hasher::Md5(hasher) => Bytes::copy_from_slice(&hasher.compute().0),
+            Hasher::Sha1(hasher) => Bytes::copy_from_slice(&hasher.finalize()),
+            Hasher::Sha224(hasher) => Bytes::copy_from_slice(&hasher.finalize()),
+            Hasher::Sha256(hasher) => Bytes::copy_from_slice(&hasher.finalize()),
+            Hasher::Sha384(hasher) => Bytes::copy_from_slice(&hasher.finalize()),
+            Hasher::Sha512(hasher) => Bytes::copy_from_slice(&hasher.finalize()),
+        }
+    }
+}
+
+/// Reader that hashes the data read from the inner reader.
+pub(super) struct HashingReader<R> {
+    pub(super) inner: R,
+    pub(super) hasher: Hasher,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        std::task::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.hasher.update(&buf.filled()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Writer that hashes the data written, and discards it.
+pub(super) struct HashingWriter(pub(super) Hasher);
+
+impl AsyncWrite for HashingWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.0.update(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
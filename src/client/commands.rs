@@ -14,14 +14,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bytes::Bytes;
-use futures::Future;
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::stream::{FuturesOrdered, FuturesUnordered};
+use futures::{Future, FutureExt, Stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
 
 use crate::client::{Dir, Error, File, SftpClient, SftpFuture, SftpReply, SftpRequest, StatusCode};
 use crate::message::{
     Attrs, Close, Data, Extended, ExtendedReply, FSetStat, FStat, Handle, LStat, Message, MkDir,
-    Name, Open, OpenDir, PFlags, Path, Read, ReadDir, ReadLink, RealPath, Remove, Rename, RmDir,
-    SetStat, Stat, Status, Symlink, Write,
+    Name, Open, OpenDir, PFlags, Path, Permisions, Read, ReadDir, ReadLink, RealPath, Remove,
+    Rename, RmDir, SetStat, Stat, Status, Symlink, Time, Write,
 };
 use crate::utils::IntoBytes;
 
@@ -46,6 +51,149 @@ impl SftpClient {
         self.request(Close { handle })
     }
 
+    /// Copy the permissions and the ownership of a file or directory to another one.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn copy_permissions(&self, from: impl Into<Path>, to: impl Into<Path>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// The attributes of `from` are read with `stat`,
+    /// and its `perms` and `owner` are applied to `to` with `setstat`.
+    /// Nothing is applied if the server reports neither of them.
+    ///
+    /// Changing the owner usually requires elevated rights on the server:
+    /// an error is returned if the user is not allowed to apply the owner of `from`.
+    ///
+    /// # Arguments
+    ///
+    /// * `from`: Path of the file or directory whose permissions are copied
+    /// * `to`: Path of the file or directory to change the permissions
+    ///
+    /// # Cancel safety
+    ///
+    /// The `stat` request is sent before the future is returned.
+    /// If the future is dropped before completion, the permissions of `to` might be left unchanged.
+    pub fn copy_permissions(
+        &self,
+        from: impl Into<Path>,
+        to: impl Into<Path>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        let stat = self.stat(from);
+        let to = self.resolve_path(to);
+        let client = self.clone();
+
+        async move {
+            match permissions(stat.await?) {
+                Some(attrs) => client.setstat(to, attrs).await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Copy the permissions and the ownership of an open file or directory to another one.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn copy_permissions_handle(&self, from: Handle, to: Handle) -> Result<(), Error>;
+    /// ```
+    ///
+    /// See [`SftpClient::copy_permissions`].
+    ///
+    /// # Arguments
+    ///
+    /// * `from`: Handle of the file or directory whose permissions are copied
+    /// * `to`: Handle of the file or directory to change the permissions
+    ///
+    /// # Cancel safety
+    ///
+    /// The `fstat` request is sent before the future is returned.
+    /// If the future is dropped before completion, the permissions of `to` might be left unchanged.
+    pub fn copy_permissions_handle(
+        &self,
+        from: Handle,
+        to: Handle,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        let stat = self.fstat(from);
+        let client = self.clone();
+
+        async move {
+            match permissions(stat.await?) {
+                Some(attrs) => client.fsetstat(to, attrs).await,
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Expand a path, including `~` and `~user` prefixes, and canonicalize it.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn expand_path(&self, path: impl Into<Path>) -> Result<Path, Error>;
+    /// ```
+    ///
+    /// Uses the `expand-path@openssh.com` extension.
+    /// If the server does not support it, the path is canonicalized with `realpath` instead,
+    /// and the `~` prefixes are expanded only if the server does it on its own.
+    ///
+    /// Paths starting with `~` are sent as-is, even if the client has a working directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to expand
+    ///
+    /// # Cancel safety
+    ///
+    /// The `expand-path@openssh.com` request is sent before the future is returned.
+    /// It is safe to cancel the future.
+    pub fn expand_path(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<Path, Error>> + Send + Sync + 'static {
+        let path = path.into();
+        let path = if path.starts_with('~') {
+            path
+        } else {
+            self.resolve_path(path)
+        };
+
+        let mut data = BytesMut::with_capacity(4 + path.len());
+        data.put_u32(path.len() as u32);
+        data.put_slice(path.as_bytes());
+
+        let expand = self.request_with(
+            Extended {
+                request: Bytes::from_static(b"expand-path@openssh.com"),
+                data: data.freeze(),
+            }
+            .to_request_message(),
+            (),
+            extract_path_from_name_message,
+        );
+        let client = self.clone();
+
+        async move {
+            match expand.await {
+                Err(Error::Sftp(Status {
+                    code: StatusCode::OpUnsupported,
+                    ..
+                })) => {
+                    client
+                        .request_with(
+                            RealPath { path }.to_request_message(),
+                            (),
+                            extract_path_from_name_message,
+                        )
+                        .await
+                }
+                result => result,
+            }
+        }
+    }
+
     /// Send an extended request.
     ///
     /// Equivalent to:
@@ -75,6 +223,87 @@ impl SftpClient {
         )
     }
 
+    /// Send an extended request, and decode its reply.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn extended_decode<T>(&self, request: impl Into<Bytes>, data: impl Into<Bytes>) -> Result<T, Error>;
+    /// ```
+    ///
+    /// The data of the reply is decoded with the SFTP wire format (see [`ExtendedReply::decode`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Extended-request name (format: `name@domain`)
+    /// * `data` - Specific data needed by the extension to intrepret the request
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn extended_decode<T: DeserializeOwned>(
+        &self,
+        request: impl IntoBytes,
+        data: impl IntoBytes,
+    ) -> SftpFuture<T> {
+        self.request_with(
+            Extended {
+                request: request.into_bytes(),
+                data: data.into_bytes(),
+            }
+            .to_request_message(),
+            (),
+            |_, msg| Ok(ExtendedReply::from_reply_message(msg)?.decode()?),
+        )
+    }
+
+    /// Send an extended request whose reply is a single `u64`.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn extended_u64(&self, request: impl Into<Bytes>, data: impl Into<Bytes>) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Extended-request name (format: `name@domain`)
+    /// * `data` - Specific data needed by the extension to intrepret the request
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn extended_u64(&self, request: impl IntoBytes, data: impl IntoBytes) -> SftpFuture<u64> {
+        self.extended_decode(request, data)
+    }
+
+    /// Send an extended request whose reply is a single string.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn extended_string(&self, request: impl Into<Bytes>, data: impl Into<Bytes>) -> Result<Bytes, Error>;
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Extended-request name (format: `name@domain`)
+    /// * `data` - Specific data needed by the extension to intrepret the request
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn extended_string(
+        &self,
+        request: impl IntoBytes,
+        data: impl IntoBytes,
+    ) -> SftpFuture<Bytes> {
+        self.extended_decode(request, data)
+    }
+
     /// Change the attributes (metadata) of an open file or directory.
     ///
     /// Equivalent to:
@@ -130,7 +359,7 @@ impl SftpClient {
     /// async fn lstat(&self, path: impl Into<Path>) -> Result<Attrs, Error>;
     /// ```
     ///
-    /// Symbolic links are followed.
+    /// Symbolic links *are not* followed.
     ///
     /// # Arguments
     ///
@@ -141,7 +370,9 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn lstat(&self, path: impl Into<Path>) -> SftpFuture<Attrs> {
-        self.request(LStat { path: path.into() })
+        self.request(LStat {
+            path: self.resolve_path(path),
+        })
     }
 
     /// Create a new directory.
@@ -187,7 +418,7 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn mkdir_with_attrs(&self, path: impl Into<Path>, attrs: Attrs) -> SftpFuture {
         self.request(MkDir {
-            path: path.into(),
+            path: self.resolve_path(path),
             attrs,
         })
     }
@@ -219,7 +450,7 @@ impl SftpClient {
         attrs: Attrs,
     ) -> SftpFuture<Handle> {
         self.request(Open {
-            filename: filename.into(),
+            filename: self.resolve_path(filename),
             pflags,
             attrs,
         })
@@ -253,16 +484,55 @@ impl SftpClient {
     ) -> SftpFuture<File, SftpClient> {
         self.request_with(
             Open {
-                filename: filename.into(),
+                filename: self.resolve_path(filename),
                 pflags,
                 attrs,
             }
             .to_request_message(),
             self.clone(),
-            |client, msg| Ok(File::new(client, Handle::from_reply_message(msg)?)),
+            |client, msg| Ok(File::from_handle(client, Handle::from_reply_message(msg)?)),
         )
     }
 
+    /// Open a file for reading or writing, and get its attributes.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn open_with_stat(&self, filename: impl Into<Path>, pflags: PFlags, attrs: Attrs) -> Result<(File, Attrs), Error>;
+    /// ```
+    ///
+    /// As the open request only returns a handle, the attributes are obtained with a `fstat` request
+    /// sent as soon as the file is opened.
+    /// This still requires one extra round-trip, but saves a manual follow-up.
+    ///
+    /// If the `fstat` request fails, the file is closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to open
+    /// * `pflags` - Flags for the file opening
+    /// * `attrs` - Default file attributes to use upon file creation
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the file is closed as soon as it is opened.
+    pub fn open_with_stat(
+        &self,
+        filename: impl Into<Path>,
+        pflags: PFlags,
+        attrs: Attrs,
+    ) -> impl Future<Output = Result<(File, Attrs), Error>> + Send + Sync + 'static {
+        let file = self.open_with_flags_attrs(filename, pflags, attrs);
+
+        async move {
+            let file = file.await?;
+            let attrs = file.stat().await?;
+            Ok((file.with_size_hint(attrs.size), attrs))
+        }
+    }
+
     /// Open a file for reading or writing.
     ///
     /// Equivalent to:
@@ -300,6 +570,9 @@ impl SftpClient {
     ///
     /// Returns a [`File`] object that is compatible with [`tokio::io`].
     ///
+    /// The file is opened with [`PFlags::READ`].
+    /// Use [`SftpClient::open_with_flags_attrs`] to set the flags explicitly.
+    ///
     /// # Arguments
     ///
     /// * `path` - Path of the file to open
@@ -314,7 +587,7 @@ impl SftpClient {
         filename: impl Into<Path>,
         attrs: Attrs,
     ) -> SftpFuture<File, SftpClient> {
-        self.open_with_flags_attrs(filename, PFlags::default(), attrs)
+        self.open_with_flags_attrs(filename, PFlags::READ, attrs)
     }
 
     /// Open a file for reading or writing.
@@ -327,6 +600,9 @@ impl SftpClient {
     ///
     /// Returns a [`File`] object that is compatible with [`tokio::io`].
     ///
+    /// The file is opened with [`PFlags::READ`].
+    /// Use [`SftpClient::open_with_flags`] to open it for writing.
+    ///
     /// # Arguments
     ///
     /// * `path` - Path of the file to open
@@ -336,7 +612,142 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn open(&self, filename: impl Into<Path>) -> SftpFuture<File, SftpClient> {
-        self.open_with_flags_attrs(filename, PFlags::default(), Attrs::default())
+        self.open_with_flags_attrs(filename, PFlags::READ, Attrs::default())
+    }
+
+    /// Open a file for reading.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn open_read(&self, filename: impl Into<Path>) -> Result<File, Error>;
+    /// ```
+    ///
+    /// The file is opened with [`PFlags::READ`], like [`SftpClient::open`].
+    /// Returns a [`File`] object that is compatible with [`tokio::io`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to open
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn open_read(&self, filename: impl Into<Path>) -> SftpFuture<File, SftpClient> {
+        self.open_with_flags_attrs(filename, PFlags::READ, Attrs::default())
+    }
+
+    /// Create and open a new file for writing, failing if the file already exists.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn open_exclusive(&self, filename: impl Into<Path>, attrs: Attrs) -> Result<File, Error>;
+    /// ```
+    ///
+    /// The file is opened with `CREATE | EXCLUDE | WRITE`, which is suitable for lock files.
+    ///
+    /// If the file already exists, an error is returned for which [`Error::is_already_exists`] is `true`.
+    /// As SFTP version 3 has no status code for this condition,
+    /// most servers reply with a generic `Failure` status.
+    /// Therefore, a `Failure` status is followed by a `lstat` of the path,
+    /// and is considered as the file already existing only if the path exists.
+    /// Otherwise, the `Failure` status is returned as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to create
+    /// * `attrs` - Default file attributes to use upon file creation
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn open_exclusive(
+        &self,
+        filename: impl Into<Path>,
+        attrs: Attrs,
+    ) -> impl Future<Output = Result<File, Error>> + Send + Sync + 'static {
+        let filename = self.resolve_path(filename);
+        let open = self.open_with_flags_attrs(
+            filename.clone(),
+            PFlags::CREATE | PFlags::EXCLUDE | PFlags::WRITE,
+            attrs,
+        );
+        let client = self.clone();
+
+        async move {
+            match open.await {
+                Err(Error::Sftp(
+                    status @ Status {
+                        code: StatusCode::Failure,
+                        ..
+                    },
+                )) => match client.lstat(filename).await {
+                    Ok(_) => Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::AlreadyExists,
+                        status,
+                    ))),
+                    Err(_) => Err(Error::Sftp(status)),
+                },
+                result => result,
+            }
+        }
+    }
+
+    /// Create or truncate a file, and open it for writing with the given permissions.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn open_create_mode(&self, filename: impl Into<Path>, mode: Permisions, force_mode: bool) -> Result<File, Error>;
+    /// ```
+    ///
+    /// The file is opened with `WRITE | CREATE | TRUNCATE`.
+    ///
+    /// The server applies its own umask to the permissions of a newly created file:
+    /// a file created with `0o666` usually ends up with `0o644`.
+    /// The permissions of a file that already exists are left unchanged.
+    /// If `force_mode` is set, the permissions are set explicitly with a `fsetstat` request
+    /// once the file is opened, which is the only way to get exactly `mode`.
+    /// It costs an extra round-trip, and also applies to a file that already existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to create
+    /// * `mode` - Permissions of the file
+    /// * `force_mode` - Set the permissions after the file is opened, regardless of the umask of the server
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the file is closed as soon as it is opened,
+    /// or in the background if the `fsetstat` request is pending.
+    /// The file may have been created or truncated anyway.
+    pub fn open_create_mode(
+        &self,
+        filename: impl Into<Path>,
+        mode: Permisions,
+        force_mode: bool,
+    ) -> impl Future<Output = Result<File, Error>> + Send + Sync + 'static {
+        let attrs = Attrs {
+            perms: Some(mode),
+            ..Default::default()
+        };
+        let file = self.open_with_flags_attrs(
+            filename,
+            PFlags::WRITE | PFlags::CREATE | PFlags::TRUNCATE,
+            attrs.clone(),
+        );
+
+        async move {
+            let file = file.await?;
+            if force_mode {
+                file.set_stat(attrs).await?;
+            }
+            Ok(file)
+        }
     }
 
     /// Open a directory for listing.
@@ -361,7 +772,9 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn opendir_handle(&self, path: impl Into<Path>) -> SftpFuture<Handle> {
-        self.request(OpenDir { path: path.into() })
+        self.request(OpenDir {
+            path: self.resolve_path(path),
+        })
     }
 
     /// Open a directory for listing.
@@ -385,56 +798,133 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn opendir(&self, path: impl Into<Path>) -> SftpFuture<Dir, SftpClient> {
         self.request_with(
-            OpenDir { path: path.into() }.to_request_message(),
+            OpenDir {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
             self.clone(),
-            |client, msg| Ok(Dir::new(client, Handle::from_reply_message(msg)?)),
+            |client, msg| Ok(Dir::from_handle(client, Handle::from_reply_message(msg)?)),
         )
     }
 
-    /// Read a portion of an opened file.
+    /// Open a directory for listing, without keeping the long names of the entries.
     ///
     /// Equivalent to:
     ///
     /// ```ignore
-    /// async fn read(&self, handle: Handle, offset: u64, length: u32) -> Result<Bytes, Error>;
+    /// async fn opendir_names_only(&self, path: impl Into<Path>) -> Result<Dir, Error>;
     /// ```
     ///
+    /// Same as `opendir()`, but the [`long_name`](crate::message::NameEntry::long_name) of the entries is dropped.
+    /// Filenames and attributes are kept.
+    /// See [`Dir::with_long_names`] for the memory impact.
+    ///
     /// # Arguments
     ///
-    /// * `handle`: Handle of the file to read from
-    /// * `offset`: Byte offset where the read should start
-    /// * `length`: Number of bytes to read
+    /// * `path` - Path of the directory to open
     ///
     /// # Cancel safety
     ///
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
-    pub fn read(&self, handle: Handle, offset: u64, length: u32) -> SftpFuture<Bytes> {
+    pub fn opendir_names_only(&self, path: impl Into<Path>) -> SftpFuture<Dir, SftpClient> {
         self.request_with(
-            Read {
-                handle,
-                offset,
-                length,
+            OpenDir {
+                path: self.resolve_path(path),
             }
             .to_request_message(),
-            (),
-            |_, msg| Ok(Handle::from_reply_message(msg)?.0),
+            self.clone(),
+            |client, msg| {
+                Ok(Dir::from_handle(client, Handle::from_reply_message(msg)?)
+                    .with_long_names(false))
+            },
         )
     }
 
-    /// Read a directory listing.
+    /// Open a directory for listing, and request its first entries as soon as it is open.
     ///
     /// Equivalent to:
     ///
     /// ```ignore
-    /// async fn readdir_handle(&self, handle: Handle) -> Result<Name, Error>;
+    /// async fn opendir_prefetched(&self, path: impl Into<Path>) -> Result<Dir, Error>;
     /// ```
     ///
-    /// Each `readdir_handle` returns one or more file names with full file attributes for each file.
-    /// The client should call `readdir_handle` repeatedly until it has found the file it is looking for
-    /// or until the server responds with a [`Status`] message indicating an error
-    /// (normally `EOF` if there are no more files in the directory).
-    /// The client should then close the handle using `close`.
+    /// Same as `opendir()`, but the first `ReadDir` request is sent when the handle is received,
+    /// instead of when the [`Dir`] is first polled.
+    /// This saves a round-trip when the directory is listed right after being opened.
+    /// See [`Dir::prefetch`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to open
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn opendir_prefetched(&self, path: impl Into<Path>) -> SftpFuture<Dir, SftpClient> {
+        self.request_with(
+            OpenDir {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
+            self.clone(),
+            |client, msg| {
+                let mut dir = Dir::from_handle(client, Handle::from_reply_message(msg)?);
+                dir.prefetch();
+                Ok(dir)
+            },
+        )
+    }
+
+    /// Read a portion of an opened file.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read(&self, handle: Handle, offset: u64, length: u32) -> Result<Bytes, Error>;
+    /// ```
+    ///
+    /// The server can return less bytes than requested, even before the end of the file
+    /// (eg: when `length` exceeds its maximal read length).
+    /// Use [`SftpClient::read_exact_at`] to read a whole range.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: Handle of the file to read from
+    /// * `offset`: Byte offset where the read should start
+    /// * `length`: Number of bytes to read
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn read(&self, handle: Handle, offset: u64, length: u32) -> SftpFuture<Bytes> {
+        self.request_with(
+            Read {
+                handle,
+                offset,
+                length,
+            }
+            .to_request_message(),
+            (),
+            |_, msg| Ok(Data::from_reply_message(msg)?.0),
+        )
+    }
+
+    /// Read a directory listing.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn readdir_handle(&self, handle: Handle) -> Result<Name, Error>;
+    /// ```
+    ///
+    /// Each `readdir_handle` returns one or more file names with full file attributes for each file.
+    /// The client should call `readdir_handle` repeatedly until it has found the file it is looking for
+    /// or until the server responds with a [`Status`] message indicating an error
+    /// (normally `EOF` if there are no more files in the directory).
+    /// The client should then close the handle using `close`.
     ///
     /// # Arguments
     ///
@@ -456,7 +946,9 @@ impl SftpClient {
     /// async fn readdir(&self, path: impl Into<Path>) -> Result<Name, Error>;
     /// ```
     ///
-    /// If you need an asynchronous [`Stream`](futures::stream::Stream), you can use `opendir()` instead
+    /// The whole listing is kept in memory, which can be large for huge directories.
+    /// If you need an asynchronous [`Stream`](futures::stream::Stream), you can use `opendir()` instead,
+    /// or `readdir_capped()` to bound the number of entries.
     ///
     /// # Arguments
     ///
@@ -470,28 +962,165 @@ impl SftpClient {
         &self,
         path: impl Into<Path>,
     ) -> impl Future<Output = Result<Name, Error>> + Send + Sync + 'static {
-        let dir = self.request(OpenDir { path: path.into() });
+        self.readdir_capped(path, usize::MAX)
+    }
+
+    /// Read the beginning of a directory listing.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn readdir_capped(&self, path: impl Into<Path>, max_entries: usize) -> Result<Name, Error>;
+    /// ```
+    ///
+    /// Same as `readdir()`, but no more `ReadDir` requests are sent once `max_entries` entries have been received,
+    /// and the listing is truncated to `max_entries` entries.
+    /// A listing of exactly `max_entries` entries might thus be incomplete.
+    ///
+    /// The entries are listed in the order of the server, which is usually not sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the directory to list
+    /// * `max_entries`: Maximal number of entries returned
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn readdir_capped(
+        &self,
+        path: impl Into<Path>,
+        max_entries: usize,
+    ) -> impl Future<Output = Result<Name, Error>> + Send + Sync + 'static {
+        let dir = self.opendir(path);
+
+        async move {
+            let mut dir = dir.await?;
+            let mut entries = Vec::new();
+
+            let result = loop {
+                if entries.len() >= max_entries {
+                    break Ok(());
+                }
+                match dir.fill(&mut entries).await {
+                    Ok(0) => break Ok(()),
+                    Ok(_) => (),
+                    Err(err) => break Err(err),
+                }
+            };
+            let closed = dir.close_handle_only().await;
+
+            result?;
+            closed?;
+            entries.truncate(max_entries);
+            Ok(Name(entries))
+        }
+    }
+
+    /// List a single directory, with the full path of the entries.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// fn list(&self, dir: impl Into<Path>) -> impl Stream<Item = Result<(Path, Attrs), Error>>;
+    /// ```
+    ///
+    /// The filename of each entry is joined onto `dir`, and paired with the attributes of the entry.
+    /// The `.` and `..` entries are skipped.
+    /// The entries are yielded as soon as their batch is received, and the directory is not recursed into.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: Path of the directory to list
+    ///
+    /// # Cancel safety
+    ///
+    /// The opendir request is sent before the stream is returned.
+    /// If the stream is dropped, the directory handle is closed in the background.
+    pub fn list(
+        &self,
+        dir: impl Into<Path>,
+    ) -> impl Stream<Item = Result<(Path, Attrs), Error>> + Send + Sync + 'static {
+        let dir = self.resolve_path(dir);
+        let open = self.opendir_names_only(dir.clone());
+
+        futures::stream::once(open)
+            .try_flatten()
+            .try_filter_map(move |entry| {
+                let item = match &*entry.filename {
+                    "." | ".." => None,
+                    _ => Some((dir.clone() / entry.filename, entry.attrs)),
+                };
+                std::future::ready(Ok(item))
+            })
+    }
+
+    /// Read a directory listing, with the attributes of all the entries.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read_dir_all_with_stat(&self, path: impl Into<Path>) -> Result<Name, Error>;
+    /// ```
+    ///
+    /// Some servers do not send the attributes of the entries when listing a directory.
+    /// Entries without any attribute are completed with concurrent `lstat` requests,
+    /// with at most [`SftpClientConfig::read_ahead`](crate::client::SftpClientConfig::read_ahead)
+    /// requests on-flight at the same time.
+    /// Entries removed between the listing and the `lstat` are kept with empty attributes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the directory to list
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn read_dir_all_with_stat(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<Name, Error>> + Send + Sync + 'static {
+        let max_concurrent_stats = self.config.read_ahead.max(1);
+        let path = self.resolve_path(path);
+        let readdir = self.readdir(path.clone());
         let client = self.clone();
-        let mut entries = Name::default();
 
         async move {
-            let handle = dir.await?;
+            let mut entries = readdir.await?;
+            let mut pending = FuturesUnordered::new();
+            let mut missing = entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| entry.attrs == Attrs::default())
+                .map(|(i, entry)| (i, path.clone() / &entry.filename))
+                .collect::<Vec<_>>()
+                .into_iter();
 
             loop {
-                match client.readdir_handle(handle.clone()).await {
-                    Ok(mut chunk) => entries.0.append(&mut chunk.0),
+                // Limit the number of on-flight requests
+                while pending.len() < max_concurrent_stats {
+                    let Some((i, path)) = missing.next() else {
+                        break;
+                    };
+                    pending.push(client.lstat(path).map(move |attrs| (i, attrs)));
+                }
+
+                let Some((i, attrs)) = pending.next().await else {
+                    break;
+                };
+
+                match attrs {
+                    Ok(attrs) => entries[i].attrs = attrs,
                     Err(Error::Sftp(Status {
-                        code: StatusCode::Eof,
+                        code: StatusCode::NoSuchFile,
                         ..
-                    })) => break,
-                    Err(err) => {
-                        _ = client.close(handle).await;
-                        return Err(err);
-                    }
+                    })) => (),
+                    Err(err) => return Err(err),
                 }
             }
 
-            client.close(handle).await?;
             Ok(entries)
         }
     }
@@ -514,7 +1143,10 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn readlink(&self, path: impl Into<Path>) -> SftpFuture<Path> {
         self.request_with(
-            ReadLink { path: path.into() }.to_request_message(),
+            ReadLink {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
             (),
             extract_path_from_name_message,
         )
@@ -538,12 +1170,45 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn realpath(&self, path: impl Into<Path>) -> SftpFuture<Path> {
         self.request_with(
-            RealPath { path: path.into() }.to_request_message(),
+            RealPath {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
             (),
             extract_path_from_name_message,
         )
     }
 
+    /// Canonicalize a path relative to a base directory.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn realpath_with_base(&self, path: impl Into<Path>, base: impl Into<Path>) -> Result<Path, Error>;
+    /// ```
+    ///
+    /// The path is joined to the base before being canonicalized by the server.
+    /// If the path is absolute, the base is ignored.
+    /// This is useful to resolve user-supplied relative paths against a working directory
+    /// tracked by the client, as SFTP has no notion of current directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to canonicalize
+    /// * `base`: Directory used to resolve `path` if it is relative
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn realpath_with_base(
+        &self,
+        path: impl Into<Path>,
+        base: impl Into<Path>,
+    ) -> SftpFuture<Path> {
+        self.realpath(base.into() / path.into())
+    }
+
     /// Remove a file.
     ///
     /// Equivalent to:
@@ -561,7 +1226,39 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn remove(&self, path: impl Into<Path>) -> SftpFuture {
-        self.request(Remove { path: path.into() })
+        self.request(Remove {
+            path: self.resolve_path(path),
+        })
+    }
+
+    /// Remove a file, if it exists.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn remove_if_exists(&self, path: impl Into<Path>) -> Result<bool, Error>;
+    /// ```
+    ///
+    /// Returns `true` if the file was removed, and `false` if it did not exist (`NoSuchFile`).
+    /// Other errors are returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the file to remove
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn remove_if_exists(&self, path: impl Into<Path>) -> SftpFuture<bool> {
+        self.request_with(
+            Remove {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
+            (),
+            existed_from_reply_message,
+        )
     }
 
     /// Rename/move a file or a directory.
@@ -583,11 +1280,66 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn rename(&self, old_path: impl Into<Path>, new_path: impl Into<Path>) -> SftpFuture {
         self.request(Rename {
-            old_path: old_path.into(),
-            new_path: new_path.into(),
+            old_path: self.resolve_path(old_path),
+            new_path: self.resolve_path(new_path),
         })
     }
 
+    /// Rename/move a file or a directory, replacing the destination if it already exists.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn rename_overwrite(&self, old_path: impl Into<Path>, new_path: impl Into<Path>, fallback: bool) -> Result<(), Error>;
+    /// ```
+    ///
+    /// A regular `rename` is tried first.
+    /// Strict servers refuse to rename onto an existing file and reply with a `Failure` status.
+    /// In that case, and only if `fallback` is `true`, the destination is removed and the rename is tried again.
+    /// Other errors (eg: `NoSuchFile` for a missing source) are returned as-is.
+    ///
+    /// The fallback path is *not* atomic:
+    /// if the second rename fails, the destination has already been removed,
+    /// and another client can create a new file at the destination between the two steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_path`: Current path of the file or directory to rename/move
+    /// * `new_path`: New path where the file or directory will be moved to
+    /// * `fallback`: Allow removing the destination when the rename fails
+    ///
+    /// # Cancel safety
+    ///
+    /// The first rename request is sent before the future is returned.
+    /// If the future is dropped while in the fallback path,
+    /// the destination might have been removed without the file being renamed.
+    pub fn rename_overwrite(
+        &self,
+        old_path: impl Into<Path>,
+        new_path: impl Into<Path>,
+        fallback: bool,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        let old_path = self.resolve_path(old_path);
+        let new_path = self.resolve_path(new_path);
+        let rename = self.rename(old_path.clone(), new_path.clone());
+        let client = self.clone();
+
+        async move {
+            match rename.await {
+                Err(Error::Sftp(Status {
+                    code: StatusCode::Failure,
+                    ..
+                })) if fallback => {
+                    if let Err(err) = client.remove(new_path.clone()).await {
+                        log::debug!("Could not remove rename destination {new_path:?}: {err:?}");
+                    }
+                    client.rename(old_path, new_path).await
+                }
+                result => result,
+            }
+        }
+    }
+
     /// Remove an existing directory.
     ///
     /// Equivalent to:
@@ -609,7 +1361,39 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn rmdir(&self, path: impl Into<Path>) -> SftpFuture {
-        self.request(RmDir { path: path.into() })
+        self.request(RmDir {
+            path: self.resolve_path(path),
+        })
+    }
+
+    /// Remove an existing directory, if it exists.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn rmdir_if_exists(&self, path: impl Into<Path>) -> Result<bool, Error>;
+    /// ```
+    ///
+    /// Returns `true` if the directory was removed, and `false` if it did not exist (`NoSuchFile`).
+    /// Other errors (eg: the directory is not empty) are returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the directory to remove
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn rmdir_if_exists(&self, path: impl Into<Path>) -> SftpFuture<bool> {
+        self.request_with(
+            RmDir {
+                path: self.resolve_path(path),
+            }
+            .to_request_message(),
+            (),
+            existed_from_reply_message,
+        )
     }
 
     /// Change the attributes (metadata) of a file or directory.
@@ -637,11 +1421,45 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn setstat(&self, path: impl Into<Path>, attrs: Attrs) -> SftpFuture {
         self.request(SetStat {
-            path: path.into(),
+            path: self.resolve_path(path),
             attrs,
         })
     }
 
+    /// Change the access and modification times of a file or directory.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn set_times(&self, path: impl Into<Path>, atime: SystemTime, mtime: SystemTime) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Times are truncated to the second.
+    /// An error is returned if a time cannot be represented in the SFTP protocol
+    /// (before 1970, or after 2106).
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path of the file or directory to change the times
+    /// * `atime`: New access time
+    /// * `mtime`: New modification time
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn set_times(
+        &self,
+        path: impl Into<Path>,
+        atime: SystemTime,
+        mtime: SystemTime,
+    ) -> SftpFuture {
+        match times_to_attrs(atime, mtime) {
+            Ok(attrs) => self.setstat(path, attrs),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
     /// Read the attributes (metadata) of a file or directory.
     ///
     /// Equivalent to:
@@ -650,7 +1468,7 @@ impl SftpClient {
     /// async fn stat(&self, path: impl Into<Path>) -> Result<Attrs, Error>;
     /// ```
     ///
-    /// Symbolic links *are not* followed.
+    /// Symbolic links are followed.
     ///
     /// # Arguments
     ///
@@ -661,7 +1479,144 @@ impl SftpClient {
     /// It is safe to cancel the future.
     /// However, the request is actually sent before the future is returned.
     pub fn stat(&self, path: impl Into<Path>) -> SftpFuture<Attrs> {
-        self.request(Stat { path: path.into() })
+        self.request(Stat {
+            path: self.resolve_path(path),
+        })
+    }
+
+    /// Read the attributes (metadata) of many files or directories concurrently.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn stat_many(&self, paths: Vec<Path>, limit: usize) -> Vec<Result<Attrs, Error>>;
+    /// ```
+    ///
+    /// At most `limit` `stat` requests are on-flight at the same time:
+    /// a new request is sent as soon as the reply of the oldest one is received.
+    /// The results are returned in the same order as `paths`,
+    /// and the failure of a path does not prevent the others from being stat-ed.
+    ///
+    /// Symbolic links are followed.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Paths of the files or directories
+    /// * `limit`: Maximal number of requests on-flight at the same time (at least 1)
+    ///
+    /// # Cancel safety
+    ///
+    /// The first `limit` requests are sent before the future is returned.
+    /// If the future is dropped, the remaining paths are not stat-ed.
+    pub fn stat_many(
+        &self,
+        paths: Vec<Path>,
+        limit: usize,
+    ) -> impl Future<Output = Vec<Result<Attrs, Error>>> + Send + Sync + 'static {
+        self.attrs_many(paths, limit, |client, path| client.stat(path))
+    }
+
+    /// Read the attributes (metadata) of many files or directories concurrently, keyed by path.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn stat_many_map(&self, paths: Vec<Path>, limit: usize) -> HashMap<Path, Result<Attrs, Error>>;
+    /// ```
+    ///
+    /// Same as `stat_many()`, but the results are looked up by path instead of by position.
+    /// Duplicate paths collapse into a single entry of the map, and are stat-ed only once.
+    /// The keys are the paths as given, even if the client has a working directory.
+    ///
+    /// Symbolic links are followed: use `lstat_many_map()` to read the attributes of the links themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Paths of the files or directories
+    /// * `limit`: Maximal number of requests on-flight at the same time (at least 1)
+    ///
+    /// # Cancel safety
+    ///
+    /// The first `limit` requests are sent before the future is returned.
+    /// If the future is dropped, the remaining paths are not stat-ed.
+    pub fn stat_many_map(
+        &self,
+        paths: Vec<Path>,
+        limit: usize,
+    ) -> impl Future<Output = HashMap<Path, Result<Attrs, Error>>> + Send + Sync + 'static {
+        self.attrs_many_map(paths, limit, |client, path| client.stat(path))
+    }
+
+    /// Read the attributes (metadata) of many files, directories or symbolic links concurrently, keyed by path.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn lstat_many_map(&self, paths: Vec<Path>, limit: usize) -> HashMap<Path, Result<Attrs, Error>>;
+    /// ```
+    ///
+    /// Same as `stat_many_map()`, but with `lstat` requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths`: Paths of the files, directories or symbolic links
+    /// * `limit`: Maximal number of requests on-flight at the same time (at least 1)
+    ///
+    /// # Cancel safety
+    ///
+    /// The first `limit` requests are sent before the future is returned.
+    /// If the future is dropped, the remaining paths are not stat-ed.
+    pub fn lstat_many_map(
+        &self,
+        paths: Vec<Path>,
+        limit: usize,
+    ) -> impl Future<Output = HashMap<Path, Result<Attrs, Error>>> + Send + Sync + 'static {
+        self.attrs_many_map(paths, limit, |client, path| client.lstat(path))
+    }
+
+    /// Send `stat` for every path, with at most `limit` requests on-flight, and keep the results in order.
+    fn attrs_many(
+        &self,
+        paths: Vec<Path>,
+        limit: usize,
+        stat: fn(&SftpClient, Path) -> SftpFuture<Attrs>,
+    ) -> impl Future<Output = Vec<Result<Attrs, Error>>> + Send + Sync + 'static {
+        let count = paths.len();
+        let mut paths = paths.into_iter();
+        let mut pending = paths
+            .by_ref()
+            .take(limit.max(1))
+            .map(|path| stat(self, path))
+            .collect::<FuturesOrdered<_>>();
+        let client = self.clone();
+
+        async move {
+            let mut results = Vec::with_capacity(count);
+
+            while let Some(result) = pending.next().await {
+                results.push(result);
+
+                if let Some(path) = paths.next() {
+                    pending.push_back(stat(&client, path));
+                }
+            }
+
+            results
+        }
+    }
+
+    /// Send `stat` once for every distinct path, and key the results by path.
+    fn attrs_many_map(
+        &self,
+        mut paths: Vec<Path>,
+        limit: usize,
+        stat: fn(&SftpClient, Path) -> SftpFuture<Attrs>,
+    ) -> impl Future<Output = HashMap<Path, Result<Attrs, Error>>> + Send + Sync + 'static {
+        let mut seen = HashSet::with_capacity(paths.len());
+        paths.retain(|path| seen.insert(path.clone()));
+        let results = self.attrs_many(paths.clone(), limit, stat);
+
+        async move { paths.into_iter().zip(results.await).collect() }
     }
 
     /// Create a symbolic link.
@@ -672,6 +1627,11 @@ impl SftpClient {
     /// async fn symlink(&self, link_path: impl Into<Path>, target_path: impl Into<Path>) -> Result<(), Error>;
     /// ```
     ///
+    /// Arguments are in the order of the wire format: the link first, then the target.
+    /// This is the reverse of POSIX `ln -s target link`.
+    /// OpenSSH `sftp-server` reads the two paths in the reverse order.
+    /// Prefer `create_symlink()` that follows the POSIX order, and handles both orders of the servers.
+    ///
     /// # Arguments
     ///
     /// * `link_path`: Path name of the symbolic link to be created
@@ -683,11 +1643,46 @@ impl SftpClient {
     /// However, the request is actually sent before the future is returned.
     pub fn symlink(&self, link_path: impl Into<Path>, target_path: impl Into<Path>) -> SftpFuture {
         self.request(Symlink {
-            link_path: link_path.into(),
+            link_path: self.resolve_path(link_path),
             target_path: target_path.into(),
         })
     }
 
+    /// Create a symbolic link, with arguments in the POSIX order.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn create_symlink(&self, target: impl Into<Path>, link: impl Into<Path>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Arguments are in the same order as `ln -s target link`.
+    ///
+    /// OpenSSH `sftp-server` swaps the two paths of the request.
+    /// The server is considered to be OpenSSH if it advertises any `@openssh.com` extension
+    /// during the handshake (eg: `posix-rename@openssh.com`), and the paths are then sent in its order,
+    /// so that the link is always created at `link`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: Target of the symbolic link
+    /// * `link`: Path name of the symbolic link to be created
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn create_symlink(&self, target: impl Into<Path>, link: impl Into<Path>) -> SftpFuture {
+        if self.openssh {
+            self.request(Symlink {
+                link_path: target.into(),
+                target_path: self.resolve_path(link),
+            })
+        } else {
+            self.symlink(link, target)
+        }
+    }
+
     /// Write to a portion of an opened file.
     ///
     /// Equivalent to:
@@ -696,6 +1691,9 @@ impl SftpClient {
     /// async fn write(&self, handle: Handle, offset: u64, data: impl Into<Data>,) -> Result<(), Error>;
     /// ```
     ///
+    /// The data is sent in a single request: servers can reject writes larger than their maximal write length.
+    /// Use [`SftpClient::write_all_at`] to split large buffers.
+    ///
     /// # Arguments
     ///
     /// * `handle`: Handle of the file to write to
@@ -715,6 +1713,20 @@ impl SftpClient {
     }
 }
 
+/// Build attributes with only the access and modification times set.
+pub(super) fn times_to_attrs(atime: SystemTime, mtime: SystemTime) -> Result<Attrs, Error> {
+    match Time::from_system_times(atime, mtime) {
+        Some(time) => Ok(Attrs {
+            time: Some(time),
+            ..Attrs::new()
+        }),
+        None => Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Time cannot be represented as a 32 bits UNIX timestamp",
+        ))),
+    }
+}
+
 /// Convert a SFTP message into [`Name`], and extract its only entry.
 /// It fails if the message is not a [`Name`], or if it has not exactly one entry.
 fn extract_path_from_name_message(_: (), msg: Message) -> Result<Path, Error> {
@@ -726,3 +1738,28 @@ fn extract_path_from_name_message(_: (), msg: Message) -> Result<Path, Error> {
         )),
     }
 }
+
+/// Convert a status into `true` if successful, or `false` if the target did not exist.
+fn existed_from_reply_message(_: (), msg: Message) -> Result<bool, Error> {
+    match <()>::from_reply_message(msg) {
+        Ok(()) => Ok(true),
+        Err(Error::Sftp(Status {
+            code: StatusCode::NoSuchFile,
+            ..
+        })) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Keep only the permissions and the ownership of `attrs`, if any.
+fn permissions(attrs: Attrs) -> Option<Attrs> {
+    if attrs.perms.is_none() && attrs.owner.is_none() {
+        return None;
+    }
+
+    Some(Attrs {
+        perms: attrs.perms,
+        owner: attrs.owner,
+        ..Default::default()
+    })
+}
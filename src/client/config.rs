@@ -0,0 +1,185 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::client::Error;
+
+/// Tuning parameters of a [`SftpClient`](crate::client::SftpClient).
+///
+/// The configuration is given when the client is created
+/// (see [`SftpClient::new_with_config`](crate::client::SftpClient::new_with_config)
+/// and [`SftpClient::with_stream_config`](crate::client::SftpClient::with_stream_config)),
+/// and is shared by all the clones of the client.
+///
+/// Only the settings of the whole session that have a dedicated method can be changed afterwards
+/// (eg: [`SftpClient::with_rate_limit`](crate::client::SftpClient::with_rate_limit)).
+/// Such a method applies the new setting to the whole session,
+/// and updates the configuration of the client it returns, and of the clones made from it.
+/// The clones made before keep reporting the previous setting.
+///
+/// The default configuration matches the behavior of [`SftpClient::new`](crate::client::SftpClient::new).
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn dummy(ssh: russh::client::Handle<impl russh::client::Handler>) -> Result<(), rusftp::client::Error> {
+/// let config = rusftp::client::SftpClientConfig {
+///     request_timeout: Some(std::time::Duration::from_secs(30)),
+///     max_in_flight: 64,
+///     ..Default::default()
+/// };
+/// let sftp = rusftp::client::SftpClient::new_with_config(&ssh, config).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SftpClientConfig {
+    /// Maximal size of a single read or write request issued by files and transfers
+    ///
+    /// When the limits of the server are known (see [`SftpClientConfig::use_server_limits`]),
+    /// the maximal read and write lengths of the server are used instead.
+    pub chunk_size: u32,
+    /// Maximal number of read requests on-flight for a single download,
+    /// and of `lstat` requests on-flight for [`SftpClient::read_dir_all_with_stat`](crate::client::SftpClient::read_dir_all_with_stat)
+    pub read_ahead: usize,
+    /// Maximal number of write requests on-flight for a single upload
+    pub write_pipeline: usize,
+    /// Maximal time to wait for the reply of a request (no timeout if `None`)
+    ///
+    /// Requests that time out fail with [`TimedOut`](std::io::ErrorKind::TimedOut),
+    /// and their late replies are discarded.
+    pub request_timeout: Option<Duration>,
+    /// Maximal idle time while receiving a frame (no timeout if `None`)
+    ///
+    /// If the server starts sending a frame, but does not send any more bytes for this long,
+    /// the client is stopped, and all the pending requests fail.
+    /// This protects the client against misbehaving servers that send frames byte by byte.
+    /// The timeout does not apply while the client is waiting for a new frame.
+    pub read_timeout: Option<Duration>,
+    /// Minimal number of bytes requested from the stream at each read
+    ///
+    /// When the size of an incoming frame is known, the remaining of the frame is read at once.
+    /// Otherwise, at least this many bytes are requested from the stream,
+    /// which allows to receive multiple small frames with a single read.
+    pub min_read_size: usize,
+    /// Maximal size of a frame received from the server
+    ///
+    /// Receiving a larger frame stops the client, and all the pending requests fail.
+    pub max_frame_size: u32,
+    /// Maximal number of requests on-flight for the whole client
+    ///
+    /// Further requests are queued until some replies are received.
+    pub max_in_flight: usize,
+    /// Maximal average number of payload bytes per second for reads and writes (unlimited if `None`)
+    ///
+    /// See [`SftpClient::with_rate_limit`](crate::client::SftpClient::with_rate_limit).
+    pub rate_limit: Option<u64>,
+    /// Extensions advertised to the server in the `Init` packet (name and data)
+    ///
+    /// The server must ignore the extensions it does not recognize.
+    /// No extension is advertised by default.
+    pub init_extensions: BTreeMap<Bytes, Bytes>,
+    /// Query the limits of the server during the handshake, if it supports the `limits@openssh.com` extension
+    ///
+    /// The maximal read and write lengths of the server replace [`SftpClientConfig::chunk_size`].
+    /// It costs an extra round-trip during the handshake, so it is disabled by default.
+    /// See [`SftpClient::limits`](crate::client::SftpClient::limits).
+    pub use_server_limits: bool,
+    /// Hide the paths, file names and data of the messages traced in the logs
+    ///
+    /// Redacted contents are replaced by their length and a short hash,
+    /// so identical paths can still be correlated across log lines.
+    pub redact_logs: bool,
+    /// Stop the client once no request has been on-flight for this long (never stopped if `None`)
+    ///
+    /// See [`SftpClient::with_idle_timeout`](crate::client::SftpClient::with_idle_timeout).
+    pub idle_timeout: Option<Duration>,
+    /// Maximal time to wait for the server to reply to the `Init` packet (no timeout if `None`)
+    ///
+    /// If the server does not send its `Version` in time, the client is not created,
+    /// and the error is [`TimedOut`](std::io::ErrorKind::TimedOut).
+    /// This protects the callers (eg: connection pools running health checks)
+    /// against broken servers that never complete the handshake.
+    ///
+    /// Only the `Init`/`Version` exchange is bounded:
+    /// opening the SSH channel and requesting the subsystem are not,
+    /// and the query of the server limits is bounded by [`SftpClientConfig::request_timeout`].
+    pub handshake_timeout: Option<Duration>,
+    /// Time during which the replies of `stat`, `lstat` and `realpath` requests are reused
+    ///
+    /// See [`SftpClient::with_metadata_cache`](crate::client::SftpClient::with_metadata_cache).
+    pub metadata_cache_ttl: Duration,
+    /// Maximal number of cached replies of `stat`, `lstat` and `realpath` requests (no cache if 0)
+    ///
+    /// See [`SftpClient::with_metadata_cache`](crate::client::SftpClient::with_metadata_cache).
+    pub metadata_cache_capacity: usize,
+}
+
+impl SftpClientConfig {
+    /// Create the default configuration.
+    pub const fn new() -> Self {
+        Self {
+            chunk_size: 32768,
+            read_ahead: 16,
+            write_pipeline: 16,
+            request_timeout: None,
+            read_timeout: None,
+            min_read_size: 1024,
+            max_frame_size: u32::MAX,
+            max_in_flight: usize::MAX,
+            rate_limit: None,
+            init_extensions: BTreeMap::new(),
+            use_server_limits: false,
+            redact_logs: false,
+            idle_timeout: None,
+            handshake_timeout: None,
+            metadata_cache_ttl: Duration::ZERO,
+            metadata_cache_capacity: 0,
+        }
+    }
+
+    /// Check that the configuration can be used by a client.
+    pub(super) fn validate(&self) -> Result<(), Error> {
+        let invalid = if self.chunk_size == 0 {
+            "chunk size must not be 0"
+        } else if self.read_ahead == 0 {
+            "read-ahead depth must not be 0"
+        } else if self.write_pipeline == 0 {
+            "write pipeline depth must not be 0"
+        } else if self.min_read_size == 0 {
+            "minimal read size must not be 0"
+        } else if self.max_in_flight == 0 {
+            "maximal number of on-flight requests must not be 0"
+        } else {
+            return Ok(());
+        };
+
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid SFTP client configuration: {invalid}"),
+        )))
+    }
+}
+
+impl Default for SftpClientConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,93 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::Future;
+
+use crate::client::{Error, SftpClient, StatusCode};
+use crate::message::{Path, Permisions};
+
+impl SftpClient {
+    /// Change the working directory of the client.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn cd(&mut self, path: impl Into<Path>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// SFTP has no notion of current directory: relative paths are resolved by the server
+    /// against the login directory.
+    /// Once a working directory is set, all the commands of this client prepend it to relative paths
+    /// before sending them to the server.
+    /// The targets of symbolic links are the only paths that are sent as-is.
+    ///
+    /// The path is canonicalized by the server, and must be a directory.
+    /// The working directory is not shared with the clones of the client
+    /// that have been created before the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: New working directory, relative to the current one
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// The working directory is left unchanged if the future is dropped before completion.
+    pub fn cd(
+        &mut self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + '_ {
+        let realpath = self.realpath(path);
+
+        async move {
+            let path = realpath.await?;
+            let attrs = self.stat(path.clone()).await?;
+
+            if let Some(perms) = attrs.perms {
+                if perms.bits() & 0xF000 != Permisions::DIR.bits() {
+                    return Err(StatusCode::Failure
+                        .to_status(format!("Not a directory: {}", path.as_ref() as &str))
+                        .into());
+                }
+            }
+
+            self.cwd = Some(path);
+            Ok(())
+        }
+    }
+
+    /// Get the working directory of the client, if any.
+    ///
+    /// See [`SftpClient::cd`].
+    pub fn pwd(&self) -> Option<&Path> {
+        self.cwd.as_ref()
+    }
+
+    /// Remove the working directory of the client.
+    ///
+    /// Relative paths are then resolved by the server against the login directory.
+    pub fn reset_cwd(&mut self) {
+        self.cwd = None;
+    }
+
+    /// Prepend the working directory to `path`, if it is relative.
+    pub(super) fn resolve_path(&self, path: impl Into<Path>) -> Path {
+        match &self.cwd {
+            Some(cwd) => cwd.clone() / path.into(),
+            None => path.into(),
+        }
+    }
+}
@@ -55,10 +55,82 @@ impl Dir {
     pub fn close(&mut self) -> impl Future<Output = Result<(), Error>> + Drop + Send + Sync + '_ {
         DirClosing::new(self)
     }
+
+    /// Close the remote directory, and consume it.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn finish(self) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Dropping a directory also closes it, but without waiting for the server,
+    /// so any error while closing is lost.
+    /// `finish` is the way to observe those errors.
+    ///
+    /// See [`Dir::close`].
+    ///
+    /// # Cancel safety
+    ///
+    /// The closing request is done before returning the future.
+    /// If the future is dropped before completion, the directory is closed as if it was dropped.
+    pub fn finish(mut self) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        self.buffer = None;
+        self.pending = None;
+        let pending = self.handle.take().map(|handle| self.client.close(handle));
+
+        async move {
+            if let Some(pending) = pending {
+                pending.await?;
+            }
+            SftpClientStopping::new(&mut self.client).await;
+            Ok(())
+        }
+    }
+
+    /// Close the remote directory, without stopping the underlying sftp client.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn close_handle_only(&mut self) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Contrary to `close()`, the SFTP session is kept alive even if this directory
+    /// holds the last client of the session.
+    /// The session is stopped only when the directory is dropped.
+    ///
+    /// Pending Read operation is cancelled, if any.
+    ///
+    /// If the directory has already been closed, the future returns an error.
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn close_handle_only(&mut self) -> SftpFuture {
+        self.buffer = None;
+        self.pending = None;
+
+        if let Some(handle) = self.handle.take() {
+            self.client.close(handle)
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "Dir was already closed",
+            )))
+        }
+    }
 }
 
+/// Dropping a directory is only a best-effort fallback to [`Dir::finish`] or [`Dir::close`]:
+/// the close request is sent without waiting for its reply, and any error is lost.
+/// Dropping never blocks, so it is safe to drop a directory within an async context.
 impl Drop for Dir {
     fn drop(&mut self) {
+        if self.handle.is_some() {
+            log::warn!("Dir dropped while still open: closing it in the background");
+        }
         DirClosing::new(self).forget()
     }
 }
@@ -0,0 +1,135 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::SystemTime;
+
+use futures::{Stream, StreamExt};
+
+use crate::client::Error;
+use crate::message::{Attrs, NameEntry, Path, Permisions};
+
+use super::Dir;
+
+/// Type of a file, as reported by the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    /// Regular file
+    File,
+    /// Directory
+    Dir,
+    /// Symbolic link
+    Symlink,
+    /// FIFO (pipe)
+    Fifo,
+    /// Character device
+    CharDevice,
+    /// Block device
+    BlockDevice,
+    /// UNIX socket
+    Socket,
+    /// The type is unknown, or was not sent by the server
+    #[default]
+    Unknown,
+}
+
+impl FileType {
+    /// Get the file type from the type bits of the permissions.
+    pub fn from_permissions(perms: Permisions) -> Self {
+        match perms.bits() & 0xF000 {
+            0x1000 => FileType::Fifo,
+            0x2000 => FileType::CharDevice,
+            0x4000 => FileType::Dir,
+            0x6000 => FileType::BlockDevice,
+            0x8000 => FileType::File,
+            0xA000 => FileType::Symlink,
+            0xC000 => FileType::Socket,
+            _ => FileType::Unknown,
+        }
+    }
+
+    /// Check whether the file is a regular file.
+    pub fn is_file(&self) -> bool {
+        *self == FileType::File
+    }
+
+    /// Check whether the file is a directory.
+    pub fn is_dir(&self) -> bool {
+        *self == FileType::Dir
+    }
+
+    /// Check whether the file is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        *self == FileType::Symlink
+    }
+}
+
+/// Entry of a directory, with its attributes parsed into friendly fields.
+///
+/// It is the higher-level counterpart of [`NameEntry`], à-la [`std::fs::DirEntry`].
+/// Attributes that were not sent by the server are `None`.
+///
+/// It can be converted from a [`NameEntry`] with `From` or `TryFrom`, as the conversion cannot fail.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct DirEntry {
+    /// Name of the entry within its directory
+    pub name: Path,
+    /// Type of the entry
+    pub file_type: FileType,
+    /// Size of the entry in bytes
+    pub len: Option<u64>,
+    /// POSIX permission bits of the entry, without the file type (eg: `0o755`)
+    pub permissions: Option<u32>,
+    /// Modification time of the entry
+    pub modified: Option<SystemTime>,
+}
+
+impl DirEntry {
+    /// Create an entry from its name and its raw attributes.
+    pub fn new(name: Path, attrs: &Attrs) -> Self {
+        DirEntry {
+            name,
+            file_type: attrs
+                .perms
+                .map(FileType::from_permissions)
+                .unwrap_or_default(),
+            len: attrs.size,
+            permissions: attrs.perms.map(|perms| perms.bits() & 0o7777),
+            modified: attrs.time.map(|time| time.modification_time()),
+        }
+    }
+}
+
+impl From<NameEntry> for DirEntry {
+    fn from(value: NameEntry) -> Self {
+        DirEntry::new(value.filename, &value.attrs)
+    }
+}
+
+impl From<&NameEntry> for DirEntry {
+    fn from(value: &NameEntry) -> Self {
+        DirEntry::new(value.filename.clone(), &value.attrs)
+    }
+}
+
+impl Dir {
+    /// Convert the directory into a stream of [`DirEntry`].
+    ///
+    /// The long names of the entries are not kept.
+    pub fn into_entries(self) -> impl Stream<Item = Result<DirEntry, Error>> + Send + Sync {
+        self.with_long_names(false)
+            .map(|entry| entry.map(DirEntry::from))
+    }
+}
@@ -16,39 +16,153 @@
 
 //! [`Dir`] module.
 
-use crate::client::{SftpClient, SftpFuture};
-use crate::message::{Handle, Name};
+use std::collections::HashSet;
+
+use crate::client::{Error, HandleKind, HandleOrigin, SftpClient, SftpFuture};
+use crate::message::{Handle, Name, Path};
 
 mod close;
+mod entry;
 mod stream;
 
+pub use entry::{DirEntry, FileType};
+
 /// Directory accessible remotely with SFTP
 pub struct Dir {
     client: SftpClient,
     handle: Option<Handle>,
+    origin: HandleOrigin,
     buffer: Option<Name>,
     pending: Option<SftpFuture<Name>>,
+    long_names: bool,
+    position: u64,
+    seen: Option<HashSet<Path>>,
 }
 
 impl Dir {
     /// Create a directory from a raw [`Handle`].
     ///
-    /// The handle must come from `SftpClient::opendir`.
-    ///
-    /// The remote dir will be closed when the object is dropped.
+    /// Equivalent to [`Dir::from_handle`].
     ///
     /// # Arguments
     ///
     /// * `handle` - Handle of the open directory
     pub fn new(client: SftpClient, handle: Handle) -> Self {
+        Self::from_handle(client, handle)
+    }
+
+    /// Create a directory from a raw [`Handle`].
+    ///
+    /// This is the bridge between the raw handle API and the [`Stream`](futures::Stream) API:
+    /// a handle obtained with [`SftpClient::opendir_handle`] can be wrapped into a [`Dir`]
+    /// to iterate over its entries.
+    ///
+    /// The [`Dir`] takes ownership of the handle:
+    /// the remote dir will be closed when the object is dropped,
+    /// so the handle must not be closed by other means.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client the handle was opened with
+    /// * `handle` - Handle of the open directory
+    pub fn from_handle(client: SftpClient, handle: Handle) -> Self {
         Dir {
+            origin: HandleOrigin::new(&client, HandleKind::Dir),
             client,
             handle: Some(handle),
             buffer: Some(Default::default()),
             pending: None,
+            long_names: true,
+            position: 0,
+            seen: None,
         }
     }
 
+    /// Get the raw [`Handle`] of the directory.
+    ///
+    /// Returns `None` if the directory is closed.
+    /// The handle is still owned by the [`Dir`], and is closed when the object is dropped.
+    pub fn handle(&self) -> Option<&Handle> {
+        self.handle.as_ref()
+    }
+
+    /// Get the raw [`Handle`] of the directory, to send raw requests with `client`.
+    ///
+    /// See [`File::handle_for`](crate::client::File::handle_for).
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client the handle will be used with
+    pub fn handle_for(&self, client: &SftpClient) -> Result<Handle, Error> {
+        self.origin.check(client, self.handle(), HandleKind::Dir)
+    }
+
+    /// Choose whether the entries should keep their [`long_name`](crate::message::NameEntry::long_name).
+    ///
+    /// When disabled, the long names sent by the server are dropped as soon as a batch of entries is received,
+    /// and the yielded entries have an empty `long_name`.
+    /// On directories with millions of entries, this saves one allocation per entry,
+    /// which is usually as large as the filename and the attributes combined.
+    ///
+    /// Long names are kept by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `long_names` - Whether the long names should be kept
+    pub fn with_long_names(mut self, long_names: bool) -> Self {
+        self.long_names = long_names;
+        self
+    }
+
+    /// Choose whether the entries whose filename has already been yielded should be skipped.
+    ///
+    /// Buggy servers can return the same entry in multiple `ReadDir` batches,
+    /// which makes recursive operations process it several times.
+    /// When enabled, the filename of every yielded entry is remembered,
+    /// so the memory used grows with the number of entries in the directory.
+    /// It can be left disabled for trusted servers.
+    ///
+    /// Duplicates are not skipped by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `dedup` - Whether the duplicated entries should be skipped
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.seen = dedup.then(HashSet::new);
+        self
+    }
+
+    /// Get the number of entries already consumed from the directory.
+    ///
+    /// The entries yielded by the [`Stream`](futures::Stream) and the ones appended by [`Dir::poll_fill`] are counted.
+    /// This is meant for progress reporting (eg: "1234/?? files scanned"),
+    /// as the total number of entries is not known until the end of the directory is reached.
+    ///
+    /// The position cannot be used to resume the enumeration:
+    /// SFTP handles are bound to the session, and the server does not support seeking in a directory.
+    /// Resuming after a reconnection requires listing the directory again, and skipping the entries already processed.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Check whether the directory will not yield any more entries.
+    ///
+    /// This is the case once the server has signaled the end of the directory with an `Eof` status,
+    /// after an error has been yielded, or once the directory has been closed.
+    /// A directory that has not been read yet is not exhausted, even if it is empty:
+    /// the end is only known after the server has been asked for more entries.
+    pub fn is_exhausted(&self) -> bool {
+        self.buffer.is_none()
+    }
+
+    /// Get the number of entries already received from the server, but not yet consumed.
+    ///
+    /// These entries are yielded without any request to the server.
+    /// A batch being [prefetched](Dir::prefetch) is not counted until it has been received.
+    pub fn remaining_buffered(&self) -> usize {
+        self.buffer.as_ref().map_or(0, |buffer| buffer.len())
+    }
+
     /// Create a closed directory.
     ///
     /// The directory cannot be opened by any means.
@@ -56,8 +170,12 @@ impl Dir {
         Dir {
             client: SftpClient::new_stopped(),
             handle: None,
+            origin: HandleOrigin::STOPPED_DIR,
             buffer: None,
             pending: None,
+            long_names: true,
+            position: 0,
+            seen: None,
         }
     }
 }
@@ -71,6 +189,9 @@ impl std::fmt::Debug for Dir {
             .field("handle", &self.handle)
             .field("buffer", &self.buffer)
             .field("pending", &self.pending.as_ref().map(|_| "..."))
+            .field("long_names", &self.long_names)
+            .field("position", &self.position)
+            .field("seen", &self.seen.as_ref().map(HashSet::len))
             .finish()
     }
 }
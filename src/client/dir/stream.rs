@@ -16,83 +16,209 @@
 
 use std::future::Future;
 use std::pin::Pin;
-use std::task::ready;
+use std::task::{ready, Poll};
 
 use crate::client::Error;
-use crate::message::{NameEntry, ReadDir, Status, StatusCode};
+use crate::message::{Name, NameEntry, ReadDir, Status, StatusCode};
 
 use super::Dir;
 
+impl Dir {
+    /// Append the next entries of the directory into `entries`.
+    ///
+    /// The entries already buffered by the directory are appended first.
+    /// Otherwise, a single `ReadDir` request is sent to the server, and the whole batch is appended.
+    /// Returns the number of appended entries, which is 0 once the end of the directory is reached.
+    ///
+    /// Contrary to the [`Stream`](futures::Stream) implementation,
+    /// the caller can clear and reuse the same buffer between the batches,
+    /// which avoids growing a new vector for every batch when listing huge directories.
+    ///
+    /// The entries are appended in the same order as they are yielded by the [`Stream`](futures::Stream).
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Buffer where the entries are appended
+    pub fn poll_fill(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+        entries: &mut Vec<NameEntry>,
+    ) -> Poll<Result<usize, Error>> {
+        // If end of file reached, no entries are appended
+        let Some(buffer) = &mut self.buffer else {
+            return Poll::Ready(Ok(0));
+        };
+
+        // If still some entries in the buffer, move them
+        if !buffer.is_empty() {
+            let len = buffer.len();
+            entries.extend(buffer.0.drain(..).rev());
+            self.position += len as u64;
+            return Poll::Ready(Ok(len));
+        }
+
+        match ready!(self.poll_batch(cx)) {
+            Some(Ok(mut batch)) => {
+                self.buffer = Some(Default::default());
+                let len = batch.len();
+                entries.append(&mut batch.0);
+                self.position += len as u64;
+                Poll::Ready(Ok(len))
+            }
+            Some(Err(err)) => Poll::Ready(Err(err)),
+            None => Poll::Ready(Ok(0)),
+        }
+    }
+
+    /// Append the next entries of the directory into `entries`.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn fill(&mut self, entries: &mut Vec<NameEntry>) -> Result<usize, Error>;
+    /// ```
+    ///
+    /// See [`Dir::poll_fill`].
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Buffer where the entries are appended
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// The pending `ReadDir` request is kept, and its entries are returned by the next call.
+    pub fn fill<'a>(
+        &'a mut self,
+        entries: &'a mut Vec<NameEntry>,
+    ) -> impl Future<Output = Result<usize, Error>> + Send + Sync + 'a {
+        futures::future::poll_fn(move |cx| self.poll_fill(cx, entries))
+    }
+
+    /// Send the next `ReadDir` request now, instead of waiting for the buffered entries to be consumed.
+    ///
+    /// The batch is received in the background, and is yielded once the entries already buffered are consumed.
+    /// The settings of the directory (eg: [`Dir::with_dedup`]) still apply to the prefetched entries.
+    ///
+    /// Nothing is sent if a request is already pending, or if the directory is closed or exhausted.
+    pub fn prefetch(&mut self) {
+        if self.pending.is_some() || self.buffer.is_none() {
+            return;
+        }
+
+        if let Some(handle) = &self.handle {
+            self.pending = Some(self.client.request(ReadDir {
+                handle: handle.clone(),
+            }));
+        }
+    }
+
+    /// Poll the next batch of entries from the server.
+    ///
+    /// The buffer is reset to `None` to stop the iteration, and must be set again by the caller if the iteration continues.
+    fn poll_batch(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<Result<Name, Error>>> {
+        loop {
+            let result = match &mut self.pending {
+                Some(pending) => {
+                    ready!(Pin::new(pending).poll(cx))
+                }
+                None => {
+                    let Some(handle) = &self.handle else {
+                        // Force end of iteration
+                        self.buffer = None;
+                        return Poll::Ready(Some(Err(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "Dir was closed",
+                        )
+                        .into())));
+                    };
+
+                    let readdir = self.client.request(ReadDir {
+                        handle: handle.clone(),
+                    });
+                    let pending = self.pending.insert(readdir);
+
+                    ready!(Pin::new(pending).poll(cx))
+                }
+            };
+
+            // Polling has finished, resetting pending
+            self.pending = None;
+
+            // If the read was successful, the buffer will be populated again
+            // Stop the iteration otherwise
+            self.buffer = None;
+
+            let result = match result {
+                Ok(mut entries) => {
+                    if !self.long_names {
+                        for entry in &mut entries {
+                            entry.long_name = Default::default();
+                        }
+                    }
+
+                    if entries.is_empty() {
+                        Some(Err(std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "Found no more directory entries while it was expecting some",
+                        )
+                        .into()))
+                    } else if let Some(seen) = &mut self.seen {
+                        entries
+                            .0
+                            .retain(|entry| seen.insert(entry.filename.clone()));
+
+                        // The whole batch was already seen: get the next one
+                        if entries.is_empty() {
+                            continue;
+                        }
+                        Some(Ok(entries))
+                    } else {
+                        Some(Ok(entries))
+                    }
+                }
+                Err(Error::Sftp(Status {
+                    code: StatusCode::Eof,
+                    ..
+                })) => None,
+                Err(err) => Some(Err(err)),
+            };
+
+            return Poll::Ready(result);
+        }
+    }
+}
+
 impl futures::Stream for Dir {
     type Item = Result<NameEntry, Error>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
+    ) -> Poll<Option<Self::Item>> {
         // If end of file reached, return None
         let Some(buffer) = &mut self.buffer else {
-            return std::task::Poll::Ready(None);
+            return Poll::Ready(None);
         };
 
         // If still some entries in the buffer, get next
         if let Some(entry) = buffer.0.pop() {
-            return std::task::Poll::Ready(Some(Ok(entry)));
+            self.position += 1;
+            return Poll::Ready(Some(Ok(entry)));
         }
 
-        let result = match &mut self.pending {
-            Some(pending) => {
-                ready!(Pin::new(pending).poll(cx))
-            }
-            None => {
-                let Some(handle) = &self.handle else {
-                    // Force end of iteration
-                    self.buffer = None;
-                    return std::task::Poll::Ready(Some(Err(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "Dir was closed",
-                    )
-                    .into())));
-                };
-
-                let readdir = self.client.request(ReadDir {
-                    handle: handle.clone(),
-                });
-                let pending = self.pending.insert(readdir);
-
-                ready!(Pin::new(pending).poll(cx))
-            }
-        };
-
-        // Polling has finished, resetting pending
-        self.pending = None;
-
-        // If the read was successful, the buffer will be populated again
-        // Stop the iteration otherwise
-        self.buffer = None;
-
-        let result = match result {
-            Ok(mut entries) => {
+        let result = match ready!(self.poll_batch(cx)) {
+            Some(Ok(mut entries)) => {
                 entries.reverse();
-
-                if let Some(entry) = entries.0.pop() {
-                    self.buffer = Some(entries);
-                    Some(Ok(entry))
-                } else {
-                    Some(Err(std::io::Error::new(
-                        std::io::ErrorKind::UnexpectedEof,
-                        "Found no more directory entries while it was expecting some",
-                    )
-                    .into()))
-                }
+                let entry = entries.0.pop();
+                self.buffer = Some(entries);
+                self.position += entry.is_some() as u64;
+                entry.map(Ok)
             }
-            Err(Error::Sftp(Status {
-                code: StatusCode::Eof,
-                ..
-            })) => None,
-            Err(err) => Some(Err(err)),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
         };
 
-        std::task::Poll::Ready(result)
+        Poll::Ready(result)
     }
 }
@@ -14,8 +14,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bytes::Bytes;
 use thiserror::Error;
 
+use crate::client::ChecksumAlgorithm;
 use crate::message::{Status, StatusCode};
 
 /// SFTP client error
@@ -36,6 +38,73 @@ pub enum Error {
     /// IO error
     #[error(transparent)]
     Io(#[from] std::io::Error),
+
+    /// Checksum of a remote file does not match the expected one
+    ///
+    /// See [`SftpClient::upload_verified`](crate::client::SftpClient::upload_verified).
+    #[error("{algorithm} checksum mismatch")]
+    ChecksumMismatch {
+        /// Hash algorithm used to compute the checksums
+        algorithm: ChecksumAlgorithm,
+        /// Checksum of the local data
+        expected: Bytes,
+        /// Checksum of the remote file
+        actual: Bytes,
+    },
+
+    /// A write failed in the middle of a buffer
+    ///
+    /// All the data before `offset` has been written.
+    /// See [`SftpClient::write_all_at`](crate::client::SftpClient::write_all_at).
+    #[error("Write failed at offset {offset}: {source}")]
+    WriteFailed {
+        /// Offset in the file of the first chunk that could not be written
+        offset: u64,
+        /// Error of the write at `offset`
+        source: Box<Error>,
+    },
+
+    /// The client has been stopped, and cannot send requests anymore
+    ///
+    /// See [`SftpClient::stop`](crate::client::SftpClient::stop).
+    #[error("SFTP client has been stopped")]
+    Stopped,
+}
+
+impl Error {
+    /// Check whether the error indicates that a file already exists.
+    ///
+    /// See [`SftpClient::open_exclusive`](crate::client::SftpClient::open_exclusive).
+    pub fn is_already_exists(&self) -> bool {
+        match self {
+            Error::Io(io) => io.kind() == std::io::ErrorKind::AlreadyExists,
+            _ => false,
+        }
+    }
+
+    /// Check whether the error indicates that the connection to the server has been lost.
+    ///
+    /// The client cannot send requests anymore, and must be reconnected.
+    /// See [`ReconnectingClient`](crate::client::ReconnectingClient).
+    ///
+    /// The requests pending when the server closes the session fail with a `NoConnection` status
+    /// if the stream was closed cleanly (eg: the SSH channel received EOF),
+    /// and with a `ConnectionLost` status if the stream failed or was closed in the middle of a frame.
+    pub fn is_connection_lost(&self) -> bool {
+        match self {
+            Error::Stopped => true,
+            Error::WriteFailed { source, .. } => source.is_connection_lost(),
+            Error::Sftp(status) => matches!(
+                status.code,
+                StatusCode::ConnectionLost | StatusCode::NoConnection
+            ),
+            Error::Io(io) => matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::NotConnected
+            ),
+            _ => false,
+        }
+    }
 }
 
 impl From<russh::Error> for Error {
@@ -56,25 +125,22 @@ impl From<crate::message::DecodeError> for Error {
 impl From<Error> for std::io::Error {
     fn from(value: Error) -> Self {
         match value {
-            Error::Sftp(sftp) => {
-                let kind = match sftp.code {
-                    StatusCode::Ok => std::io::ErrorKind::Other,
-                    StatusCode::Eof => std::io::ErrorKind::UnexpectedEof,
-                    StatusCode::NoSuchFile => std::io::ErrorKind::NotFound,
-                    StatusCode::PermissionDenied => std::io::ErrorKind::PermissionDenied,
-                    StatusCode::Failure => std::io::ErrorKind::Other,
-                    StatusCode::BadMessage => std::io::ErrorKind::InvalidData,
-                    StatusCode::NoConnection => std::io::ErrorKind::Other,
-                    StatusCode::ConnectionLost => std::io::ErrorKind::Other,
-                    StatusCode::OpUnsupported => std::io::ErrorKind::Unsupported,
-                };
-
-                Self::new(kind, sftp)
-            }
-            Error::WireFormat(wire) => std::io::Error::new(std::io::ErrorKind::Other, wire),
+            Error::Sftp(sftp) => sftp.into(),
+            Error::WireFormat(wire) => std::io::Error::other(wire),
             Error::Ssh(russh::Error::IO(io)) => io,
-            Error::Ssh(ssh) => std::io::Error::new(std::io::ErrorKind::Other, ssh),
+            Error::Ssh(ssh) => std::io::Error::other(ssh),
             Error::Io(io) => io,
+            err @ Error::ChecksumMismatch { .. } => {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+            }
+            Error::WriteFailed { offset, source } => {
+                let source = std::io::Error::from(*source);
+                std::io::Error::new(
+                    source.kind(),
+                    format!("Write failed at offset {offset}: {source}"),
+                )
+            }
+            err @ Error::Stopped => std::io::Error::new(std::io::ErrorKind::BrokenPipe, err),
         }
     }
 }
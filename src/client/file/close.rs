@@ -43,6 +43,9 @@ impl File {
     ///
     /// Pending Read/Write/Seek operation is cancelled, if any.
     ///
+    /// Closing the file explicitly is the only way to observe errors while closing it.
+    /// Dropping the file closes it in the background, but any error is lost.
+    ///
     /// # Cancel safety
     ///
     /// The closing request is done before returning the future, including the pending operation.
@@ -53,8 +56,18 @@ impl File {
     }
 }
 
+/// Dropping a file is only a best-effort fallback to [`File::close`]:
+/// the close request is sent without waiting for its reply, and any error is lost.
+/// Dropping never blocks, so it is safe to drop a file within an async context.
 impl Drop for File {
     fn drop(&mut self) {
+        if self
+            .handle
+            .as_ref()
+            .is_some_and(|handle| Arc::strong_count(handle) == 1)
+        {
+            log::warn!("File dropped while still open: closing it in the background");
+        }
         FileClosing::new(self).forget()
     }
 }
@@ -21,54 +21,138 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{ready, Poll},
+    time::SystemTime,
 };
 
+use bytes::{BufMut, Bytes, BytesMut};
+
 use crate::message::{self, Attrs, Handle};
+use crate::utils::IntoBytes;
 use crate::{
-    client::{Error, SftpClient},
+    client::{Error, SftpClient, SftpReply, SftpRequest},
     message::Data,
 };
 
-use super::SftpFuture;
+use super::commands::times_to_attrs;
+use super::{HandleKind, HandleOrigin, SftpFuture};
 
 mod close;
+#[cfg(feature = "futures-io")]
+mod futures_io;
+mod prefetch;
 mod read;
 mod seek;
+mod stats;
 mod write;
 
+pub use stats::FileStats;
+
 /// File accessible remotely with SFTP.
 ///
 /// The file can be cloned, and the cloned file will point
 /// to the same remote file, with the same native handle.
 ///
 /// The remote file will be closed when all references to it have been dropped.
+///
+/// The file implements the [`tokio::io`] traits [`AsyncRead`](tokio::io::AsyncRead),
+/// [`AsyncWrite`](tokio::io::AsyncWrite) and [`AsyncSeek`](tokio::io::AsyncSeek).
+/// With the `futures-io` feature, it also implements their [`futures::io`] counterparts.
 #[derive(Debug)]
 pub struct File {
     client: SftpClient,
     handle: Option<Arc<Handle>>,
+    origin: HandleOrigin,
     offset: u64,
     pending: PendingOperation,
+    empty_read: bool,
+    prefetched: prefetch::Prefetched,
+    size: Option<u64>,
+    stats: FileStats,
 }
 
 impl File {
     /// Create a file from a raw [`Handle`].
     ///
-    /// The handle must come from `SftpClient::open`.
-    ///
-    /// The remote file will be closed when the object is dropped.
+    /// Equivalent to [`File::from_handle`].
     ///
     /// # Arguments
     ///
     /// * `handle` - Handle of the open file
     pub fn new(client: SftpClient, handle: Handle) -> Self {
+        Self::from_handle(client, handle)
+    }
+
+    /// Create a file from a raw [`Handle`].
+    ///
+    /// This is the bridge between the raw handle API and the [`AsyncRead`](tokio::io::AsyncRead)/[`AsyncWrite`](tokio::io::AsyncWrite) API:
+    /// a handle obtained with [`SftpClient::open_handle`] can be wrapped into a [`File`].
+    ///
+    /// The [`File`] takes ownership of the handle:
+    /// the remote file will be closed when the object is dropped,
+    /// so the handle must not be closed by other means.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client the handle was opened with
+    /// * `handle` - Handle of the open file
+    pub fn from_handle(client: SftpClient, handle: Handle) -> Self {
         File {
+            origin: HandleOrigin::new(&client, HandleKind::File),
             client,
             handle: Some(Arc::new(handle)),
             offset: 0,
             pending: PendingOperation::None,
+            empty_read: false,
+            prefetched: prefetch::Prefetched::new(),
+            size: None,
+            stats: FileStats::new(),
         }
     }
 
+    /// Give the known size of the remote file.
+    ///
+    /// When the position of the file reaches the known size,
+    /// [`AsyncRead`](tokio::io::AsyncRead) reports the end of the file without sending a `Read` request,
+    /// which saves the round-trip that would only reply `Eof`.
+    /// The end of file is reported this way only once:
+    /// the following reads are sent to the server, in case the file has grown since.
+    ///
+    /// The size is also learned when seeking from the end of the file,
+    /// and is extended by the writes past its end.
+    /// [`SftpClient::open_with_stat`](crate::client::SftpClient::open_with_stat) gives the size to the file it opens.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Size of the remote file (unknown if `None`)
+    pub fn with_size_hint(mut self, size: Option<u64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Get the raw [`Handle`] of the file.
+    ///
+    /// Returns `None` if the file is closed.
+    /// The handle is still owned by the [`File`], and is closed when the object is dropped.
+    pub fn handle(&self) -> Option<&Handle> {
+        self.handle.as_deref()
+    }
+
+    /// Get the raw [`Handle`] of the file, to send raw requests with `client`.
+    ///
+    /// Handles are only valid on the SFTP session that opened them (see [`SftpClient::session_id`]).
+    /// A `BadMessage` status is returned if `client` is on another session than the file,
+    /// like another client of a [`SftpPool`](crate::client::SftpPool).
+    /// A `BrokenPipe` error is returned if the file is closed.
+    ///
+    /// The handle is still owned by the [`File`], and is closed when the object is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client the handle will be used with
+    pub fn handle_for(&self, client: &SftpClient) -> Result<Handle, Error> {
+        self.origin.check(client, self.handle(), HandleKind::File)
+    }
+
     /// Create a closed file.
     ///
     /// The file cannot be opened by any means.
@@ -76,8 +160,13 @@ impl File {
         File {
             client: SftpClient::new_stopped(),
             handle: None,
+            origin: HandleOrigin::STOPPED_FILE,
             offset: 0,
             pending: PendingOperation::None,
+            empty_read: false,
+            prefetched: prefetch::Prefetched::new(),
+            size: None,
+            stats: FileStats::new(),
         }
     }
 }
@@ -85,8 +174,13 @@ impl File {
 pub static FILE_CLOSED: File = File {
     client: SftpClient::new_stopped(),
     handle: None,
+    origin: HandleOrigin::STOPPED_FILE,
     offset: 0,
     pending: PendingOperation::None,
+    empty_read: false,
+    prefetched: prefetch::Prefetched::new(),
+    size: None,
+    stats: FileStats::new(),
 };
 
 impl File {
@@ -109,6 +203,51 @@ impl File {
         }
     }
 
+    /// Check whether the handle of the file is still valid on the server.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn is_valid(&self) -> Result<bool, Error>;
+    /// ```
+    ///
+    /// A `fstat` request is sent with the handle of the file.
+    /// The handle is considered invalid if the server replies with a `Failure` status
+    /// (sent by OpenSSH for unknown handles), or with a `NoSuchFile` status
+    /// (eg: the file has been removed, on servers that check it).
+    /// Other errors (eg: the client is stopped) are returned as-is.
+    ///
+    /// Long-lived files can use it to decide whether they should be reopened.
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn is_valid(&self) -> SftpFuture<bool> {
+        if let Some(handle) = &self.handle {
+            self.client.request_with(
+                message::FStat {
+                    handle: Handle::clone(handle),
+                }
+                .to_request_message(),
+                (),
+                |_, msg| match Attrs::from_reply_message(msg) {
+                    Ok(_) => Ok(true),
+                    Err(Error::Sftp(message::Status {
+                        code: message::StatusCode::Failure | message::StatusCode::NoSuchFile,
+                        ..
+                    })) => Ok(false),
+                    Err(err) => Err(err),
+                },
+            )
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )))
+        }
+    }
+
     /// Change the attributes (metadata) of the file.
     ///
     /// This request is used for operations such as changing the ownership,
@@ -133,6 +272,64 @@ impl File {
             )))
         }
     }
+
+    /// Send an extended request targeting the file.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn extended(&self, request: impl Into<Bytes>, data: impl Into<Bytes>) -> Result<Bytes, Error>;
+    /// ```
+    ///
+    /// Following the OpenSSH convention for handle-based extensions (eg: `fsync@openssh.com`),
+    /// the handle of the file is encoded as a string at the beginning of the extension data,
+    /// followed by `data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Extended-request name (format: `name@domain`)
+    /// * `data` - Specific data needed by the extension, after the handle
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn extended(&self, request: impl IntoBytes, data: impl IntoBytes) -> SftpFuture<Bytes> {
+        if let Some(handle) = &self.handle {
+            let data = data.into_bytes();
+            let mut buffer =
+                BytesMut::with_capacity(std::mem::size_of::<u32>() + handle.len() + data.len());
+            buffer.put_u32(handle.len() as u32);
+            buffer.put_slice(handle);
+            buffer.put_slice(&data);
+
+            self.client.extended(request, buffer.freeze())
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )))
+        }
+    }
+}
+
+impl File {
+    /// Change the access and modification times of the file.
+    ///
+    /// Times are truncated to the second.
+    /// An error is returned if a time cannot be represented in the SFTP protocol
+    /// (before 1970, or after 2106).
+    ///
+    /// # Arguments
+    ///
+    /// * `atime` - New access time
+    /// * `mtime` - New modification time
+    pub fn set_times(&self, atime: SystemTime, mtime: SystemTime) -> SftpFuture {
+        match times_to_attrs(atime, mtime) {
+            Ok(attrs) => self.set_stat(attrs),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
 }
 
 impl Clone for File {
@@ -140,8 +337,13 @@ impl Clone for File {
         Self {
             client: self.client.clone(),
             handle: self.handle.clone(),
+            origin: self.origin,
             offset: self.offset,
             pending: PendingOperation::None,
+            empty_read: false,
+            prefetched: prefetch::Prefetched::new(),
+            size: None,
+            stats: FileStats::new(),
         }
     }
 }
@@ -149,7 +351,7 @@ impl Clone for File {
 enum PendingOperation {
     None,
     Read(SftpFuture<Data>),
-    Seek(SftpFuture<u64, i64>),
+    Seek(SftpFuture<(u64, u64), i64>),
     Write(SftpFuture<usize, usize>),
     Close(SftpFuture),
 }
@@ -169,7 +371,7 @@ impl std::fmt::Debug for PendingOperation {
 enum OperationResult {
     None,
     Read(Result<Data, Error>),
-    Seek(Result<u64, Error>),
+    Seek(Result<(u64, u64), Error>),
     Write(Result<usize, Error>),
     Close(Result<(), Error>),
 }
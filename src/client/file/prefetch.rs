@@ -0,0 +1,160 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::{future::Future, pin::Pin, task::ready, task::Poll};
+
+use bytes::Bytes;
+
+use crate::client::{Error, SftpFuture};
+use crate::message::Handle;
+
+use super::File;
+
+/// Chunks of a file read in advance, indexed by their offset
+pub(super) type Prefetched = BTreeMap<u64, PrefetchChunk>;
+
+/// Chunk of a file read in advance
+pub(super) struct PrefetchChunk {
+    /// Number of bytes requested
+    length: u32,
+    /// Data of the chunk, once received
+    data: PrefetchData,
+}
+
+enum PrefetchData {
+    Pending(SftpFuture<Bytes>),
+    Ready(Bytes),
+}
+
+impl std::fmt::Debug for PrefetchChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.data {
+            PrefetchData::Pending(_) => write!(f, "Pending({})", self.length),
+            PrefetchData::Ready(data) => write!(f, "Ready({})", data.len()),
+        }
+    }
+}
+
+impl File {
+    /// Read a region of the file in advance.
+    ///
+    /// The region is split into chunks, and a read request is sent for each of them immediately.
+    /// The received chunks are kept by the file, and are served by [`AsyncRead`](tokio::io::AsyncRead)
+    /// without any network round-trip when the position of the file reaches them.
+    /// Tools that know their access pattern (eg: reading a header, and then a trailer)
+    /// can prefetch all the regions they need at once to hide the latency.
+    ///
+    /// Chunks are dropped as soon as the position of the file moves past them, by reading or by seeking.
+    /// Writing with [`AsyncWrite`](tokio::io::AsyncWrite) drops all the chunks.
+    /// Chunks are not invalidated by other writes to the remote file (eg: with [`File::write`] or with a clone of the file),
+    /// which might then be served stale data.
+    ///
+    /// Every chunk is held in memory until it is consumed,
+    /// so prefetching a large region is equivalent to reading it into memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Byte offset where the region starts
+    /// * `len` - Number of bytes of the region
+    pub fn prefetch(&mut self, offset: u64, len: u64) -> Result<(), Error> {
+        let Some(handle) = &self.handle else {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )));
+        };
+        let handle = Handle::clone(handle);
+
+        let chunk_size = self.client.read_chunk_size() as u64;
+        let end = offset.saturating_add(len);
+        let mut offset = offset;
+
+        while offset < end {
+            let length = (end - offset).min(chunk_size) as u32;
+            if !self.is_prefetched(offset) {
+                let pending = self.client.read(handle.clone(), offset, length);
+                self.stats.reads += 1;
+                self.prefetched.insert(
+                    offset,
+                    PrefetchChunk {
+                        length,
+                        data: PrefetchData::Pending(pending),
+                    },
+                );
+            }
+            offset += length as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the byte at `offset` is covered by a prefetched chunk.
+    fn is_prefetched(&self, offset: u64) -> bool {
+        match self.prefetched.range(..=offset).next_back() {
+            Some((start, chunk)) => offset < start + chunk.length as u64,
+            None => false,
+        }
+    }
+
+    /// Drop the prefetched chunks that end before the current position.
+    pub(super) fn evict_prefetched(&mut self) {
+        let offset = self.offset;
+        self.prefetched
+            .retain(|start, chunk| start + chunk.length as u64 > offset);
+    }
+
+    /// Poll the prefetched data starting at the current position.
+    ///
+    /// Returns `None` if the current position is not covered by a prefetched chunk,
+    /// or if the chunk could not be read (eg: past the end of the file).
+    /// In that case, the data must be read from the server.
+    pub(super) fn poll_prefetched(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Bytes>> {
+        let offset = self.offset;
+        let Some((&start, chunk)) = self.prefetched.range_mut(..=offset).next_back() else {
+            return Poll::Ready(None);
+        };
+        if offset >= start + chunk.length as u64 {
+            return Poll::Ready(None);
+        }
+
+        if let PrefetchData::Pending(pending) = &mut chunk.data {
+            match ready!(Pin::new(pending).poll(cx)) {
+                Ok(data) => chunk.data = PrefetchData::Ready(data),
+                Err(_) => {
+                    self.prefetched.remove(&start);
+                    return Poll::Ready(None);
+                }
+            }
+        }
+
+        let PrefetchData::Ready(data) = &chunk.data else {
+            return Poll::Ready(None);
+        };
+
+        // The server might have sent less data than requested
+        let skip = (offset - start) as usize;
+        if skip >= data.len() {
+            self.prefetched.remove(&start);
+            return Poll::Ready(None);
+        }
+
+        Poll::Ready(Some(data.slice(skip..)))
+    }
+}
@@ -16,10 +16,10 @@
 
 use std::{future::Future, pin::Pin, task::ready, task::Poll};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 
-use crate::client::{Error, SftpFuture};
-use crate::message::{Handle, Read, Status, StatusCode};
+use crate::client::{Error, SftpFuture, SftpReply, SftpRequest};
+use crate::message::{Attrs, Data, FStat, Handle, Read, Status, StatusCode};
 
 use super::{File, OperationResult, PendingOperation};
 
@@ -51,6 +51,103 @@ impl File {
             )))
         }
     }
+
+    /// Read a portion of the file into a buffer provided by the caller.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read_into(&self, offset: u64, buf: &mut BytesMut, length: u32) -> Result<usize, Error>;
+    /// ```
+    ///
+    /// The data is appended to `buf`, and the number of bytes appended is returned.
+    /// The server can send less than `length` bytes, and 0 is returned at the end of the file.
+    /// Reusing the same buffer across reads avoids keeping a fresh [`Bytes`] for every read.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: Byte offset where the read should start
+    /// * `buf`: Buffer where the data is appended
+    /// * `length`: Maximal number of bytes to read
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future: the buffer is left untouched.
+    /// However, the request is actually sent before the future is returned.
+    pub fn read_into<'a>(
+        &self,
+        offset: u64,
+        buf: &'a mut BytesMut,
+        length: u32,
+    ) -> SftpFuture<usize, &'a mut BytesMut> {
+        if let Some(handle) = &self.handle {
+            self.client.request_with(
+                Read {
+                    handle: Handle::clone(handle),
+                    offset,
+                    length,
+                }
+                .to_request_message(),
+                buf,
+                |buf, msg| match Data::from_reply_message(msg) {
+                    Ok(data) => {
+                        buf.extend_from_slice(&data);
+                        Ok(data.len())
+                    }
+                    Err(Error::Sftp(Status {
+                        code: StatusCode::Eof,
+                        ..
+                    })) => Ok(0),
+                    Err(err) => Err(err),
+                },
+            )
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )))
+        }
+    }
+
+    /// Check whether the current position of the file is at (or past) its end.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn at_eof(&self) -> Result<bool, Error>;
+    /// ```
+    ///
+    /// The size of the file is obtained with a `fstat` request,
+    /// and compared to the current offset of the file.
+    /// It fails if the server does not report the size of the file.
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn at_eof(&self) -> SftpFuture<bool, u64> {
+        if let Some(handle) = &self.handle {
+            self.client.request_with(
+                FStat {
+                    handle: Handle::clone(handle),
+                }
+                .to_request_message(),
+                self.offset,
+                |offset, msg| match Attrs::from_reply_message(msg)?.size {
+                    Some(size) => Ok(offset >= size),
+                    None => Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "Unable to check end of file: could not get file size",
+                    ))),
+                },
+            )
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )))
+        }
+    }
 }
 
 impl tokio::io::AsyncRead for File {
@@ -59,48 +156,92 @@ impl tokio::io::AsyncRead for File {
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> Poll<std::result::Result<(), std::io::Error>> {
-        // Poll the pending operation, if any
-        let result = match ready!(self.pending.poll(cx)) {
-            OperationResult::Read(read) => read,
-            // The pending operation was not a read, so we must start reading
-            _ => {
-                // Get the current handle, valid only if the file is not closed
-                let Some(handle) = &self.handle else {
-                    return Poll::Ready(Err(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "File was closed",
-                    )));
-                };
-                let handle = Handle::clone(handle);
+        if let PendingOperation::None = self.pending {
+            // The end of file is known: report it without any round-trip, but only once
+            if matches!(self.size, Some(size) if self.offset >= size) && buf.remaining() > 0 {
+                self.size = None;
+                return Poll::Ready(Ok(()));
+            }
+
+            // Serve the prefetched data, if any
+            if let Some(data) = ready!(self.poll_prefetched(cx)) {
+                let length = data.len().min(buf.remaining());
+                buf.put_slice(&data[..length]);
+                self.offset += length as u64;
+                self.stats.bytes_read += length as u64;
+                self.empty_read = false;
+                self.evict_prefetched();
+                return Poll::Ready(Ok(()));
+            }
+        }
 
-                // Spawn the read future
-                self.pending = PendingOperation::Read(self.client.request(Read {
-                    handle,
-                    offset: self.offset,
-                    length: buf.remaining().min(32768) as u32, // read at most 32K
-                }));
+        loop {
+            // Poll the pending operation, if any
+            let result = match ready!(self.pending.poll(cx)) {
+                OperationResult::Read(read) => read,
+                // The pending operation was not a read, so we must start reading
+                _ => {
+                    // Get the current handle, valid only if the file is not closed
+                    let Some(handle) = &self.handle else {
+                        return Poll::Ready(Err(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "File was closed",
+                        )));
+                    };
+                    let handle = Handle::clone(handle);
 
-                // Try polling immediately
-                if let PendingOperation::Read(pending) = &mut self.pending {
-                    ready!(Pin::new(pending).poll(cx))
-                } else {
-                    unreachable!()
+                    // read at most one chunk
+                    let length = buf.remaining().min(self.client.read_chunk_size() as usize) as u32;
+
+                    // Spawn the read future
+                    self.stats.reads += 1;
+                    self.pending = PendingOperation::Read(
+                        self.client.request_with(
+                            Read {
+                                handle,
+                                offset: self.offset,
+                                length,
+                            }
+                            .to_request_message(),
+                            (),
+                            |_, msg| Data::from_reply_message(msg),
+                        ),
+                    );
+
+                    // Try polling immediately
+                    if let PendingOperation::Read(pending) = &mut self.pending {
+                        ready!(Pin::new(pending).poll(cx))
+                    } else {
+                        unreachable!()
+                    }
                 }
-            }
-        };
+            };
 
-        // Poll is ready, write to the buffer if it is a success
-        match result {
-            Ok(data) => {
-                buf.put_slice(&data);
-                self.offset += data.len() as u64;
-                std::task::Poll::Ready(Ok(()))
-            }
-            Err(Error::Sftp(Status {
-                code: StatusCode::Eof,
-                ..
-            })) => std::task::Poll::Ready(Ok(())),
-            Err(err) => Poll::Ready(Err(err.into())),
+            // Poll is ready, write to the buffer if it is a success
+            return match result {
+                // Some servers reply with empty data before the end of file:
+                // retry once before considering it as the end of file
+                Ok(data) if data.is_empty() && buf.remaining() > 0 && !self.empty_read => {
+                    self.empty_read = true;
+                    continue;
+                }
+                Ok(data) => {
+                    self.empty_read = false;
+                    buf.put_slice(&data);
+                    self.offset += data.len() as u64;
+                    self.stats.bytes_read += data.len() as u64;
+                    self.evict_prefetched();
+                    std::task::Poll::Ready(Ok(()))
+                }
+                Err(Error::Sftp(Status {
+                    code: StatusCode::Eof,
+                    ..
+                })) => {
+                    self.empty_read = false;
+                    std::task::Poll::Ready(Ok(()))
+                }
+                Err(err) => Poll::Ready(Err(err.into())),
+            };
         }
     }
 }
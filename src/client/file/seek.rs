@@ -26,6 +26,33 @@ use crate::{
 
 use super::{File, OperationResult, PendingOperation};
 
+impl File {
+    /// Get the current position in the file.
+    ///
+    /// This is the offset where the next read or write will occur.
+    /// Unlike [`stream_position`](tokio::io::AsyncSeekExt::stream_position),
+    /// it does not wait for a pending operation to complete.
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    /// Set the current position in the file.
+    ///
+    /// Equivalent to seeking with [`SeekFrom::Start`](std::io::SeekFrom::Start),
+    /// without the need to poll for completion.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - Offset from the start of the file
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`WouldBlock`](std::io::ErrorKind::WouldBlock) if an operation is still pending.
+    pub fn set_position(&mut self, position: u64) -> std::io::Result<()> {
+        tokio::io::AsyncSeek::start_seek(Pin::new(self), std::io::SeekFrom::Start(position))
+    }
+}
+
 impl tokio::io::AsyncSeek for File {
     fn start_seek(mut self: Pin<&mut Self>, position: std::io::SeekFrom) -> std::io::Result<()> {
         if let PendingOperation::None = self.pending {
@@ -33,6 +60,7 @@ impl tokio::io::AsyncSeek for File {
                 // Seek from start can be performed immediately
                 std::io::SeekFrom::Start(n) => {
                     self.offset = n;
+                    self.evict_prefetched();
                 }
                 // Seek from end requires to stat the file first
                 std::io::SeekFrom::End(i) => {
@@ -49,8 +77,8 @@ impl tokio::io::AsyncSeek for File {
                         FStat { handle }.to_request_message(),
                         i,
                         |i, msg| match Attrs::from_reply_message(msg)?.size {
-                            Some(n) => match n.checked_add_signed(i) {
-                                Some(n) => Ok(n),
+                            Some(size) => match size.checked_add_signed(i) {
+                                Some(n) => Ok((n, size)),
                                 None => Err(Error::Io(std::io::Error::new(
                                     std::io::ErrorKind::InvalidData,
                                     "Would seek to negative position",
@@ -65,7 +93,10 @@ impl tokio::io::AsyncSeek for File {
                 }
                 // Seek from current can be performed immediately
                 std::io::SeekFrom::Current(i) => match self.offset.checked_add_signed(i) {
-                    Some(n) => self.offset = n,
+                    Some(n) => {
+                        self.offset = n;
+                        self.evict_prefetched();
+                    }
                     None => {
                         return Err(std::io::Error::new(
                             std::io::ErrorKind::InvalidData,
@@ -89,11 +120,12 @@ impl tokio::io::AsyncSeek for File {
     ) -> Poll<std::io::Result<u64>> {
         match ready!(self.pending.poll(cx)) {
             OperationResult::Seek(seek) => {
-                if let Ok(n) = seek {
-                    self.offset = n;
-                }
+                let (n, size) = seek?;
+                self.offset = n;
+                self.size = Some(size);
+                self.evict_prefetched();
 
-                Poll::Ready(seek.map_err(Into::into))
+                Poll::Ready(Ok(n))
             }
             _ => Poll::Ready(Ok(self.offset)),
         }
@@ -0,0 +1,55 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::File;
+
+/// Cumulative counters of the reads and writes of a [`File`].
+///
+/// Only the operations performed through [`AsyncRead`](tokio::io::AsyncRead),
+/// [`AsyncWrite`](tokio::io::AsyncWrite) and [`File::prefetch`] are counted,
+/// not the explicit requests like [`File::read`] or [`File::write`].
+/// A clone of a file starts with empty counters.
+///
+/// Many round-trips for few bytes usually mean that the reads or writes are issued with small buffers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileStats {
+    /// Number of bytes read from the file
+    pub bytes_read: u64,
+    /// Number of bytes written to the file
+    pub bytes_written: u64,
+    /// Number of [`Read`](crate::message::Read) requests sent to the server
+    pub reads: u64,
+    /// Number of [`Write`](crate::message::Write) requests sent to the server
+    pub writes: u64,
+}
+
+impl FileStats {
+    pub(super) const fn new() -> Self {
+        Self {
+            bytes_read: 0,
+            bytes_written: 0,
+            reads: 0,
+            writes: 0,
+        }
+    }
+}
+
+impl File {
+    /// Get the read and write counters of the file.
+    pub fn stats(&self) -> FileStats {
+        self.stats
+    }
+}
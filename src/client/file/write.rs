@@ -16,8 +16,11 @@
 
 use std::{future::Future, pin::Pin, task::ready, task::Poll};
 
-use crate::client::{Error, SftpFuture, SftpReply, SftpRequest};
-use crate::message::{Close, Data, Handle, Write};
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::AsyncWriteExt;
+
+use crate::client::{Error, SftpFuture, SftpReply, SftpRequest, StatusCode};
+use crate::message::{Close, Data, Extended, Handle, Status, Write};
 
 use super::{File, OperationResult, PendingOperation};
 
@@ -49,6 +52,74 @@ impl File {
             )))
         }
     }
+
+    /// Request the server to persist the content of the file to the storage.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn fsync(&self) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Uses the `fsync@openssh.com` extension.
+    /// Servers that do not support the extension reply with an `OpUnsupported` error.
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn fsync(&self) -> SftpFuture {
+        if let Some(handle) = &self.handle {
+            let mut data = BytesMut::with_capacity(std::mem::size_of::<u32>() + handle.len());
+            data.put_u32(handle.len() as u32);
+            data.put_slice(handle);
+
+            self.client.request_with(
+                Extended {
+                    request: Bytes::from_static(b"fsync@openssh.com"),
+                    data: data.freeze(),
+                }
+                .to_request_message(),
+                (),
+                |_, msg| <()>::from_reply_message(msg),
+            )
+        } else {
+            SftpFuture::Error(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "File was already closed",
+            )))
+        }
+    }
+
+    /// Flush the pending write, and persist the content of the file to the storage.
+    ///
+    /// [`AsyncWriteExt::flush`] only ensures that the server has written the bytes,
+    /// not that they have reached the storage.
+    /// Once the pending write is flushed, the data is persisted with [`File::fsync`].
+    ///
+    /// If the server does not support the `fsync@openssh.com` extension,
+    /// a warning is logged, and only the plain flush is performed.
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// If the future is dropped after the flush, the fsync request might have been sent without waiting for its reply.
+    pub async fn flush_durable(&mut self) -> Result<(), Error> {
+        self.flush().await?;
+
+        match self.fsync().await {
+            Err(Error::Sftp(Status {
+                code: StatusCode::OpUnsupported,
+                ..
+            })) => {
+                log::warn!(
+                    "fsync@openssh.com is not supported by the server: data might not be persisted"
+                );
+                Ok(())
+            }
+            result => result,
+        }
+    }
 }
 
 impl tokio::io::AsyncWrite for File {
@@ -70,15 +141,19 @@ impl tokio::io::AsyncWrite for File {
                     )));
                 };
                 let handle = Handle::clone(handle);
-                let length = buf.len().min(32768); // write at most 32K
+                // prefetched data is now stale
+                self.prefetched.clear();
+                // write at most one chunk
+                let length = buf.len().min(self.client.write_chunk_size() as usize);
 
                 // Spawn the write future
+                self.stats.writes += 1;
                 self.pending = PendingOperation::Write(
                     self.client.request_with(
                         Write {
                             handle,
                             offset: self.offset,
-                            data: buf[0..length].to_owned().into(),
+                            data: Data::from(&buf[..length]),
                         }
                         .to_request_message(),
                         length,
@@ -102,6 +177,11 @@ impl tokio::io::AsyncWrite for File {
         match result {
             Ok(len) => {
                 self.offset += len as u64;
+                self.stats.bytes_written += len as u64;
+                let offset = self.offset;
+                if let Some(size) = &mut self.size {
+                    *size = (*size).max(offset);
+                }
                 std::task::Poll::Ready(Ok(len))
             }
             Err(err) => Poll::Ready(Err(err.into())),
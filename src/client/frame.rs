@@ -0,0 +1,68 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use crate::client::receiver::Command;
+use crate::client::{Error, SftpClient};
+
+/// Direction of a frame on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameDirection {
+    /// Frame sent by the client to the server
+    Sent,
+    /// Frame received by the client from the server
+    Received,
+}
+
+/// Callback invoked for every frame sent or received.
+pub(super) type FrameHook = Arc<dyn Fn(FrameDirection, &[u8]) + Send + Sync>;
+
+impl SftpClient {
+    /// Register a hook invoked with the raw bytes of every frame sent or received.
+    ///
+    /// This is meant for debugging the protocol with a specific server.
+    /// The hook is given the content of the frame, excluding its 4-byte length prefix,
+    /// as it is sent to or received from the stream.
+    ///
+    /// The hook is shared by all the clones of the client, and replaces any previous hook.
+    /// It is invoked from the task processing the requests, so it should return quickly.
+    /// All the requests sent after this call are given to the hook.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - Callback invoked for every frame
+    pub fn on_frame(
+        &self,
+        hook: impl Fn(FrameDirection, &[u8]) + Send + Sync + 'static,
+    ) -> Result<(), Error> {
+        self.set_frame_hook(Some(Arc::new(hook)))
+    }
+
+    /// Remove the hook registered with [`SftpClient::on_frame`], if any.
+    pub fn clear_on_frame(&self) -> Result<(), Error> {
+        self.set_frame_hook(None)
+    }
+
+    fn set_frame_hook(&self, hook: Option<FrameHook>) -> Result<(), Error> {
+        let Some(commands) = &self.commands else {
+            return Err(Error::Stopped);
+        };
+        commands
+            .send(Command::FrameHook(hook))
+            .map_err(|_| Error::Stopped)
+    }
+}
@@ -0,0 +1,186 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::Future;
+use tokio::io::AsyncRead;
+
+use crate::client::SftpClientConfig;
+
+/// Split the bytes read from a stream into SFTP frames.
+pub(super) struct FrameReader {
+    response_size: Option<u32>,
+    response_buffer: BytesMut,
+    read_timeout: Option<Duration>,
+    read_deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    stalled: bool,
+    min_read_size: usize,
+    max_frame_size: u32,
+}
+
+impl FrameReader {
+    /// Create a new frame reader
+    ///
+    /// `prebuffered` contains the bytes already read from the stream.
+    pub(super) fn new(prebuffered: &[u8], config: &SftpClientConfig) -> Self {
+        Self {
+            response_size: None,
+            response_buffer: BytesMut::from(prebuffered),
+            read_timeout: config.read_timeout,
+            read_deadline: None,
+            stalled: false,
+            min_read_size: config.min_read_size,
+            max_frame_size: config.max_frame_size,
+        }
+    }
+
+    /// Check whether the stream has stalled in the middle of a frame.
+    ///
+    /// No more frames can be received from a stalled stream.
+    pub(super) fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Poll the next frame from the stream, without its length prefix.
+    ///
+    /// Returns `None` once the stream has been closed cleanly, between two frames,
+    /// or once the stream has stalled.
+    pub(super) fn poll_frame(
+        &mut self,
+        stream: &mut (impl AsyncRead + Unpin),
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<std::io::Result<Bytes>>> {
+        // The stream has stalled in the middle of a frame: no more frames can be received
+        if self.stalled {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            let new_len;
+            match self.response_size {
+                // A size has already been read from the stream
+                Some(response_size) => {
+                    if self.response_buffer.len() >= response_size as usize {
+                        self.response_size = None;
+                        let response = self.response_buffer.split_to(response_size as usize);
+                        return Poll::Ready(Some(Ok(response.freeze())));
+                    }
+                    new_len = response_size as usize;
+                }
+                // Must read the size of the frame from the stream
+                None => {
+                    if self.response_buffer.len() >= std::mem::size_of::<u32>() {
+                        let len = self.response_buffer.get_u32();
+                        if len > self.max_frame_size {
+                            // The stream cannot be resynchronized after an oversized frame
+                            self.stalled = true;
+                            return Poll::Ready(Some(Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidData,
+                                format!(
+                                    "Received a SFTP frame of {len} bytes, larger than the limit of {} bytes",
+                                    self.max_frame_size
+                                ),
+                            ))));
+                        }
+                        self.response_size = Some(len);
+                        continue;
+                    }
+                    new_len = std::mem::size_of::<u32>();
+                }
+            }
+
+            let old_len = self.response_buffer.len();
+
+            // taking is required to avoid borrowing multiple times `self`
+            let mut buffer = std::mem::take(&mut self.response_buffer);
+
+            // tries to read the remaining of the frame at once,
+            // but at least `min_read_size` bytes to also get the beginning of the next frames
+            let read_size = new_len.saturating_sub(old_len).max(self.min_read_size);
+            buffer.resize(old_len + read_size, 0);
+            let mut read_buf = tokio::io::ReadBuf::new(&mut buffer[old_len..]);
+            let read = Pin::new(&mut *stream).poll_read(cx, &mut read_buf);
+
+            // Adjust buffer size according to what was read
+            let len = read_buf.filled().len();
+            buffer.resize(old_len + len, 0);
+            self.response_buffer = buffer;
+
+            // Check status of reading
+            match read {
+                Poll::Ready(Ok(())) => (),
+                Poll::Ready(Err(err)) => {
+                    return Poll::Ready(Some(Err(err)));
+                }
+                Poll::Pending => {
+                    return self.poll_read_timeout(cx);
+                }
+            }
+
+            // EoF
+            if len == 0 {
+                // The server closed the stream cleanly, between two frames
+                if self.response_size.is_none() && self.response_buffer.is_empty() {
+                    return Poll::Ready(None);
+                }
+
+                // The stream cannot be resynchronized after a truncated frame
+                self.stalled = true;
+                return Poll::Ready(Some(Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "SFTP stream closed in the middle of a frame",
+                ))));
+            }
+
+            // Some data has been received, so the stream is not idle
+            self.read_deadline = None;
+        }
+    }
+
+    /// Check if the stream has been idle for too long while a frame is partially received.
+    ///
+    /// Once the timeout has elapsed, the stream is considered stalled and is stopped.
+    fn poll_read_timeout(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<std::io::Result<Bytes>>> {
+        let Some(timeout) = self.read_timeout else {
+            return Poll::Pending;
+        };
+
+        // No frame is being received: the stream can be idle indefinitely
+        if self.response_size.is_none() && self.response_buffer.is_empty() {
+            self.read_deadline = None;
+            return Poll::Pending;
+        }
+
+        let deadline = self
+            .read_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        futures::ready!(deadline.as_mut().poll(cx));
+
+        self.read_deadline = None;
+        self.stalled = true;
+        Poll::Ready(Some(Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "Timed out while receiving a partial SFTP frame",
+        ))))
+    }
+}
@@ -0,0 +1,206 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::client::{Error, SftpClient};
+use crate::message::{Attrs, Handle, PFlags, Path};
+
+/// Cache of open file handles, closing the least recently used ones.
+///
+/// Opening the same files repeatedly costs an `open` and a `close` request each time.
+/// The cache keeps up to `capacity` handles open, keyed by the path and the flags they were opened with,
+/// and closes the least recently used handle when a new one must be opened.
+///
+/// A file is *not* closed when the caller is done with its handle, but only when it is evicted,
+/// when the cache is [cleared](HandleCache::clear), or when the cache is dropped.
+/// Servers limit the number of handles open at the same time
+/// (see [`Limits::max_open_handles`](crate::client::Limits::max_open_handles)),
+/// so the capacity must leave room for the other handles of the session.
+///
+/// The cache keeps a clone of its client, so the session is not stopped while the cache is alive.
+/// Use [`HandleCache::stop`] to close all the handles and stop the client.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn dummy(sftp: rusftp::client::SftpClient) -> Result<(), rusftp::client::Error> {
+/// use rusftp::message::PFlags;
+///
+/// let mut cache = rusftp::client::HandleCache::new(sftp.clone(), 16);
+/// let handle = cache.open("file", PFlags::READ).await?;
+/// let data = sftp.read(handle, 0, 1024).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HandleCache {
+    client: SftpClient,
+    capacity: usize,
+    /// Open handles, and their last use
+    handles: HashMap<(Path, PFlags), (Handle, u64)>,
+    /// Keys of the handles, ordered by last use
+    usage: BTreeMap<u64, (Path, PFlags)>,
+    tick: u64,
+}
+
+impl HandleCache {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - Client used to open and close the handles
+    /// * `capacity` - Maximal number of handles kept open (at least 1)
+    pub fn new(client: SftpClient, capacity: usize) -> Self {
+        Self {
+            client,
+            capacity: capacity.max(1),
+            handles: HashMap::new(),
+            usage: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Get the client of the cache.
+    pub fn client(&self) -> &SftpClient {
+        &self.client
+    }
+
+    /// Number of handles currently open.
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Check whether no handle is currently open.
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Get a handle for the file, opening it if it is not in the cache.
+    ///
+    /// If the cache is full, the least recently used handle is closed beforehand.
+    ///
+    /// The handle remains owned by the cache: it must not be closed,
+    /// and must not be used after it has been evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to open
+    /// * `pflags` - Flags for the file opening
+    pub async fn open(&mut self, path: impl Into<Path>, pflags: PFlags) -> Result<Handle, Error> {
+        let key = (self.client.resolve_path(path), pflags);
+        self.tick += 1;
+
+        if let Some((handle, last_use)) = self.handles.get_mut(&key) {
+            self.usage.remove(last_use);
+            *last_use = self.tick;
+            let handle = handle.clone();
+            self.usage.insert(self.tick, key);
+            return Ok(handle);
+        }
+
+        if self.handles.len() >= self.capacity {
+            self.evict().await?;
+        }
+
+        let handle = self
+            .client
+            .open_handle(key.0.clone(), pflags, Attrs::default())
+            .await?;
+        self.handles
+            .insert(key.clone(), (handle.clone(), self.tick));
+        self.usage.insert(self.tick, key);
+
+        Ok(handle)
+    }
+
+    /// Close the handle of the file, if it is in the cache.
+    ///
+    /// Returns `false` if the file was not in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to close
+    /// * `pflags` - Flags the file was opened with
+    pub async fn close(&mut self, path: impl Into<Path>, pflags: PFlags) -> Result<bool, Error> {
+        let key = (self.client.resolve_path(path), pflags);
+        match self.handles.remove(&key) {
+            Some((handle, last_use)) => {
+                self.usage.remove(&last_use);
+                self.client.close(handle).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Close all the handles of the cache.
+    ///
+    /// All the handles are closed, even if some of them fail to close.
+    /// The first error is returned.
+    pub async fn clear(&mut self) -> Result<(), Error> {
+        self.usage.clear();
+        let closing = self
+            .handles
+            .drain()
+            .map(|(_, (handle, _))| self.client.close(handle))
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        for close in closing {
+            if let Err(err) = close.await {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
+    /// Close all the handles of the cache, and stop its client.
+    ///
+    /// See [`SftpClient::stop`].
+    pub async fn stop(&mut self) -> Result<(), Error> {
+        let result = self.clear().await;
+        self.client.stop().await;
+        result
+    }
+
+    /// Close the least recently used handle.
+    async fn evict(&mut self) -> Result<(), Error> {
+        if let Some((_, key)) = self.usage.pop_first() {
+            if let Some((handle, _)) = self.handles.remove(&key) {
+                log::trace!("Evicting handle of {:?}", key.0);
+                self.client.close(handle).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HandleCache {
+    fn drop(&mut self) {
+        // The close requests are sent eagerly, the replies are not awaited
+        for (_, (handle, _)) in self.handles.drain() {
+            _ = self.client.close(handle);
+        }
+    }
+}
+
+impl std::fmt::Debug for HandleCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HandleCache({}/{})", self.handles.len(), self.capacity)
+    }
+}
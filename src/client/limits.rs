@@ -0,0 +1,80 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Error, SftpClient};
+
+/// Limits of the server, negotiated with the `limits@openssh.com` extension.
+///
+/// A limit of 0 means that the server did not set any limit.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Limits {
+    /// Maximal size of a packet accepted by the server
+    pub max_packet_length: u64,
+    /// Maximal length of a read request
+    pub max_read_length: u64,
+    /// Maximal length of the data of a write request
+    pub max_write_length: u64,
+    /// Maximal number of handles open at the same time
+    pub max_open_handles: u64,
+}
+
+impl SftpClient {
+    /// Get the limits of the server.
+    ///
+    /// The limits are queried during the handshake if the server supports the `limits@openssh.com` extension,
+    /// and if [`SftpClientConfig::use_server_limits`](crate::client::SftpClientConfig::use_server_limits) is set.
+    /// Returns `None` otherwise.
+    pub fn limits(&self) -> Option<&Limits> {
+        self.limits.as_ref()
+    }
+
+    /// Length of the read requests issued by files and transfers.
+    ///
+    /// The maximal read length of the server is used if known,
+    /// and [`SftpClientConfig::chunk_size`](crate::client::SftpClientConfig::chunk_size) otherwise.
+    pub fn read_chunk_size(&self) -> u32 {
+        match self.limits {
+            Some(Limits {
+                max_read_length: length @ 1..,
+                ..
+            }) => length.min(u32::MAX as u64) as u32,
+            _ => self.config.chunk_size,
+        }
+    }
+
+    /// Length of the write requests issued by files and transfers.
+    ///
+    /// The maximal write length of the server is used if known,
+    /// and [`SftpClientConfig::chunk_size`](crate::client::SftpClientConfig::chunk_size) otherwise.
+    pub fn write_chunk_size(&self) -> u32 {
+        match self.limits {
+            Some(Limits {
+                max_write_length: length @ 1..,
+                ..
+            }) => length.min(u32::MAX as u64) as u32,
+            _ => self.config.chunk_size,
+        }
+    }
+
+    /// Query the limits of the server.
+    pub(super) async fn query_limits(&self) -> Result<Limits, Error> {
+        self.extended_decode("limits@openssh.com", Bytes::new())
+            .await
+    }
+}
@@ -0,0 +1,245 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::client::receiver::Command;
+use crate::client::transfer::remote_parent;
+use crate::client::{SftpClient, SftpClientConfig};
+use crate::message::{Message, PFlags, Path};
+
+impl SftpClient {
+    /// Cache the results of `stat`, `lstat` and `realpath` requests.
+    ///
+    /// The cache applies to the whole SFTP session, including all the clones of the client,
+    /// and replaces any previous cache, dropping its entries.
+    /// The cache can also be enabled when the client is created, with
+    /// [`SftpClientConfig::metadata_cache_ttl`] and [`SftpClientConfig::metadata_cache_capacity`].
+    /// The configuration of the returned client is updated accordingly.
+    ///
+    /// Successful replies are kept for `ttl`, keyed by the request and the path as sent to the server
+    /// (ie: after resolving it against the [working directory](SftpClient::cd)).
+    /// When more than `capacity` entries are cached, the least recently used one is evicted.
+    /// Errors are never cached, and cached replies are not counted in the [metrics](SftpClient::metrics).
+    ///
+    /// Requests sent through the client invalidate the entries they may affect:
+    ///
+    /// * `setstat`, `remove`, `mkdir`, `symlink` (on both its paths),
+    ///   and `open` with any of `WRITE`, `APPEND`, `CREATE` or `TRUNCATE`:
+    ///   all the entries of the path, and of its parent directory.
+    /// * `write` and `fsetstat`: all the `stat` and `lstat` entries, as the path of a handle is not known.
+    ///   `realpath` entries are kept.
+    /// * `rename`, `rmdir` and extended requests (eg: `posix-rename@openssh.com`, `hardlink@openssh.com`):
+    ///   the whole cache, as they can change any path below them.
+    ///
+    /// A reply is not cached if an invalidation happened while its request was in flight.
+    ///
+    /// Other paths designating the same file are not invalidated:
+    /// a `stat` through a symbolic link, or a path with `..` components, may return stale attributes
+    /// until its entry expires. Changes made by other sessions are only seen once the entries expire.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - Time during which a reply is reused
+    /// * `capacity` - Maximal number of cached replies (the cache is disabled if 0)
+    pub fn with_metadata_cache(mut self, ttl: Duration, capacity: usize) -> Self {
+        if let Some(commands) = &self.commands {
+            _ = commands.send(Command::MetadataCache(ttl, capacity));
+        }
+        self.config.metadata_cache_ttl = ttl;
+        self.config.metadata_cache_capacity = capacity;
+        self
+    }
+}
+
+/// Request whose reply can be cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum CachedKind {
+    Stat,
+    LStat,
+    RealPath,
+}
+
+type CacheKey = (CachedKind, Path);
+
+/// LRU cache of metadata replies, owned by the receiver.
+#[derive(Debug, Default)]
+pub(super) struct MetadataCache {
+    ttl: Duration,
+    capacity: usize,
+    /// Cached replies, their expiration, and their last use
+    entries: HashMap<CacheKey, (Message, Instant, u64)>,
+    /// Keys of the entries, ordered by last use
+    usage: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    /// Number of invalidations so far
+    generation: u64,
+    /// Requests in flight whose reply can be cached, with the generation they were sent in
+    pending: HashMap<u32, (CacheKey, u64)>,
+}
+
+impl MetadataCache {
+    /// Create the cache configured by the client configuration.
+    pub(super) fn new(config: &SftpClientConfig) -> Self {
+        let mut cache = Self::default();
+        cache.configure(config.metadata_cache_ttl, config.metadata_cache_capacity);
+        cache
+    }
+
+    /// Replace the configuration of the cache, and drop all its entries.
+    pub(super) fn configure(&mut self, ttl: Duration, capacity: usize) {
+        self.ttl = ttl;
+        self.capacity = capacity;
+        self.clear();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.capacity > 0 && !self.ttl.is_zero()
+    }
+
+    /// Get the cached reply of the request, if any.
+    pub(super) fn lookup(&mut self, message: &Message) -> Option<Message> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let key = cache_key(message)?;
+
+        let (reply, expiration, last_use) = self.entries.get_mut(&key)?;
+        if *expiration <= Instant::now() {
+            let last_use = *last_use;
+            self.usage.remove(&last_use);
+            self.entries.remove(&key);
+            return None;
+        }
+
+        self.tick += 1;
+        self.usage.remove(last_use);
+        *last_use = self.tick;
+        let reply = reply.clone();
+        self.usage.insert(self.tick, key);
+        Some(reply)
+    }
+
+    /// Register a request being sent: invalidate the entries it affects,
+    /// and remember it if its reply can be cached.
+    pub(super) fn request(&mut self, id: u32, message: &Message) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        match message {
+            Message::SetStat(setstat) => self.invalidate_path(&setstat.path),
+            Message::Remove(remove) => self.invalidate_path(&remove.path),
+            Message::MkDir(mkdir) => self.invalidate_path(&mkdir.path),
+            // OpenSSH swaps the paths: the link can be at either of them
+            Message::Symlink(symlink) => {
+                self.invalidate_path(&symlink.link_path);
+                self.invalidate_path(&symlink.target_path);
+            }
+            Message::Open(open)
+                if open.pflags.intersects(
+                    PFlags::WRITE | PFlags::APPEND | PFlags::CREATE | PFlags::TRUNCATE,
+                ) =>
+            {
+                self.invalidate_path(&open.filename)
+            }
+            Message::Write(_) | Message::FSetStat(_) => self.invalidate_attrs(),
+            Message::Rename(_) | Message::RmDir(_) | Message::Extended(_) => self.clear(),
+            _ => {
+                if let Some(key) = cache_key(message) {
+                    self.pending.insert(id, (key, self.generation));
+                }
+            }
+        }
+    }
+
+    /// Store the reply of a request, if it can be cached.
+    pub(super) fn reply(&mut self, id: u32, reply: &Message) {
+        let Some(((kind, path), generation)) = self.pending.remove(&id) else {
+            return;
+        };
+        let cacheable = matches!(
+            (kind, reply),
+            (CachedKind::Stat | CachedKind::LStat, Message::Attrs(_))
+                | (CachedKind::RealPath, Message::Name(_))
+        );
+        if !cacheable || generation != self.generation || !self.is_enabled() {
+            return;
+        }
+
+        let key = (kind, path);
+        if let Some((_, _, last_use)) = self.entries.remove(&key) {
+            self.usage.remove(&last_use);
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.usage.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        self.tick += 1;
+        self.entries.insert(
+            key.clone(),
+            (reply.clone(), Instant::now() + self.ttl, self.tick),
+        );
+        self.usage.insert(self.tick, key);
+    }
+
+    /// Forget a request whose reply will not be received.
+    pub(super) fn discard(&mut self, id: u32) {
+        self.pending.remove(&id);
+    }
+
+    /// Remove the entries of the path and of its parent directory.
+    fn invalidate_path(&mut self, path: &Path) {
+        let parent = remote_parent(path);
+        self.invalidate(|(_, entry)| entry == path || Some(entry) == parent.as_ref());
+    }
+
+    /// Remove all the `stat` and `lstat` entries.
+    fn invalidate_attrs(&mut self) {
+        self.invalidate(|(kind, _)| *kind != CachedKind::RealPath);
+    }
+
+    /// Remove all the entries.
+    fn clear(&mut self) {
+        self.invalidate(|_| true);
+    }
+
+    fn invalidate(&mut self, mut predicate: impl FnMut(&CacheKey) -> bool) {
+        self.generation += 1;
+        self.entries.retain(|key, (_, _, last_use)| {
+            let keep = !predicate(key);
+            if !keep {
+                self.usage.remove(last_use);
+            }
+            keep
+        });
+    }
+}
+
+fn cache_key(message: &Message) -> Option<CacheKey> {
+    match message {
+        Message::Stat(stat) => Some((CachedKind::Stat, stat.path.clone())),
+        Message::LStat(lstat) => Some((CachedKind::LStat, lstat.path.clone())),
+        Message::RealPath(realpath) => Some((CachedKind::RealPath, realpath.path.clone())),
+        _ => None,
+    }
+}
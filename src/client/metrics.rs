@@ -0,0 +1,88 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::client::SftpClient;
+use crate::message::Message;
+
+/// Cumulative counters of a SFTP session.
+///
+/// Counters are shared by all the clones of a [`SftpClient`].
+/// They are a snapshot taken when calling [`SftpClient::metrics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SftpMetrics {
+    /// Number of file bytes received from [`Data`](crate::message::Data) replies
+    pub bytes_read: u64,
+    /// Number of file bytes sent with [`Write`](crate::message::Write) requests
+    pub bytes_written: u64,
+    /// Number of requests sent to the server
+    pub requests_sent: u64,
+    /// Number of responses received from the server
+    pub responses_received: u64,
+}
+
+/// Atomic counters updated by the receiver.
+#[derive(Debug, Default)]
+pub(super) struct MetricsCounters {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    requests_sent: AtomicU64,
+    responses_received: AtomicU64,
+}
+
+impl MetricsCounters {
+    /// Account for a request that has been sent
+    pub(super) fn request_sent(&self, message: &Message) {
+        self.requests_sent.fetch_add(1, Ordering::Relaxed);
+        if let Message::Write(write) = message {
+            self.bytes_written
+                .fetch_add(write.data.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Account for a response that has been received
+    pub(super) fn response_received(&self, message: &Message) {
+        self.responses_received.fetch_add(1, Ordering::Relaxed);
+        if let Message::Data(data) = message {
+            self.bytes_read
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Get the current value of the counters
+    pub(super) fn snapshot(&self) -> SftpMetrics {
+        SftpMetrics {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            requests_sent: self.requests_sent.load(Ordering::Relaxed),
+            responses_received: self.responses_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl SftpClient {
+    /// Get the cumulative counters of the SFTP session.
+    ///
+    /// A stopped client returns the counters at the time it was stopped,
+    /// or zero if it has never been started.
+    pub fn metrics(&self) -> SftpMetrics {
+        match &self.metrics {
+            Some(metrics) => metrics.snapshot(),
+            None => SftpMetrics::default(),
+        }
+    }
+}
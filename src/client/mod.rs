@@ -18,30 +18,64 @@
 //!
 //! See [`SftpClient`]
 
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use russh::ChannelStream;
 use russh::{client::Msg, Channel};
-use tokio::io::AsyncWrite;
+use tokio::io::{AsyncReadExt, AsyncWrite};
 use tokio::task::JoinHandle;
 use tokio::{io::AsyncRead, sync::mpsc};
 
 use crate::message::{Init, Message, StatusCode, Version};
 
+mod checksum;
 mod commands;
+mod config;
+mod cwd;
 mod dir;
 mod error;
 mod file;
+mod frame;
+mod framing;
+mod handle_cache;
+mod limits;
+mod metadata_cache;
+mod metrics;
+mod origin;
+mod pool;
+mod rate;
 mod receiver;
+mod reconnect;
+mod redact;
 mod request;
+#[cfg(feature = "diagnostics")]
+mod sniffer;
+mod statvfs;
 mod stop;
+mod transfer;
 
-pub use dir::{Dir, DIR_CLOSED};
+pub use checksum::{ChecksumAlgorithm, VerifyOptions};
+pub use config::SftpClientConfig;
+pub use dir::{Dir, DirEntry, FileType, DIR_CLOSED};
 pub use error::Error;
-pub use file::{File, FILE_CLOSED};
-pub use request::{SftpFuture, SftpReply, SftpRequest};
+pub use file::{File, FileStats, FILE_CLOSED};
+pub use frame::FrameDirection;
+pub use handle_cache::HandleCache;
+pub use limits::Limits;
+pub use metrics::SftpMetrics;
+use origin::{HandleKind, HandleOrigin};
+pub use pool::{PoolHandle, SftpPool};
+pub use reconnect::ReconnectingClient;
+pub use request::{SftpCancelToken, SftpFuture, SftpReply, SftpRequest};
+#[cfg(feature = "diagnostics")]
+pub use sniffer::SftpSniffer;
+pub use statvfs::StatVfs;
 use stop::SftpClientStopping;
+pub use transfer::OverwritePolicy;
 
 /// SFTP client
 ///
@@ -72,10 +106,22 @@ use stop::SftpClientStopping;
 /// ```
 #[derive(Default, Clone)]
 pub struct SftpClient {
-    commands: Option<mpsc::UnboundedSender<receiver::Request>>,
+    commands: Option<mpsc::UnboundedSender<receiver::Command>>,
     request_processor: Option<Arc<JoinHandle<()>>>,
+    metrics: Option<Arc<metrics::MetricsCounters>>,
+    config: SftpClientConfig,
+    cwd: Option<crate::message::Path>,
+    limits: Option<Limits>,
+    session: u64,
+    /// Id of the last request sent on the session
+    last_id: Option<Arc<AtomicU32>>,
+    /// Whether the server is OpenSSH, that swaps the paths of symlink requests
+    openssh: bool,
 }
 
+/// Identifier of the next SFTP session (0 is for the stopped clients)
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
 pub static SFTP_CLIENT_STOPPED: SftpClient = SftpClient::new_stopped();
 
 impl SftpClient {
@@ -85,6 +131,13 @@ impl SftpClient {
         Self {
             commands: None,
             request_processor: None,
+            metrics: None,
+            config: SftpClientConfig::new(),
+            cwd: None,
+            limits: None,
+            session: 0,
+            last_id: None,
+            openssh: false,
         }
     }
 
@@ -94,52 +147,282 @@ impl SftpClient {
     /// or a [`russh::client::Handler`].
     /// In case of the handler, it can be moved or borrowed.
     pub async fn new<T: IntoSftpStream>(ssh: T) -> Result<Self, Error> {
-        Self::with_stream(ssh.into_sftp_stream().await?).await
+        Self::new_with_config(ssh, SftpClientConfig::default()).await
+    }
+
+    /// Creates a new client from a ssh connection, with a custom configuration.
+    ///
+    /// See [`SftpClient::new`] and [`SftpClientConfig`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ssh` - SSH connection used to open the SFTP session
+    /// * `config` - Configuration of the client
+    pub async fn new_with_config<T: IntoSftpStream>(
+        ssh: T,
+        config: SftpClientConfig,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+        Self::with_stream_config(ssh.into_sftp_stream().await?, config).await
+    }
+
+    /// Creates a new client from a ssh connection, bounding the time of the handshake.
+    ///
+    /// Same as [`SftpClient::new_with_config`] with [`SftpClientConfig::handshake_timeout`] set to `timeout`:
+    /// an `Io(TimedOut)` error is returned if the server has not replied to the `Init` request before `timeout` elapses.
+    /// The stream is then dropped, as the receiver is only spawned once the handshake has completed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ssh` - SSH connection used to open the SFTP session
+    /// * `timeout` - Maximal duration of the handshake
+    pub async fn new_with_handshake_timeout<T: IntoSftpStream>(
+        ssh: T,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let config = SftpClientConfig {
+            handshake_timeout: Some(timeout),
+            ..Default::default()
+        };
+        Self::new_with_config(ssh, config).await
+    }
+
+    /// Creates a new client from a ssh channel, using a custom subsystem.
+    ///
+    /// Some servers expose SFTP under a subsystem name other than `sftp`.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - SSH channel where the subsystem is started
+    /// * `name` - Name of the SFTP subsystem
+    pub async fn with_subsystem(channel: Channel<Msg>, name: &str) -> Result<Self, Error> {
+        channel.request_subsystem(false, name).await?;
+        Self::with_stream(channel.into_stream()).await
+    }
+
+    /// Creates a new client from a ssh channel, by executing a SFTP server command.
+    ///
+    /// Useful for servers where the SFTP subsystem is not configured,
+    /// but a SFTP server can still be executed (eg: `/usr/libexec/sftp-server`).
+    ///
+    /// # Arguments
+    ///
+    /// * `channel` - SSH channel where the command is executed
+    /// * `command` - Command that starts a SFTP server on its standard input and output
+    pub async fn with_exec(channel: Channel<Msg>, command: &str) -> Result<Self, Error> {
+        channel.exec(false, command).await?;
+        Self::with_stream(channel.into_stream()).await
+    }
+
+    /// Creates a new client from a ssh connection, setting remote environment variables before starting SFTP.
+    ///
+    /// Some servers select their behavior (eg: the chroot or the locale) from the environment of the session.
+    /// The variables are set on a new session channel, before the `sftp` subsystem is requested.
+    /// The server may silently ignore the variables it does not accept (eg: OpenSSH `AcceptEnv`).
+    ///
+    /// # Arguments
+    ///
+    /// * `ssh` - SSH connection used to open the SFTP session
+    /// * `env` - Names and values of the environment variables to set
+    pub async fn from_session_with_env<H, K, V>(
+        ssh: &russh::client::Handle<H>,
+        env: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<Self, Error>
+    where
+        H: russh::client::Handler,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let channel = ssh.channel_open_session().await?;
+        for (name, value) in env {
+            channel.set_env(false, name, value).await?;
+        }
+        Self::new(channel).await
     }
 
     /// Creates a new client from a stream ([`AsyncRead`] + [`AsyncWrite`]).
     pub async fn with_stream(
+        stream: impl AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+    ) -> Result<Self, Error> {
+        Self::with_stream_config(stream, SftpClientConfig::default()).await
+    }
+
+    /// Creates a new client from a stream ([`AsyncRead`] + [`AsyncWrite`]),
+    /// with a custom configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream used to communicate with the server
+    /// * `config` - Configuration of the client
+    pub async fn with_stream_config(
+        stream: impl AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+        config: SftpClientConfig,
+    ) -> Result<Self, Error> {
+        Self::with_stream_config_prebuffered(stream, config, Bytes::new()).await
+    }
+
+    /// Creates a new client from a stream ([`AsyncRead`] + [`AsyncWrite`]),
+    /// with a custom configuration, and where some bytes sent by the server have already been read.
+    ///
+    /// This is useful when the caller has peeked at the stream before handing it to the client
+    /// (eg: to detect the protocol spoken by the server).
+    /// The `prebuffered` bytes are processed as if they were the first bytes read from the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream used to communicate with the server
+    /// * `config` - Configuration of the client
+    /// * `prebuffered` - Bytes already read from the stream
+    pub async fn with_stream_config_prebuffered(
         mut stream: impl AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+        config: SftpClientConfig,
+        prebuffered: Bytes,
     ) -> Result<Self, Error> {
-        // Init SFTP handshake
-        receiver::write_msg(
-            &mut stream,
-            Message::Init(Init {
-                version: 3,
-                extensions: Default::default(),
-            }),
-            3,
-        )
-        .await?;
+        config.validate()?;
 
-        match receiver::read_msg(&mut stream).await? {
-            // Valid response: continue
-            (
-                _,
-                Message::Version(Version {
+        // The bytes already read are consumed before the stream
+        let mut prebuffered = prebuffered.as_ref();
+
+        // Init SFTP handshake
+        let handshake = async {
+            receiver::write_msg(
+                &mut stream,
+                &Message::Init(Init {
                     version: 3,
-                    extensions: _,
+                    extensions: config.init_extensions.clone(),
                 }),
-            ) => (),
+                3,
+                None,
+            )
+            .await?;
+
+            read_version(&mut (&mut prebuffered).chain(&mut stream)).await
+        };
+        let version = match config.handshake_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handshake)
+                .await
+                .map_err(|_| {
+                    Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Timed out while waiting for the SFTP version of the server",
+                    ))
+                })??,
+            None => handshake.await?,
+        };
+
+        let extensions = match version {
+            // Valid response: continue
+            Message::Version(Version {
+                version: 3,
+                extensions,
+            }) => extensions,
 
             // Invalid responses: abort
-            (_, Message::Version(_)) => {
+            Message::Version(Version { version, .. }) => {
                 return Err(StatusCode::BadMessage
-                    .to_status("Invalid sftp version")
+                    .to_status(format!(
+                        "Invalid sftp version: server replied with version {version}, but only version 3 is supported"
+                    ))
                     .into());
             }
-            _ => {
-                return Err(StatusCode::BadMessage.to_status("Bad SFTP init").into());
+            reply => {
+                return Err(StatusCode::BadMessage
+                    .to_status(format!(
+                        "Bad SFTP init: expected a Version reply, but received {:?} (code {})",
+                        reply.kind(),
+                        reply.code(),
+                    ))
+                    .into());
             }
-        }
+        };
 
-        let (receiver, tx) = receiver::Receiver::new(stream);
+        let metrics = Arc::new(metrics::MetricsCounters::default());
+        let session = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+        let last_id = Arc::new(AtomicU32::new(0));
+        let (receiver, tx) = receiver::Receiver::new(
+            stream,
+            prebuffered,
+            metrics.clone(),
+            &config,
+            last_id.clone(),
+        );
         let request_processor = tokio::spawn(receiver.run());
 
-        Ok(Self {
+        let use_server_limits = config.use_server_limits;
+        let mut client = Self {
             commands: Some(tx),
             request_processor: Some(Arc::new(request_processor)),
-        })
+            metrics: Some(metrics),
+            config,
+            cwd: None,
+            limits: None,
+            session,
+            last_id: Some(last_id),
+            openssh: extensions
+                .keys()
+                .any(|name| name.ends_with(b"@openssh.com")),
+        };
+
+        if use_server_limits && extensions.contains_key(b"limits@openssh.com".as_slice()) {
+            match client.query_limits().await {
+                Ok(limits) => client.limits = Some(limits),
+                Err(err) => log::debug!("Could not get the limits of the server: {err:?}"),
+            }
+        }
+
+        Ok(client)
+    }
+
+    /// Get the configuration of the client.
+    pub fn config(&self) -> &SftpClientConfig {
+        &self.config
+    }
+
+    /// Get the identifier of the SFTP session of the client.
+    ///
+    /// Each session has a unique identifier, shared by all the clones of its client.
+    /// Handles are only valid on the session that opened them:
+    /// two clients can share handles only if they have the same identifier.
+    /// Clients created with [`SftpClient::new_stopped`] have the identifier `0`.
+    pub fn session_id(&self) -> u64 {
+        self.session
+    }
+}
+
+/// Maximal size of the `Version` frame sent by the server during the handshake
+const MAX_VERSION_LENGTH: u32 = 256 * 1024;
+
+/// Read the reply of the server to the `Init` request.
+///
+/// Endpoints that are not SFTP servers (eg: a shell printing a banner) send arbitrary bytes
+/// that are interpreted as a huge frame length: such frames are rejected before being read,
+/// and the error shows the received bytes.
+async fn read_version(stream: &mut (impl AsyncRead + Unpin)) -> Result<Message, Error> {
+    let length = stream.read_u32().await?;
+    if length > MAX_VERSION_LENGTH {
+        return Err(StatusCode::BadMessage
+            .to_status(format!(
+                "Bad SFTP init: received a frame of {length} bytes, the endpoint might not be a SFTP server (received {:?})",
+                Bytes::copy_from_slice(&length.to_be_bytes()),
+            ))
+            .into());
+    }
+
+    let mut bytes = vec![0u8; length as usize];
+    stream.read_exact(bytes.as_mut_slice()).await?;
+
+    match Message::decode_raw(bytes.as_slice()) {
+        Ok((_, message)) => Ok(message),
+        Err(err) => {
+            bytes.truncate(64);
+            Err(StatusCode::BadMessage
+                .to_status(format!(
+                    "Bad SFTP init: could not decode the reply of the server ({}), starting with {:?}",
+                    err.inner,
+                    Bytes::from(bytes),
+                ))
+                .into())
+        }
     }
 }
 
@@ -0,0 +1,95 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Origin of the handles opened by a client.
+
+use crate::client::{Error, SftpClient};
+use crate::message::{Handle, StatusCode};
+
+/// What a handle designates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum HandleKind {
+    File,
+    Dir,
+}
+
+/// Session and kind of the request that opened a handle.
+///
+/// Handles are only valid on the session that opened them,
+/// so the origin is kept next to the handle, and checked before the handle is given to another client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct HandleOrigin {
+    /// Identifier of the session (see [`SftpClient::session_id`])
+    pub(crate) session: u64,
+    pub(crate) kind: HandleKind,
+}
+
+impl HandleOrigin {
+    /// Origin of the files closed from the start, attached to a stopped client
+    pub(crate) const STOPPED_FILE: Self = Self {
+        session: 0,
+        kind: HandleKind::File,
+    };
+
+    /// Origin of the directories closed from the start, attached to a stopped client
+    pub(crate) const STOPPED_DIR: Self = Self {
+        session: 0,
+        kind: HandleKind::Dir,
+    };
+
+    /// Origin of a handle of `kind` opened by `client`.
+    pub(crate) fn new(client: &SftpClient, kind: HandleKind) -> Self {
+        Self {
+            session: client.session,
+            kind,
+        }
+    }
+
+    /// Check that `handle`, with this origin, can be used with `client` by a request expecting `kind`.
+    ///
+    /// Returns a `BrokenPipe` error if the handle is closed (`None`),
+    /// and a `BadMessage` status if the session or the kind do not match.
+    pub(crate) fn check(
+        &self,
+        client: &SftpClient,
+        handle: Option<&Handle>,
+        kind: HandleKind,
+    ) -> Result<Handle, Error> {
+        let Some(handle) = handle else {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "Handle was already closed",
+            )));
+        };
+        if client.session != self.session {
+            return Err(StatusCode::BadMessage
+                .to_status(format!(
+                    "Handle of SFTP session {} used with SFTP session {}",
+                    self.session, client.session
+                ))
+                .into());
+        }
+        match (self.kind, kind) {
+            (HandleKind::File, HandleKind::Dir) => Err(StatusCode::BadMessage
+                .to_status("File handle used as a directory handle")
+                .into()),
+            (HandleKind::Dir, HandleKind::File) => Err(StatusCode::BadMessage
+                .to_status("Directory handle used as a file handle")
+                .into()),
+            _ => Ok(handle.clone()),
+        }
+    }
+}
@@ -0,0 +1,546 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::FutureExt;
+
+use crate::client::{
+    Dir, Error, File, HandleKind, HandleOrigin, SftpClient, SftpFuture, SftpReply, SftpRequest,
+};
+use crate::message::{Attrs, Data, Handle, Message, Name, Open, OpenDir, PFlags, Path, StatusCode};
+use crate::utils::IntoBytes;
+
+/// Pool of SFTP clients sharing the same SSH connection.
+///
+/// Each client of the pool has its own SSH channel, and thus its own SFTP session.
+/// Requests are distributed across the clients in a round-robin fashion,
+/// so that multiple byte streams can be used concurrently:
+/// the commands of the pool (eg: [`SftpPool::stat`]) are sent by the next client of the pool,
+/// which can also be selected explicitly with [`SftpPool::client`].
+///
+/// # Handles
+///
+/// Handles are only valid on the session that created them.
+/// [`File`] and [`Dir`] keep the client they were opened with,
+/// so all their operations are routed to the right channel.
+/// [`SftpPool::open`] and [`SftpPool::opendir`] return them bound to the client that has opened them.
+///
+/// Raw handles are returned as [`PoolHandle`]s, which remember the session that has opened them.
+/// The handle commands of the pool (eg: [`SftpPool::read`]) are sent by this session,
+/// whatever the next client of the pool.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn dummy(ssh: russh::client::Handle<impl russh::client::Handler>) -> Result<(), rusftp::client::Error> {
+/// let sftp = rusftp::client::SftpPool::new(&ssh, 4).await?;
+/// let (a, b) = tokio::join!(sftp.client().stat("a"), sftp.client().stat("b"));
+/// let file = sftp.open("a").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SftpPool {
+    clients: Vec<SftpClient>,
+    next: Arc<AtomicUsize>,
+}
+
+impl SftpPool {
+    /// Creates a pool of `size` clients from a ssh connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `ssh` - SSH connection used to open the channels
+    /// * `size` - Number of channels to open (must be at least 1)
+    pub async fn new<H: russh::client::Handler>(
+        ssh: &russh::client::Handle<H>,
+        size: usize,
+    ) -> Result<Self, Error> {
+        if size == 0 {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "SFTP pool must have at least one client",
+            )));
+        }
+
+        let clients =
+            futures::future::try_join_all((0..size).map(|_| SftpClient::new(ssh))).await?;
+
+        Ok(Self::with_clients(clients))
+    }
+
+    /// Creates a pool from already created clients.
+    ///
+    /// The pool is stopped if `clients` is empty.
+    pub fn with_clients(clients: impl IntoIterator<Item = SftpClient>) -> Self {
+        Self {
+            clients: clients.into_iter().collect(),
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Number of clients in the pool.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Check whether the pool has no client.
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Get the next client of the pool.
+    ///
+    /// If the pool is empty, a stopped client is returned.
+    pub fn client(&self) -> &SftpClient {
+        if self.clients.is_empty() {
+            return &super::SFTP_CLIENT_STOPPED;
+        }
+
+        let i = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.clients[i % self.clients.len()]
+    }
+
+    /// Get the client of the pool that has opened `handle`.
+    ///
+    /// Returns a `BadMessage` status if the handle was opened outside of the pool,
+    /// or does not designate the expected `kind` of object.
+    fn route(&self, handle: &PoolHandle, kind: HandleKind) -> Result<(&SftpClient, Handle), Error> {
+        let Some(client) = self
+            .clients
+            .iter()
+            .find(|client| client.session == handle.origin.session)
+        else {
+            return Err(StatusCode::BadMessage
+                .to_status(format!(
+                    "Handle of SFTP session {} is not from this pool",
+                    handle.origin.session
+                ))
+                .into());
+        };
+        let handle = handle.origin.check(client, Some(&handle.handle), kind)?;
+        Ok((client, handle))
+    }
+
+    /// Open a file on the next client of the pool, and get its raw handle.
+    ///
+    /// See [`SftpClient::open_handle`].
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Path of the file to open
+    /// * `pflags` - Flags for the file opening
+    /// * `attrs` - Default file attributes to use upon file creation
+    pub fn open_handle(
+        &self,
+        filename: impl Into<Path>,
+        pflags: PFlags,
+        attrs: Attrs,
+    ) -> SftpFuture<PoolHandle, SftpClient> {
+        let client = self.client();
+        client.request_with(
+            Open {
+                filename: client.resolve_path(filename),
+                pflags,
+                attrs,
+            }
+            .to_request_message(),
+            client.clone(),
+            |client, msg| PoolHandle::from_reply(&client, msg, HandleKind::File),
+        )
+    }
+
+    /// Open a directory on the next client of the pool, and get its raw handle.
+    ///
+    /// See [`SftpClient::opendir_handle`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to open
+    pub fn opendir_handle(&self, path: impl Into<Path>) -> SftpFuture<PoolHandle, SftpClient> {
+        let client = self.client();
+        client.request_with(
+            OpenDir {
+                path: client.resolve_path(path),
+            }
+            .to_request_message(),
+            client.clone(),
+            |client, msg| PoolHandle::from_reply(&client, msg, HandleKind::Dir),
+        )
+    }
+
+    /// Close a raw handle on the session that has opened it.
+    ///
+    /// See [`SftpClient::close`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle to close
+    pub fn close(&self, handle: PoolHandle) -> SftpFuture {
+        let kind = handle.origin.kind;
+        match self.route(&handle, kind) {
+            Ok((client, handle)) => client.close(handle),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Read from a raw file handle, on the session that has opened it.
+    ///
+    /// See [`SftpClient::read`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the file
+    /// * `offset` - Position in the file where to start reading
+    /// * `length` - Number of bytes to read
+    pub fn read(&self, handle: &PoolHandle, offset: u64, length: u32) -> SftpFuture<Bytes> {
+        match self.route(handle, HandleKind::File) {
+            Ok((client, handle)) => client.read(handle, offset, length),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Write to a raw file handle, on the session that has opened it.
+    ///
+    /// See [`SftpClient::write`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the file
+    /// * `offset` - Position in the file where to start writing
+    /// * `data` - Data to write
+    pub fn write(&self, handle: &PoolHandle, offset: u64, data: impl Into<Data>) -> SftpFuture {
+        match self.route(handle, HandleKind::File) {
+            Ok((client, handle)) => client.write(handle, offset, data),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Get the attributes of a raw file handle, on the session that has opened it.
+    ///
+    /// See [`SftpClient::fstat`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the file
+    pub fn fstat(&self, handle: &PoolHandle) -> SftpFuture<Attrs> {
+        match self.route(handle, HandleKind::File) {
+            Ok((client, handle)) => client.fstat(handle),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Set the attributes of a raw file handle, on the session that has opened it.
+    ///
+    /// See [`SftpClient::fsetstat`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the file
+    /// * `attrs` - New attributes to apply
+    pub fn fsetstat(&self, handle: &PoolHandle, attrs: Attrs) -> SftpFuture {
+        match self.route(handle, HandleKind::File) {
+            Ok((client, handle)) => client.fsetstat(handle, attrs),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Read a batch of entries from a raw directory handle, on the session that has opened it.
+    ///
+    /// See [`SftpClient::readdir_handle`].
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Handle of the directory
+    pub fn readdir_handle(&self, handle: &PoolHandle) -> SftpFuture<Name> {
+        match self.route(handle, HandleKind::Dir) {
+            Ok((client, handle)) => client.readdir_handle(handle),
+            Err(err) => SftpFuture::Error(err),
+        }
+    }
+
+    /// Open a file on the next client of the pool.
+    ///
+    /// The file keeps the client that has opened it.
+    /// See [`SftpClient::open_with_flags_attrs`].
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Path of the file to open
+    /// * `pflags` - Flags for the file opening
+    /// * `attrs` - Default file attributes to use upon file creation
+    pub fn open_with_flags_attrs(
+        &self,
+        filename: impl Into<Path>,
+        pflags: PFlags,
+        attrs: Attrs,
+    ) -> SftpFuture<File, SftpClient> {
+        self.client().open_with_flags_attrs(filename, pflags, attrs)
+    }
+
+    /// Open a file on the next client of the pool.
+    ///
+    /// The file keeps the client that has opened it.
+    /// See [`SftpClient::open_with_flags`].
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Path of the file to open
+    /// * `pflags` - Flags for the file opening
+    pub fn open_with_flags(
+        &self,
+        filename: impl Into<Path>,
+        pflags: PFlags,
+    ) -> SftpFuture<File, SftpClient> {
+        self.client().open_with_flags(filename, pflags)
+    }
+
+    /// Open a file for reading on the next client of the pool.
+    ///
+    /// The file keeps the client that has opened it.
+    /// See [`SftpClient::open`].
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Path of the file to open
+    pub fn open(&self, filename: impl Into<Path>) -> SftpFuture<File, SftpClient> {
+        self.client().open(filename)
+    }
+
+    /// Open a directory for listing on the next client of the pool.
+    ///
+    /// The directory keeps the client that has opened it.
+    /// See [`SftpClient::opendir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to open
+    pub fn opendir(&self, path: impl Into<Path>) -> SftpFuture<Dir, SftpClient> {
+        self.client().opendir(path)
+    }
+
+    /// Send an extended request on the next client of the pool.
+    ///
+    /// See [`SftpClient::extended`].
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - Name of the extended request
+    /// * `data` - Payload of the request
+    pub fn extended(&self, request: impl IntoBytes, data: impl IntoBytes) -> SftpFuture<Bytes> {
+        self.client().extended(request, data)
+    }
+
+    /// Get the attributes of a file, without following symlinks, on the next client of the pool.
+    ///
+    /// See [`SftpClient::lstat`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    pub fn lstat(&self, path: impl Into<Path>) -> SftpFuture<Attrs> {
+        self.client().lstat(path)
+    }
+
+    /// Create a directory on the next client of the pool.
+    ///
+    /// See [`SftpClient::mkdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to create
+    pub fn mkdir(&self, path: impl Into<Path>) -> SftpFuture {
+        self.client().mkdir(path)
+    }
+
+    /// Create a directory with the given attributes, on the next client of the pool.
+    ///
+    /// See [`SftpClient::mkdir_with_attrs`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to create
+    /// * `attrs` - Attributes of the directory
+    pub fn mkdir_with_attrs(&self, path: impl Into<Path>, attrs: Attrs) -> SftpFuture {
+        self.client().mkdir_with_attrs(path, attrs)
+    }
+
+    /// List a directory on the next client of the pool.
+    ///
+    /// See [`SftpClient::readdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to list
+    pub fn readdir(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<Name, Error>> + Send + Sync + 'static {
+        self.client().readdir(path)
+    }
+
+    /// Read the target of a symlink on the next client of the pool.
+    ///
+    /// See [`SftpClient::readlink`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the symlink
+    pub fn readlink(&self, path: impl Into<Path>) -> SftpFuture<Path> {
+        self.client().readlink(path)
+    }
+
+    /// Canonicalize a path on the next client of the pool.
+    ///
+    /// See [`SftpClient::realpath`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to canonicalize
+    pub fn realpath(&self, path: impl Into<Path>) -> SftpFuture<Path> {
+        self.client().realpath(path)
+    }
+
+    /// Remove a file on the next client of the pool.
+    ///
+    /// See [`SftpClient::remove`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file to remove
+    pub fn remove(&self, path: impl Into<Path>) -> SftpFuture {
+        self.client().remove(path)
+    }
+
+    /// Rename a file on the next client of the pool.
+    ///
+    /// See [`SftpClient::rename`].
+    ///
+    /// # Arguments
+    ///
+    /// * `old_path` - Current path of the file
+    /// * `new_path` - New path of the file
+    pub fn rename(&self, old_path: impl Into<Path>, new_path: impl Into<Path>) -> SftpFuture {
+        self.client().rename(old_path, new_path)
+    }
+
+    /// Remove a directory on the next client of the pool.
+    ///
+    /// See [`SftpClient::rmdir`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the directory to remove
+    pub fn rmdir(&self, path: impl Into<Path>) -> SftpFuture {
+        self.client().rmdir(path)
+    }
+
+    /// Set the attributes of a file on the next client of the pool.
+    ///
+    /// See [`SftpClient::setstat`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    /// * `attrs` - New attributes to apply
+    pub fn setstat(&self, path: impl Into<Path>, attrs: Attrs) -> SftpFuture {
+        self.client().setstat(path, attrs)
+    }
+
+    /// Get the attributes of a file on the next client of the pool.
+    ///
+    /// See [`SftpClient::stat`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the file
+    pub fn stat(&self, path: impl Into<Path>) -> SftpFuture<Attrs> {
+        self.client().stat(path)
+    }
+
+    /// Create a symlink on the next client of the pool.
+    ///
+    /// See [`SftpClient::symlink`].
+    ///
+    /// # Arguments
+    ///
+    /// * `link_path` - Path of the symlink to create
+    /// * `target_path` - Target of the symlink
+    pub fn symlink(&self, link_path: impl Into<Path>, target_path: impl Into<Path>) -> SftpFuture {
+        self.client().symlink(link_path, target_path)
+    }
+
+    /// Get all the clients of the pool.
+    pub fn clients(&self) -> &[SftpClient] {
+        &self.clients
+    }
+
+    /// Stop all the clients of the pool.
+    ///
+    /// See [`SftpClient::stop`].
+    pub fn stop(&mut self) -> impl Future<Output = ()> + Send + Sync + '_ {
+        futures::future::join_all(self.clients.iter_mut().map(SftpClient::stop)).map(|_| ())
+    }
+
+    /// Check whether all the clients of the pool are stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.clients.iter().all(SftpClient::is_stopped)
+    }
+}
+
+impl std::fmt::Debug for SftpPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SftpPool({})", self.clients.len())
+    }
+}
+
+/// Raw handle opened by a client of a [`SftpPool`].
+///
+/// The handle remembers the session that has opened it, and whether it designates a file or a directory,
+/// so that the handle commands of the pool are routed to this session.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolHandle {
+    handle: Handle,
+    origin: HandleOrigin,
+}
+
+impl PoolHandle {
+    fn from_reply(client: &SftpClient, msg: Message, kind: HandleKind) -> Result<Self, Error> {
+        Ok(Self {
+            handle: Handle::from_reply_message(msg)?,
+            origin: HandleOrigin::new(client, kind),
+        })
+    }
+
+    /// Get the raw [`Handle`].
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Get the identifier of the session that has opened the handle (see [`SftpClient::session_id`]).
+    pub fn session_id(&self) -> u64 {
+        self.origin.session
+    }
+
+    /// Get the raw [`Handle`], to be used with the client of the pool that has opened it.
+    pub fn into_handle(self) -> Handle {
+        self.handle
+    }
+}
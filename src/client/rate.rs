@@ -0,0 +1,139 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+
+use crate::client::receiver::Command;
+use crate::client::SftpClient;
+use crate::message::Message;
+
+impl SftpClient {
+    /// Limit the bandwidth used by file reads and writes.
+    ///
+    /// The limit applies to the whole SFTP session, including all the clones of the client,
+    /// and replaces any previous limit, including the one from [`SftpClientConfig::rate_limit`](crate::client::SftpClientConfig::rate_limit).
+    /// The configuration of the returned client is updated accordingly.
+    ///
+    /// `Read` and `Write` requests are delayed to stay under `bytes_per_sec` on average,
+    /// with bursts of at most one second worth of data.
+    /// This applies to [`File`](crate::client::File) I/O, to the transfer helpers
+    /// (eg: [`SftpClient::upload_stream`]), and to raw `read` and `write` requests.
+    /// Requests are sent in order, so other requests sent after a delayed one are also delayed.
+    /// Cancellations are not delayed.
+    ///
+    /// Only the payload bytes are counted (the requested length of reads, and the data of writes),
+    /// not the protocol overhead.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes_per_sec` - Maximal average number of payload bytes per second (unlimited if `None`)
+    pub fn with_rate_limit(mut self, bytes_per_sec: Option<u64>) -> Self {
+        if let Some(commands) = &self.commands {
+            _ = commands.send(Command::RateLimit(bytes_per_sec));
+        }
+        self.config.rate_limit = bytes_per_sec;
+        self
+    }
+}
+
+/// Token bucket limiting the payload bytes of the requests.
+///
+/// The bucket can go into debt: a request is sent as soon as the bucket is not empty,
+/// and the following requests wait until the debt has been paid back.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+    bytes_per_sec: Option<u64>,
+    tokens: f64,
+    last_refill: Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl RateLimiter {
+    pub(super) fn new(bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.filter(|rate| *rate > 0),
+            tokens: 0.0,
+            last_refill: Instant::now(),
+            sleep: None,
+        }
+    }
+
+    /// Check whether the bandwidth is limited.
+    pub(super) fn is_limited(&self) -> bool {
+        self.bytes_per_sec.is_some()
+    }
+
+    /// Change the limit.
+    pub(super) fn set_limit(&mut self, bytes_per_sec: Option<u64>) {
+        *self = Self::new(bytes_per_sec);
+    }
+
+    /// Check whether a new request can be sent.
+    pub(super) fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        let Some(rate) = self.bytes_per_sec else {
+            return Poll::Ready(());
+        };
+
+        self.refill(rate);
+        if self.tokens >= 0.0 {
+            self.sleep = None;
+            return Poll::Ready(());
+        }
+
+        // Wait until the debt is paid back
+        let delay = Duration::from_secs_f64(-self.tokens / rate as f64);
+        let deadline = Instant::now() + delay;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+        if sleep.deadline() != deadline {
+            sleep.as_mut().reset(deadline);
+        }
+
+        std::task::ready!(sleep.as_mut().poll(cx));
+        self.sleep = None;
+        Poll::Ready(())
+    }
+
+    /// Account for a request that has been sent.
+    pub(super) fn consume(&mut self, message: &Message) {
+        if self.bytes_per_sec.is_none() {
+            return;
+        }
+
+        let bytes = match message {
+            Message::Read(read) => read.length as f64,
+            Message::Write(write) => write.data.len() as f64,
+            _ => return,
+        };
+        self.tokens -= bytes;
+    }
+
+    /// Add the tokens accumulated since the last refill, up to one second worth of data.
+    fn refill(&mut self, rate: u64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        self.last_refill = now;
+
+        let capacity = rate as f64;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * capacity).min(capacity);
+    }
+}
@@ -14,51 +14,152 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::pin::Pin;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
 use std::task::Poll;
+use std::time::Duration;
 
 use bytes::{Buf, Bytes, BytesMut};
-use futures::{Stream, StreamExt};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures::{Future, Stream, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
 
-use crate::client::Error;
-use crate::message::{Message, StatusCode};
+use crate::client::frame::FrameHook;
+use crate::client::framing::FrameReader;
+use crate::client::metadata_cache::MetadataCache;
+use crate::client::metrics::MetricsCounters;
+use crate::client::rate::RateLimiter;
+use crate::client::redact::{LogFrame, LogMessage};
+use crate::client::request::next_id;
+use crate::client::{Error, FrameDirection, SftpClientConfig};
+use crate::message::{Close, Handle, Message, StatusCode};
 
 pub(super) type Response = Result<Message, Error>;
-pub struct Request(pub(super) Message, pub(super) oneshot::Sender<Response>);
+
+/// Maximal number of requests sent at once
+const MAX_BATCH_LENGTH: usize = 64;
+
+/// Maximal number of cancelled requests whose late reply is expected
+///
+/// Beyond, the oldest ones are forgotten, and their reply is logged as having an invalid id.
+const MAX_CANCELLED: usize = 4096;
+pub struct Request(
+    pub(super) u32,
+    pub(super) Message,
+    pub(super) oneshot::Sender<Response>,
+    pub(super) Option<u64>,
+);
+
+/// Command sent from the clients to the receiver
+pub enum Command {
+    /// Send a new request to the server
+    Request(Request),
+    /// Cancel the pending request associated with the cancellation key
+    Cancel(u64),
+    /// Replace the hook invoked for every frame
+    FrameHook(Option<FrameHook>),
+    /// Replace the bandwidth limit of reads and writes
+    RateLimit(Option<u64>),
+    /// Replace the idle time after which the client is stopped
+    IdleTimeout(Option<Duration>),
+    /// Replace the cache of metadata replies (time to live and capacity)
+    MetadataCache(Duration, usize),
+}
+
+/// Request sent to the server, waiting for its reply
+struct Onflight {
+    tx: oneshot::Sender<Response>,
+    cancel_key: Option<u64>,
+    /// Number of bytes requested, if the request is a read
+    read_length: Option<u32>,
+}
 
 pub(super) struct Receiver<S> {
-    onflight: HashMap<u32, oneshot::Sender<Response>>,
-    next_id: u32,
-    commands: mpsc::UnboundedReceiver<Request>,
+    onflight: HashMap<u32, Onflight>,
+    cancellable: HashMap<u64, u32>,
+    cancelled: HashSet<u32>,
+    /// Cancelled ids, in the order they were cancelled
+    cancelled_order: VecDeque<u32>,
+    commands: mpsc::UnboundedReceiver<Command>,
+    /// Requests received while they could not be sent, waiting for the limits to allow them
+    queued: VecDeque<Request>,
     stream: S,
-    response_size: Option<u32>,
-    response_buffer: BytesMut,
+    frames: FrameReader,
+    metrics: Arc<MetricsCounters>,
+    max_in_flight: usize,
+    request_timeout: Option<Duration>,
+    request_deadlines: VecDeque<(Instant, u32)>,
+    request_deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    frame_hook: Option<FrameHook>,
+    rate_limiter: RateLimiter,
+    redact_logs: bool,
+    idle_timeout: Option<Duration>,
+    idle_deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    metadata_cache: MetadataCache,
+    /// Id of the last request sent on the session, shared with the clients
+    last_id: Arc<AtomicU32>,
 }
 
 impl<S> Receiver<S> {
     /// Create a new receiver
-    pub(super) fn new(stream: S) -> (Self, mpsc::UnboundedSender<Request>) {
+    ///
+    /// `prebuffered` contains the bytes already read from the stream.
+    pub(super) fn new(
+        stream: S,
+        prebuffered: &[u8],
+        metrics: Arc<MetricsCounters>,
+        config: &SftpClientConfig,
+        last_id: Arc<AtomicU32>,
+    ) -> (Self, mpsc::UnboundedSender<Command>) {
         let (tx, rx) = mpsc::unbounded_channel();
         (
             Self {
                 onflight: HashMap::new(),
-                next_id: 0,
+                cancellable: HashMap::new(),
+                cancelled: HashSet::new(),
+                cancelled_order: VecDeque::new(),
                 commands: rx,
+                queued: VecDeque::new(),
                 stream,
-                response_size: None,
-                response_buffer: Default::default(),
+                frames: FrameReader::new(prebuffered, config),
+                metrics,
+                max_in_flight: config.max_in_flight,
+                request_timeout: config.request_timeout,
+                request_deadlines: VecDeque::new(),
+                request_deadline: None,
+                frame_hook: None,
+                rate_limiter: RateLimiter::new(config.rate_limit),
+                redact_logs: config.redact_logs,
+                idle_timeout: config.idle_timeout,
+                idle_deadline: None,
+                metadata_cache: MetadataCache::new(config),
+                last_id,
             },
             tx,
         )
     }
+
+    /// Discard the late reply of the request `id`, if it is ever received.
+    fn mark_cancelled(&mut self, id: u32) {
+        if self.cancelled.insert(id) {
+            self.cancelled_order.push_back(id);
+        }
+        while self.cancelled_order.len() > MAX_CANCELLED {
+            if let Some(oldest) = self.cancelled_order.pop_front() {
+                self.cancelled.remove(&oldest);
+            }
+        }
+    }
 }
 
 pub enum StreamItem {
-    Request(Request),
+    Command(Command),
     Response(Bytes),
+    Timeout,
+    Idle,
     Error(std::io::Error),
 }
 
@@ -69,74 +170,132 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Stream for Receiver<S> {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
-        // Check if new commands have been sent
-        match self.commands.poll_recv(cx) {
-            Poll::Ready(Some(request)) => {
-                return Poll::Ready(Some(StreamItem::Request(request)));
-            }
-            Poll::Ready(None) => {
-                // If commands are closed and no request is on-flight,
-                // No more messages could be received
-                if self.onflight.is_empty() {
-                    return Poll::Ready(None);
-                }
+        // The stream has stalled in the middle of a frame: no more messages can be received
+        if self.frames.is_stalled() {
+            return Poll::Ready(None);
+        }
+
+        // Requests can be sent, unless too many requests are already on-flight, or the bandwidth limit is reached
+        let can_send =
+            self.onflight.len() < self.max_in_flight && self.rate_limiter.poll_ready(cx).is_ready();
+        if can_send {
+            if let Some(request) = self.queued.pop_front() {
+                return Poll::Ready(Some(StreamItem::Command(Command::Request(request))));
             }
-            Poll::Pending => (),
-        };
+        }
 
-        // No command was available, trying to read responses from the stream
+        // Check if new commands have been sent:
+        // the requests that cannot be sent yet are queued, but the other commands are processed immediately
         loop {
-            let new_len;
-            match self.response_size {
-                // A size has already been read from the stream
-                Some(response_size) => {
-                    if self.response_buffer.len() >= response_size as usize {
-                        self.response_size = None;
-                        let response = self.response_buffer.split_to(response_size as usize);
-                        return Poll::Ready(Some(StreamItem::Response(response.freeze())));
-                    }
-                    new_len = response_size as usize;
+            match self.commands.poll_recv(cx) {
+                Poll::Ready(Some(Command::Request(request))) if !can_send => {
+                    self.queued.push_back(request);
+                }
+                Poll::Ready(Some(command)) => {
+                    return Poll::Ready(Some(StreamItem::Command(command)));
                 }
-                // Must read the size of the frame from the stream
-                None => {
-                    if self.response_buffer.len() >= std::mem::size_of::<u32>() {
-                        let len = self.response_buffer.get_u32();
-                        self.response_size = Some(len);
-                        continue;
+                Poll::Ready(None) => {
+                    // If commands are closed and no request is on-flight or queued,
+                    // No more messages could be received
+                    if self.onflight.is_empty() && self.queued.is_empty() {
+                        return Poll::Ready(None);
                     }
-                    new_len = std::mem::size_of::<u32>();
+                    break;
                 }
-            }
+                Poll::Pending => break,
+            };
+        }
+
+        // Check if the oldest request has timed out
+        if self.poll_request_timeout(cx).is_ready() {
+            return Poll::Ready(Some(StreamItem::Timeout));
+        }
+
+        // Check if the client has been idle for too long
+        if self.poll_idle_timeout(cx).is_ready() {
+            return Poll::Ready(Some(StreamItem::Idle));
+        }
 
-            let old_len = self.response_buffer.len();
+        // No command was available, trying to read responses from the stream
+        let this = &mut *self;
+        let frame = futures::ready!(this.frames.poll_frame(&mut this.stream, cx));
+        Poll::Ready(frame.map(|frame| match frame {
+            Ok(response) => StreamItem::Response(response),
+            Err(err) => StreamItem::Error(err),
+        }))
+    }
+}
 
-            // taking is required to avoid borrowing multiple times `self`
-            let mut buffer = std::mem::take(&mut self.response_buffer);
+impl<S> Receiver<S> {
+    /// Check if no request has been on-flight for the idle timeout.
+    ///
+    /// The idle time starts once the last on-flight request has been replied.
+    fn poll_idle_timeout(&mut self, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        let Some(timeout) = self.idle_timeout else {
+            self.idle_deadline = None;
+            return Poll::Pending;
+        };
 
-            // tries to read the whole frame, or at least the next kilobyte
-            buffer.resize(new_len.max(1024), 0);
-            let mut read_buf = tokio::io::ReadBuf::new(&mut buffer[old_len..]);
-            let read = Pin::new(&mut self.stream).poll_read(cx, &mut read_buf);
+        if !self.onflight.is_empty() || !self.queued.is_empty() {
+            self.idle_deadline = None;
+            return Poll::Pending;
+        }
 
-            // Adjust buffer size according to what was read
-            let len = read_buf.filled().len();
-            buffer.resize(old_len + len, 0);
-            self.response_buffer = buffer;
+        let deadline = self
+            .idle_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        deadline.as_mut().poll(cx)
+    }
 
-            // Check status of reading
-            match read {
-                Poll::Ready(Ok(())) => (),
-                Poll::Ready(Err(err)) => {
-                    return Poll::Ready(Some(StreamItem::Error(err)));
-                }
-                Poll::Pending => {
-                    return Poll::Pending;
+    /// Check if the deadline of the oldest on-flight request has elapsed.
+    fn poll_request_timeout(&mut self, cx: &mut std::task::Context<'_>) -> Poll<()> {
+        let Some(&(deadline, _)) = self.request_deadlines.front() else {
+            self.request_deadline = None;
+            return Poll::Pending;
+        };
+
+        match &mut self.request_deadline {
+            Some(sleep) => {
+                if sleep.deadline() != deadline {
+                    sleep.as_mut().reset(deadline);
                 }
             }
+            None => {
+                self.request_deadline = Some(Box::pin(tokio::time::sleep_until(deadline)));
+            }
+        }
 
-            // EoF
-            if len == old_len {
-                return Poll::Ready(None);
+        match &mut self.request_deadline {
+            Some(sleep) => sleep.as_mut().poll(cx),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Fail all the on-flight requests whose deadline has elapsed.
+    ///
+    /// Late replies to those requests are silently discarded.
+    fn expire_requests(&mut self) {
+        let now = Instant::now();
+        while let Some(&(deadline, id)) = self.request_deadlines.front() {
+            if deadline > now {
+                break;
+            }
+            self.request_deadlines.pop_front();
+
+            // The reply may have already been received
+            if let Some(Onflight { tx, cancel_key, .. }) = self.onflight.remove(&id) {
+                log::debug!("Request #{id} timed out");
+                if let Some(key) = cancel_key {
+                    self.cancellable.remove(&key);
+                }
+                self.mark_cancelled(id);
+                send_response(
+                    tx,
+                    Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Request has timed out",
+                    ))),
+                );
             }
         }
     }
@@ -147,50 +306,114 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Receiver<S> {
     pub(super) async fn run(mut self) {
         log::debug!("Start SFTP client");
 
+        // Error that stopped the receiver, if the stream has not been closed cleanly
+        let mut failure = None;
+
         // Read all the events
         while let Some(event) = self.next().await {
             match event {
                 // New request was received
-                StreamItem::Request(Request(message, tx)) => {
-                    self.next_id += 1;
-                    let id = self.next_id;
+                StreamItem::Command(Command::Request(request)) => {
+                    // Gather the requests already queued, to send them all at once
+                    let mut batch = vec![request];
+                    let mut next_command = None;
+                    while batch.len() < MAX_BATCH_LENGTH
+                        && self.onflight.len() + batch.len() < self.max_in_flight
+                        && !self.rate_limiter.is_limited()
+                    {
+                        let command = match self.queued.pop_front() {
+                            Some(request) => Ok(Command::Request(request)),
+                            None => self.commands.try_recv(),
+                        };
+                        match command {
+                            Ok(Command::Request(request)) => batch.push(request),
+                            Ok(command) => {
+                                next_command = Some(command);
+                                break;
+                            }
+                            Err(_) => break,
+                        }
+                    }
 
-                    log::trace!("Request #{id}: {message:?}");
+                    self.send_requests(batch).await;
 
-                    match write_msg(&mut self.stream, message, id).await {
-                        Ok(()) => {
-                            self.onflight.insert(id, tx);
-                        }
-                        Err(err) => {
-                            log::debug!("Could not send request #{id}: {err:?}");
-                            send_response(tx, Err(err));
-                        }
+                    // Commands must be processed in order
+                    if let Some(command) = next_command {
+                        self.process_command(command);
                     }
                 }
 
+                StreamItem::Command(command) => self.process_command(command),
+
+                // The oldest requests have timed out
+                StreamItem::Timeout => self.expire_requests(),
+
+                // No request has been sent for too long
+                StreamItem::Idle => {
+                    log::debug!("SFTP client idle: stopping");
+                    break;
+                }
+
                 // New response was received
-                StreamItem::Response(response) => match Message::decode_raw(response.as_ref()) {
-                    Ok((id, message)) => {
-                        log::trace!("Response #{id}: {message:?}");
-                        if let Some(tx) = self.onflight.remove(&id) {
-                            send_response(tx, Ok(message));
-                        } else {
-                            log::error!("SFTP Error: Received a reply with an invalid id");
-                        }
+                StreamItem::Response(response) => {
+                    if let Some(hook) = &self.frame_hook {
+                        hook(FrameDirection::Received, &response);
                     }
-                    Err(err) => {
-                        log::trace!("Failed to parse message: {response:?}: {err:?}");
-                        if let Some(id) = err.id {
-                            if let Some(tx) = self.onflight.remove(&id) {
-                                send_response(tx, Err(err.into()));
+
+                    match Message::decode_raw(response.as_ref()) {
+                        Ok((id, message)) => {
+                            log::trace!(
+                                "Response #{id}: {:?}",
+                                LogMessage {
+                                    message: &message,
+                                    redact: self.redact_logs
+                                }
+                            );
+                            self.metrics.response_received(&message);
+                            self.metadata_cache.reply(id, &message);
+                            let cancelled = self.cancelled.contains(&id);
+                            match self.take_onflight(id) {
+                                Some(onflight) => {
+                                    let reply = check_data_length(onflight.read_length, message);
+                                    match onflight.tx.send(reply) {
+                                        Ok(()) => (),
+                                        // The future was dropped: the handle would be leaked
+                                        Err(Ok(Message::Handle(handle))) => {
+                                            self.close_orphan(id, handle).await
+                                        }
+                                        Err(reply) => log::error!(
+                                            "Could not send back message to client: {reply:?}"
+                                        ),
+                                    }
+                                }
+                                // The request was cancelled or has timed out
+                                None if cancelled => {
+                                    if let Message::Handle(handle) = message {
+                                        self.close_orphan(id, handle).await
+                                    }
+                                }
+                                None => (),
+                            }
+                        }
+                        Err(err) => {
+                            log::trace!(
+                                "Failed to parse message: {:?}: {err:?}",
+                                LogFrame {
+                                    frame: &response,
+                                    redact: self.redact_logs
+                                }
+                            );
+                            if let Some(id) = err.id {
+                                self.metadata_cache.discard(id);
+                                if let Some(onflight) = self.take_onflight(id) {
+                                    send_response(onflight.tx, Err(err.into()));
+                                }
                             } else {
-                                log::error!("SFTP Error: Received a reply with an invalid id");
+                                log::error!("SFTP Error: Received a bad reply");
                             }
-                        } else {
-                            log::error!("SFTP Error: Received a bad reply");
                         }
                     }
-                },
+                }
 
                 // Error while receiving
                 StreamItem::Error(err) => {
@@ -201,19 +424,30 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Receiver<S> {
                         std::io::ErrorKind::WriteZero => (),
                         std::io::ErrorKind::Interrupted => (),
                         std::io::ErrorKind::OutOfMemory => (),
-                        _ => break,
+                        _ => {
+                            failure = Some(err);
+                            break;
+                        }
                     }
                 }
             }
         }
 
-        for (_, tx) in self.onflight {
-            send_response(
-                tx,
-                Err(Error::Sftp(StatusCode::ConnectionLost.to_status(
-                    "Could not receive response: SFTP stream stopped",
-                ))),
-            );
+        // A clean close of the stream by the server is distinguished from a broken stream
+        let status = match &failure {
+            Some(err) => StatusCode::ConnectionLost.to_status(format!(
+                "Could not receive response: SFTP stream stopped ({err})"
+            )),
+            None if self.frames.is_stalled() => StatusCode::ConnectionLost
+                .to_status("Could not receive response: SFTP stream stalled"),
+            None => StatusCode::NoConnection
+                .to_status("Could not receive response: SFTP session closed by the server"),
+        };
+        for (_, Onflight { tx, .. }) in self.onflight {
+            send_response(tx, Err(Error::Sftp(status.clone())));
+        }
+        for Request(_, _, tx, _) in self.queued {
+            send_response(tx, Err(Error::Sftp(status.clone())));
         }
 
         self.commands.close();
@@ -223,6 +457,206 @@ impl<S: AsyncRead + AsyncWrite + Unpin> Receiver<S> {
 
         log::debug!("SFTP client stopped");
     }
+
+    /// Process a command that is not a request.
+    fn process_command(&mut self, command: Command) {
+        match command {
+            Command::Request(Request(_, _, tx, _)) => {
+                log::error!("SFTP Error: Request processed as a command");
+                send_response(
+                    tx,
+                    Err(StatusCode::Failure.to_status("Request not sent").into()),
+                );
+            }
+
+            // A pending request was cancelled
+            Command::Cancel(key) => {
+                // The request may not have been sent yet
+                if let Some(position) = self
+                    .queued
+                    .iter()
+                    .position(|Request(_, _, _, cancel_key)| *cancel_key == Some(key))
+                {
+                    if let Some(Request(id, _, tx, _)) = self.queued.remove(position) {
+                        log::trace!("Cancel #{id} before sending it");
+                        send_response(
+                            tx,
+                            Err(Error::Io(std::io::Error::new(
+                                std::io::ErrorKind::Interrupted,
+                                "Request has been cancelled",
+                            ))),
+                        );
+                    }
+                }
+
+                // If the key is unknown, the reply has already been received
+                if let Some(id) = self.cancellable.remove(&key) {
+                    log::trace!("Cancel #{id}");
+                    if let Some(Onflight { tx, .. }) = self.onflight.remove(&id) {
+                        self.mark_cancelled(id);
+                        send_response(
+                            tx,
+                            Err(Error::Io(std::io::Error::new(
+                                std::io::ErrorKind::Interrupted,
+                                "Request has been cancelled",
+                            ))),
+                        );
+                    }
+                }
+            }
+
+            // The frame hook was replaced
+            Command::FrameHook(hook) => self.frame_hook = hook,
+
+            // The bandwidth limit was replaced
+            Command::RateLimit(limit) => self.rate_limiter.set_limit(limit),
+
+            // The idle timeout was replaced, and restarts from now
+            Command::IdleTimeout(timeout) => {
+                self.idle_timeout = timeout;
+                self.idle_deadline = None;
+            }
+
+            // The metadata cache was replaced
+            Command::MetadataCache(ttl, capacity) => self.metadata_cache.configure(ttl, capacity),
+        }
+    }
+
+    /// Close a handle whose request has been cancelled, or whose future has been dropped.
+    ///
+    /// The reply to the close request is discarded.
+    async fn close_orphan(&mut self, request: u32, handle: Handle) {
+        log::debug!("Handle of request #{request} will never be used: closing it");
+
+        let id = next_id(&self.last_id);
+        let message = Message::Close(Close { handle });
+        let frame = match message.encode(id) {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::debug!("Could not encode request #{id}: {err:?}");
+                return;
+            }
+        };
+        if let Some(hook) = &self.frame_hook {
+            hook(FrameDirection::Sent, &frame[std::mem::size_of::<u32>()..]);
+        }
+
+        self.mark_cancelled(id);
+        match write_frames(&mut self.stream, vec![frame]).await {
+            Ok(()) => self.metrics.request_sent(&message),
+            Err(err) => log::debug!("Could not close the handle of request #{request}: {err:?}"),
+        }
+    }
+
+    /// Send a batch of requests to the server, with as few writes as possible.
+    ///
+    /// If the write fails, all the requests of the batch fail.
+    async fn send_requests(&mut self, batch: Vec<Request>) {
+        let mut frames = Vec::with_capacity(batch.len());
+        let mut sent = Vec::with_capacity(batch.len());
+
+        // Requests answered from the metadata cache are also an activity of the session
+        self.idle_deadline = None;
+
+        for Request(id, message, tx, cancel_key) in batch {
+            log::trace!(
+                "Request #{id}: {:?}",
+                LogMessage {
+                    message: &message,
+                    redact: self.redact_logs
+                }
+            );
+
+            // The reply is known without sending the request
+            if let Some(reply) = self.metadata_cache.lookup(&message) {
+                log::trace!("Request #{id} answered from the metadata cache");
+                send_response(tx, Ok(reply));
+                continue;
+            }
+
+            match message.encode(id) {
+                Ok(frame) => {
+                    if let Some(hook) = &self.frame_hook {
+                        hook(FrameDirection::Sent, &frame[std::mem::size_of::<u32>()..]);
+                    }
+                    self.metadata_cache.request(id, &message);
+                    frames.push(frame);
+                    sent.push((id, message, tx, cancel_key));
+                }
+                Err(err) => {
+                    log::debug!("Could not encode request #{id}: {err:?}");
+                    send_response(tx, Err(err.into()));
+                }
+            }
+        }
+
+        if let Err(err) = write_frames(&mut self.stream, frames).await {
+            log::debug!("Could not send {} requests: {err:?}", sent.len());
+            for (id, _, tx, _) in sent {
+                self.metadata_cache.discard(id);
+                send_response(tx, Err(Error::Io(clone_io_error(&err))));
+            }
+            return;
+        }
+
+        for (id, message, tx, cancel_key) in sent {
+            self.metrics.request_sent(&message);
+            self.rate_limiter.consume(&message);
+            if let Some(key) = cancel_key {
+                self.cancellable.insert(key, id);
+            }
+            if let Some(timeout) = self.request_timeout {
+                self.request_deadlines
+                    .push_back((Instant::now() + timeout, id));
+            }
+            // The id may be reused after wrapping around: its new reply must not be discarded
+            self.cancelled.remove(&id);
+            let read_length = match &message {
+                Message::Read(read) => Some(read.length),
+                _ => None,
+            };
+            self.onflight.insert(
+                id,
+                Onflight {
+                    tx,
+                    cancel_key,
+                    read_length,
+                },
+            );
+        }
+    }
+
+    /// Remove the on-flight request `id`.
+    ///
+    /// Replies to cancelled requests are silently discarded.
+    fn take_onflight(&mut self, id: u32) -> Option<Onflight> {
+        if let Some(onflight) = self.onflight.remove(&id) {
+            if let Some(key) = onflight.cancel_key {
+                self.cancellable.remove(&key);
+            }
+            Some(onflight)
+        } else {
+            if !self.cancelled.remove(&id) {
+                log::error!("SFTP Error: Received a reply with an invalid id");
+            }
+            None
+        }
+    }
+}
+
+/// Check that the data replied to a read is not longer than the requested length.
+fn check_data_length(read_length: Option<u32>, message: Message) -> Response {
+    match (read_length, &message) {
+        (Some(length), Message::Data(data)) if data.len() > length as usize => {
+            Err(StatusCode::BadMessage
+                .to_status(format!(
+                    "Received {} bytes of data while {length} bytes were requested",
+                    data.len()
+                ))
+                .into())
+        }
+        _ => Ok(message),
+    }
 }
 
 fn send_response(tx: oneshot::Sender<Response>, msg: Response) {
@@ -234,22 +668,70 @@ fn send_response(tx: oneshot::Sender<Response>, msg: Response) {
     }
 }
 
+/// Write all the frames to the stream.
+///
+/// The frames are written with a single vectored write if the stream supports it,
+/// and are concatenated into a single buffer otherwise.
+async fn write_frames(
+    stream: &mut (impl AsyncWrite + Unpin),
+    mut frames: Vec<Bytes>,
+) -> std::io::Result<()> {
+    if frames.len() <= 1 || !stream.is_write_vectored() {
+        let frame = match frames.len() {
+            0 => return Ok(()),
+            1 => frames.swap_remove(0),
+            _ => {
+                let mut buffer = BytesMut::with_capacity(frames.iter().map(Bytes::len).sum());
+                for frame in &frames {
+                    buffer.extend_from_slice(frame);
+                }
+                buffer.freeze()
+            }
+        };
+        return stream.write_all(&frame).await;
+    }
+
+    // Index of the first frame not fully written
+    let mut first = 0;
+    while first < frames.len() {
+        let slices = frames[first..]
+            .iter()
+            .map(|frame| std::io::IoSlice::new(frame))
+            .collect::<Vec<_>>();
+        let mut written = stream.write_vectored(&slices).await?;
+        if written == 0 {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+
+        // Skip what has been written
+        while first < frames.len() {
+            let frame = &mut frames[first];
+            if written < frame.len() {
+                frame.advance(written);
+                break;
+            }
+            written -= frame.len();
+            first += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy an io error, as [`std::io::Error`] is not [`Clone`].
+fn clone_io_error(err: &std::io::Error) -> std::io::Error {
+    std::io::Error::new(err.kind(), err.to_string())
+}
+
 pub(super) async fn write_msg(
     stream: &mut (impl AsyncWrite + Unpin),
-    msg: Message,
+    msg: &Message,
     id: u32,
+    hook: Option<&FrameHook>,
 ) -> Result<(), Error> {
     let frame = msg.encode(id)?;
+    if let Some(hook) = hook {
+        hook(FrameDirection::Sent, &frame[std::mem::size_of::<u32>()..]);
+    }
     Ok(stream.write_all(frame.as_ref()).await?)
 }
-
-pub(super) async fn read_msg(
-    stream: &mut (impl AsyncRead + Unpin),
-) -> Result<(u32, Message), Error> {
-    let length = stream.read_u32().await?;
-
-    let mut bytes = vec![0u8; length as usize];
-    stream.read_exact(bytes.as_mut_slice()).await?;
-
-    Ok(Message::decode_raw(bytes.as_slice())?)
-}
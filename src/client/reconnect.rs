@@ -0,0 +1,254 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use futures::FutureExt;
+
+use crate::client::{Error, SftpClient, StatVfs};
+use crate::message::{Attrs, Name, Path};
+
+type Connect = dyn Fn() -> BoxFuture<'static, Result<SftpClient, Error>> + Send + Sync;
+
+/// SFTP client that reconnects when its connection is lost.
+///
+/// The client is created by a factory closure, that is called again
+/// each time a request fails because the connection has been lost
+/// (see [`Error::is_connection_lost`]).
+/// Concurrent failures trigger a single reconnection.
+///
+/// Only idempotent requests are retried transparently:
+/// [`stat`](ReconnectingClient::stat), [`lstat`](ReconnectingClient::lstat),
+/// [`readlink`](ReconnectingClient::readlink), [`realpath`](ReconnectingClient::realpath),
+/// [`expand_path`](ReconnectingClient::expand_path), [`readdir`](ReconnectingClient::readdir),
+/// [`read_dir_all_with_stat`](ReconnectingClient::read_dir_all_with_stat),
+/// [`statvfs`](ReconnectingClient::statvfs) and [`read_to_string`](ReconnectingClient::read_to_string).
+/// Other operations can be retried explicitly with [`ReconnectingClient::retry`],
+/// or sent without retry on the current client returned by [`ReconnectingClient::client`].
+///
+/// # Handles
+///
+/// Handles are only valid on the session that created them.
+/// [`File`](crate::client::File)s and [`Dir`](crate::client::Dir)s opened before a reconnection
+/// become invalid, and their operations fail with [`Error::Stopped`] or a lost connection.
+/// They must be opened again on the new client.
+///
+/// The state of the client, like its current directory, is not preserved either:
+/// the factory is responsible for configuring the new clients.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn connect() -> Result<rusftp::client::SftpClient, rusftp::client::Error> { todo!() }
+/// # async fn dummy() -> Result<(), rusftp::client::Error> {
+/// let sftp = rusftp::client::ReconnectingClient::new(connect).await?;
+/// let attrs = sftp.stat("file").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReconnectingClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    connect: Box<Connect>,
+    /// Current client, and the number of reconnections it results from
+    client: RwLock<(u64, SftpClient)>,
+    /// Serializes the reconnections
+    reconnecting: Mutex<()>,
+}
+
+impl ReconnectingClient {
+    /// Creates a client from a factory, and connects it.
+    ///
+    /// # Arguments
+    ///
+    /// * `connect` - Factory creating a new connected client
+    pub async fn new<F, Fut>(connect: F) -> Result<Self, Error>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<SftpClient, Error>> + Send + 'static,
+    {
+        let connect: Box<Connect> = Box::new(move || connect().boxed());
+        let client = connect().await?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                connect,
+                client: RwLock::new((0, client)),
+                reconnecting: Mutex::new(()),
+            }),
+        })
+    }
+
+    /// Get the current client.
+    ///
+    /// Requests sent directly on the client are not retried.
+    pub fn client(&self) -> SftpClient {
+        self.current().1
+    }
+
+    /// Number of times the client has been reconnected.
+    pub fn reconnections(&self) -> u64 {
+        self.current().0
+    }
+
+    /// Replace the current client with a new connection.
+    ///
+    /// The previous client is not stopped explicitly:
+    /// it stops when its last clone is dropped.
+    pub async fn reconnect(&self) -> Result<SftpClient, Error> {
+        let generation = self.current().0;
+        self.reconnect_from(generation).await
+    }
+
+    /// Run an operation on the current client, reconnecting and running it again if the connection was lost.
+    ///
+    /// The operation is attempted at most twice.
+    /// It must be idempotent, as it might have been performed by the server
+    /// before the connection was lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Operation to run on the client
+    pub async fn retry<T, F, Fut>(&self, f: F) -> Result<T, Error>
+    where
+        F: Fn(SftpClient) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let (generation, client) = self.current();
+        match f(client).await {
+            Err(err) if err.is_connection_lost() => {
+                log::debug!("SFTP connection lost, reconnecting: {err:?}");
+                let client = self.reconnect_from(generation).await?;
+                f(client).await
+            }
+            result => result,
+        }
+    }
+
+    /// Get the attributes of a file or directory, following symbolic links.
+    ///
+    /// See [`SftpClient::stat`].
+    pub async fn stat(&self, path: impl Into<Path>) -> Result<Attrs, Error> {
+        let path = path.into();
+        self.retry(|client| client.stat(path.clone())).await
+    }
+
+    /// Get the attributes of a file or directory, without following symbolic links.
+    ///
+    /// See [`SftpClient::lstat`].
+    pub async fn lstat(&self, path: impl Into<Path>) -> Result<Attrs, Error> {
+        let path = path.into();
+        self.retry(|client| client.lstat(path.clone())).await
+    }
+
+    /// Read the target of a symbolic link.
+    ///
+    /// See [`SftpClient::readlink`].
+    pub async fn readlink(&self, path: impl Into<Path>) -> Result<Path, Error> {
+        let path = path.into();
+        self.retry(|client| client.readlink(path.clone())).await
+    }
+
+    /// Canonicalize a path.
+    ///
+    /// See [`SftpClient::realpath`].
+    pub async fn realpath(&self, path: impl Into<Path>) -> Result<Path, Error> {
+        let path = path.into();
+        self.retry(|client| client.realpath(path.clone())).await
+    }
+
+    /// Expand a path, including `~` and `~user` prefixes, and canonicalize it.
+    ///
+    /// See [`SftpClient::expand_path`].
+    pub async fn expand_path(&self, path: impl Into<Path>) -> Result<Path, Error> {
+        let path = path.into();
+        self.retry(|client| client.expand_path(path.clone())).await
+    }
+
+    /// Read a directory listing.
+    ///
+    /// See [`SftpClient::readdir`].
+    pub async fn readdir(&self, path: impl Into<Path>) -> Result<Name, Error> {
+        let path = path.into();
+        self.retry(|client| client.readdir(path.clone())).await
+    }
+
+    /// Read a directory listing, with the attributes of all the entries.
+    ///
+    /// See [`SftpClient::read_dir_all_with_stat`].
+    pub async fn read_dir_all_with_stat(&self, path: impl Into<Path>) -> Result<Name, Error> {
+        let path = path.into();
+        self.retry(|client| client.read_dir_all_with_stat(path.clone()))
+            .await
+    }
+
+    /// Get the statistics of the filesystem containing a path.
+    ///
+    /// See [`SftpClient::statvfs`].
+    pub async fn statvfs(&self, path: impl Into<Path>) -> Result<StatVfs, Error> {
+        let path = path.into();
+        self.retry(|client| client.statvfs(path.clone())).await
+    }
+
+    /// Read the whole content of a remote file as a UTF-8 string.
+    ///
+    /// See [`SftpClient::read_to_string`].
+    pub async fn read_to_string(&self, path: impl Into<Path>) -> Result<String, Error> {
+        let path = path.into();
+        self.retry(|client| client.read_to_string(path.clone()))
+            .await
+    }
+
+    fn current(&self) -> (u64, SftpClient) {
+        self.inner
+            .client
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Reconnect, unless the client has already been reconnected since `generation`.
+    async fn reconnect_from(&self, generation: u64) -> Result<SftpClient, Error> {
+        let _guard = self.inner.reconnecting.lock().await;
+
+        let (current, client) = self.current();
+        if current != generation {
+            return Ok(client);
+        }
+
+        let client = (self.inner.connect)().await?;
+        *self
+            .inner
+            .client
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = (current + 1, client.clone());
+
+        log::debug!("SFTP client reconnected");
+        Ok(client)
+    }
+}
+
+impl std::fmt::Debug for ReconnectingClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReconnectingClient({})", self.reconnections())
+    }
+}
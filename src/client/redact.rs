@@ -0,0 +1,126 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bytes::Bytes;
+
+use crate::message::{
+    Data, Extended, ExtendedReply, Message, Name, NameEntry, Path, Symlink, Write,
+};
+
+/// Debug formatting of a message for the logs, hiding its paths and data if `redact` is set.
+///
+/// The message is only copied and redacted when it is actually formatted,
+/// so disabled log levels have no overhead.
+pub(super) struct LogMessage<'a> {
+    pub(super) message: &'a Message,
+    pub(super) redact: bool,
+}
+
+impl std::fmt::Debug for LogMessage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.redact {
+            redact_message(self.message).fmt(f)
+        } else {
+            self.message.fmt(f)
+        }
+    }
+}
+
+/// Debug formatting of a raw frame for the logs, showing only its length if `redact` is set.
+pub(super) struct LogFrame<'a> {
+    pub(super) frame: &'a [u8],
+    pub(super) redact: bool,
+}
+
+impl std::fmt::Debug for LogFrame<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.redact {
+            write!(f, "<{} bytes>", self.frame.len())
+        } else {
+            Bytes::copy_from_slice(self.frame).fmt(f)
+        }
+    }
+}
+
+/// Placeholder of a redacted content: its length, and a hash to correlate identical contents.
+fn placeholder(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!(
+        "<redacted {} bytes #{:08x}>",
+        content.len(),
+        hasher.finish() as u32
+    )
+}
+
+fn redact_path(path: &Path) -> Path {
+    Path(placeholder(path.as_bytes()))
+}
+
+fn redact_bytes(bytes: &Bytes) -> Bytes {
+    Bytes::from(placeholder(bytes))
+}
+
+/// Copy of the message where the paths, file names and data are replaced by placeholders.
+fn redact_message(message: &Message) -> Message {
+    let mut message = message.clone();
+
+    match &mut message {
+        Message::LStat(msg) => msg.path = redact_path(&msg.path),
+        Message::MkDir(msg) => msg.path = redact_path(&msg.path),
+        Message::Open(msg) => msg.filename = redact_path(&msg.filename),
+        Message::OpenDir(msg) => msg.path = redact_path(&msg.path),
+        Message::ReadLink(msg) => msg.path = redact_path(&msg.path),
+        Message::RealPath(msg) => msg.path = redact_path(&msg.path),
+        Message::Remove(msg) => msg.path = redact_path(&msg.path),
+        Message::Rename(msg) => {
+            msg.old_path = redact_path(&msg.old_path);
+            msg.new_path = redact_path(&msg.new_path);
+        }
+        Message::RmDir(msg) => msg.path = redact_path(&msg.path),
+        Message::SetStat(msg) => msg.path = redact_path(&msg.path),
+        Message::Stat(msg) => msg.path = redact_path(&msg.path),
+        Message::Symlink(Symlink {
+            link_path,
+            target_path,
+        }) => {
+            *link_path = redact_path(link_path);
+            *target_path = redact_path(target_path);
+        }
+        Message::Write(Write { data, .. }) | Message::Data(data) => {
+            *data = Data(redact_bytes(&data.0));
+        }
+        Message::Name(Name(entries)) => {
+            for NameEntry {
+                filename,
+                long_name,
+                ..
+            } in entries
+            {
+                *filename = redact_path(filename);
+                *long_name = redact_bytes(long_name);
+            }
+        }
+        Message::Extended(Extended { data, .. })
+        | Message::ExtendedReply(ExtendedReply { data })
+        | Message::Unknown { data, .. } => *data = redact_bytes(data),
+        _ => (),
+    }
+
+    message
+}
@@ -16,12 +16,15 @@
 
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::task::{ready, Poll};
 
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
+use crate::client::receiver::Command;
+use crate::client::redact::LogMessage;
 use crate::client::{Error, SftpClient};
-use crate::message::{self, Message, Status, StatusCode};
+use crate::message::{self, HandleTooLong, Message, Status, StatusCode};
 
 impl SftpClient {
     /// Send a SFTP request, and return its reply.
@@ -71,6 +74,55 @@ impl SftpClient {
         )
     }
 
+    /// Send a SFTP request, and return its id along with its reply.
+    ///
+    /// The id is the one of the frame sent to the server.
+    /// It can be used to correlate the request with the logs of the client (`Request #id`) or of the server,
+    /// or to track the requests in a custom scheduler.
+    /// Ids are unique among the on-flight requests of the session, but are reused once they wrap around.
+    ///
+    /// If the request fails before being sent (eg: the client is stopped), its id is `0`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - SFTP Request to be sent
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn request_with_id<R: SftpRequest>(&self, request: R) -> (u32, SftpFuture<R::Reply>) {
+        self.send_request(
+            request.to_request_message(),
+            (),
+            stateless_from_reply_message::<R::Reply>,
+            None,
+        )
+    }
+
+    /// Send a SFTP request, and spawn a task waiting for its reply.
+    ///
+    /// This is convenient for best-effort operations (eg: cleanup) that should not be awaited inline.
+    /// The request is sent before the task is spawned, so the order of requests is preserved.
+    ///
+    /// Errors are only observable through the returned [`JoinHandle`](tokio::task::JoinHandle).
+    /// If the handle is dropped, the task keeps running, and its result is discarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - SFTP Request to be sent
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime, like [`tokio::spawn`].
+    pub fn spawn_request<R>(&self, request: R) -> tokio::task::JoinHandle<Result<R::Reply, Error>>
+    where
+        R: SftpRequest,
+        R::Reply: Send + 'static,
+    {
+        tokio::spawn(self.request(request))
+    }
+
     /// Send a raw SFTP request, and return its reply.
     ///
     /// # Arguments
@@ -114,45 +166,165 @@ impl SftpClient {
         state: S,
         f: fn(S, Message) -> Result<T, Error>,
     ) -> SftpFuture<T, S> {
-        if let Some(commands) = &self.commands {
-            match request {
-                Ok(Message::Status(Status {
-                    code: StatusCode::Ok,
-                    ..
-                })) => SftpFuture::Error(
+        self.send_request(request, state, f, None).1
+    }
+
+    /// Send a SFTP request that can be cancelled, and return its reply.
+    ///
+    /// Calling [`SftpCancelToken::cancel`] on the returned token
+    /// makes the future return immediately with an [`Interrupted`](std::io::ErrorKind::Interrupted) error.
+    /// The reply of the server, if it ever comes, is then discarded.
+    ///
+    /// As the request has already been sent to the server when cancelled,
+    /// the server might still perform the operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - SFTP Request to be sent
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn request_cancellable<R: SftpRequest>(
+        &self,
+        request: R,
+    ) -> (SftpFuture<R::Reply>, SftpCancelToken) {
+        self.request_cancellable_with(
+            request.to_request_message(),
+            (),
+            stateless_from_reply_message::<R::Reply>,
+        )
+    }
+
+    /// Send a raw SFTP request that can be cancelled, and return its reply.
+    ///
+    /// See [`SftpClient::request_with`] and [`SftpClient::request_cancellable`].
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - SFTP Request to be sent
+    /// * `state` - State used by the callback
+    /// * `f` - callback used to transform the reply into a specific type
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn request_cancellable_with<S, T>(
+        &self,
+        request: Result<Message, Error>,
+        state: S,
+        f: fn(S, Message) -> Result<T, Error>,
+    ) -> (SftpFuture<T, S>, SftpCancelToken) {
+        static NEXT_CANCEL_KEY: AtomicU64 = AtomicU64::new(0);
+        let key = NEXT_CANCEL_KEY.fetch_add(1, Ordering::Relaxed);
+
+        let (_, future) = self.send_request(request, state, f, Some(key));
+        let token = SftpCancelToken {
+            commands: match &future {
+                SftpFuture::Pending { .. } => self.commands.clone(),
+                _ => None,
+            },
+            key,
+        };
+
+        (future, token)
+    }
+
+    /// Send a request to the receiver, and return its id (`0` if it could not be sent).
+    fn send_request<S, T>(
+        &self,
+        request: Result<Message, Error>,
+        state: S,
+        f: fn(S, Message) -> Result<T, Error>,
+        cancel_key: Option<u64>,
+    ) -> (u32, SftpFuture<T, S>) {
+        let (Some(commands), Some(last_id)) = (&self.commands, &self.last_id) else {
+            return (0, SftpFuture::Error(Error::Stopped));
+        };
+
+        match request {
+            Ok(Message::Status(Status {
+                code: StatusCode::Ok,
+                ..
+            })) => (
+                0,
+                SftpFuture::Error(
                     StatusCode::BadMessage
                         .to_status("Tried to send an OK status message to the server")
                         .into(),
                 ),
-                Ok(Message::Status(status)) => SftpFuture::Error(status.into()),
-                Ok(msg) => {
-                    let (tx, rx) = oneshot::channel();
-                    log::trace!("Sending: {msg:?}");
-                    match commands.send(super::receiver::Request(msg, tx)) {
-                        Ok(()) => SftpFuture::Pending {
+            ),
+            Ok(Message::Status(status)) => (0, SftpFuture::Error(status.into())),
+            Ok(msg) => {
+                // Oversized handles are a protocol violation, and must not be sent to the server
+                if let Err(err) = validate_handle(&msg) {
+                    return (
+                        0,
+                        SftpFuture::Error(StatusCode::BadMessage.to_status(err.to_string()).into()),
+                    );
+                }
+
+                let id = next_id(last_id);
+
+                let (tx, rx) = oneshot::channel();
+                log::trace!(
+                    "Sending #{id}: {:?}",
+                    LogMessage {
+                        message: &msg,
+                        redact: self.config.redact_logs
+                    }
+                );
+                match commands.send(Command::Request(super::receiver::Request(
+                    id, msg, tx, cancel_key,
+                ))) {
+                    Ok(()) => (
+                        id,
+                        SftpFuture::Pending {
                             future: rx,
                             state,
                             f,
                         },
-                        Err(err) => {
-                            SftpFuture::Error(StatusCode::Failure.to_status(err.to_string()).into())
-                        }
-                    }
+                    ),
+                    // The receiver has stopped: the connection is lost
+                    Err(_) => (0, SftpFuture::Error(Error::Stopped)),
                 }
-                Err(err) => SftpFuture::Error(err),
             }
-        } else {
-            SftpFuture::Error(
-                std::io::Error::new(
-                    std::io::ErrorKind::BrokenPipe,
-                    "SFTP client has been stopped",
-                )
-                .into(),
-            )
+            Err(err) => (0, SftpFuture::Error(err)),
         }
     }
 }
 
+/// Token to cancel a pending SFTP request.
+///
+/// It is created by [`SftpClient::request_cancellable`].
+///
+/// Cancelling a request that has already completed has no effect.
+#[derive(Clone)]
+pub struct SftpCancelToken {
+    commands: Option<mpsc::UnboundedSender<Command>>,
+    key: u64,
+}
+
+impl SftpCancelToken {
+    /// Cancel the pending request.
+    ///
+    /// The associated [`SftpFuture`] will return an [`Interrupted`](std::io::ErrorKind::Interrupted) error,
+    /// unless the reply has already been received.
+    pub fn cancel(&self) {
+        if let Some(commands) = &self.commands {
+            _ = commands.send(Command::Cancel(self.key));
+        }
+    }
+}
+
+impl std::fmt::Debug for SftpCancelToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SftpCancelToken({})", self.key)
+    }
+}
+
 /// SFTP future.
 ///
 /// Represents a future of a SFTP request that returns a `Result<Output, Error>` when polled.
@@ -180,6 +352,16 @@ pub enum SftpFuture<Output = (), State = ()> {
     Polled,
 }
 
+impl<Output, State> std::fmt::Debug for SftpFuture<Output, State> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error(err) => f.debug_tuple("Error").field(err).finish(),
+            Self::Pending { .. } => write!(f, "Pending(...)"),
+            Self::Polled => write!(f, "Polled"),
+        }
+    }
+}
+
 impl<Output, State> Future for SftpFuture<Output, State>
 where
     State: Unpin,
@@ -333,6 +515,30 @@ reply_impl!(Handle);
 reply_impl!(Name);
 reply_impl!(ExtendedReply);
 
+/// Check the length of the handle of a request, if any.
+fn validate_handle(msg: &Message) -> Result<(), HandleTooLong> {
+    match msg {
+        Message::Close(message::Close { handle })
+        | Message::Read(message::Read { handle, .. })
+        | Message::Write(message::Write { handle, .. })
+        | Message::FStat(message::FStat { handle })
+        | Message::FSetStat(message::FSetStat { handle, .. })
+        | Message::ReadDir(message::ReadDir { handle })
+        | Message::Handle(handle) => handle.validate(),
+        _ => Ok(()),
+    }
+}
+
+/// Allocate the id of a new request on a session.
+///
+/// 0 is reserved for the requests that are not sent.
+pub(super) fn next_id(last_id: &AtomicU32) -> u32 {
+    match last_id.fetch_add(1, Ordering::Relaxed).wrapping_add(1) {
+        0 => last_id.fetch_add(1, Ordering::Relaxed).wrapping_add(1),
+        id => id,
+    }
+}
+
 /// Wrapper for [`SftpReply::from_reply_message`] that takes an empty state.
 ///
 /// Useful for [`SftpClient::request_with`]
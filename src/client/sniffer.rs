@@ -0,0 +1,112 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::Poll;
+
+use futures::Stream;
+use tokio::io::AsyncRead;
+
+use crate::client::framing::FrameReader;
+use crate::client::{Error, SftpClientConfig};
+use crate::message::Message;
+
+/// Read-only decoder of the SFTP frames received from a stream.
+///
+/// Contrary to [`SftpClient`](crate::client::SftpClient), nothing is ever sent on the stream,
+/// and the messages are not correlated with any request:
+/// every frame is decoded and yielded as is, with its id.
+/// The id of `Init` and `Version` messages, which have none, is 0.
+///
+/// This is meant for protocol analysis, like building a traffic analyzer,
+/// or capturing the greeting and the extensions of a server.
+/// The frames are split the same way as by the client,
+/// including the [`max_frame_size`](SftpClientConfig::max_frame_size)
+/// and [`read_timeout`](SftpClientConfig::read_timeout) checks.
+///
+/// A frame that cannot be decoded yields an error, and the following frames are still yielded.
+/// The stream ends when the underlying stream is closed between two frames,
+/// or after an error that prevents finding the next frame (eg: a truncated frame).
+///
+/// Only available with the `diagnostics` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn dummy(stream: impl tokio::io::AsyncRead + Unpin) -> Result<(), rusftp::client::Error> {
+/// use futures::StreamExt;
+///
+/// let mut sniffer = rusftp::client::SftpSniffer::new(stream);
+/// while let Some((id, message)) = sniffer.next().await.transpose()? {
+///     println!("#{id}: {message:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct SftpSniffer<S> {
+    stream: S,
+    frames: FrameReader,
+}
+
+impl<S: AsyncRead + Unpin> SftpSniffer<S> {
+    /// Create a sniffer decoding the frames read from `stream`.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream the frames are read from
+    pub fn new(stream: S) -> Self {
+        Self::with_config(stream, SftpClientConfig::default())
+    }
+
+    /// Create a sniffer decoding the frames read from `stream`, with a custom configuration.
+    ///
+    /// Only the options related to the reception of the frames are used
+    /// (`min_read_size`, `max_frame_size` and `read_timeout`).
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - Stream the frames are read from
+    /// * `config` - Configuration of the reception of the frames
+    pub fn with_config(stream: S, config: SftpClientConfig) -> Self {
+        Self {
+            stream,
+            frames: FrameReader::new(&[], &config),
+        }
+    }
+
+    /// Get back the underlying stream.
+    ///
+    /// The bytes already read from the stream, but not yet yielded as a message, are lost.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + Unpin> Stream for SftpSniffer<S> {
+    type Item = Result<(u32, Message), Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let frame = futures::ready!(this.frames.poll_frame(&mut this.stream, cx));
+        Poll::Ready(frame.map(|frame| match frame {
+            Ok(frame) => Message::decode_raw(&frame).map_err(Error::from),
+            Err(err) => Err(Error::Io(err)),
+        }))
+    }
+}
@@ -0,0 +1,170 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::{BufMut, BytesMut};
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{Error, SftpClient};
+use crate::message::{Path, Status, StatusCode};
+
+/// Statistics of a remote filesystem, queried with the `statvfs@openssh.com` extension.
+///
+/// The fields are the ones of the POSIX `statvfs` structure.
+/// Sizes are given in blocks of [`StatVfs::frsize`] bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StatVfs {
+    /// Filesystem block size
+    pub bsize: u64,
+    /// Fundamental filesystem block size
+    pub frsize: u64,
+    /// Number of blocks (unit: `frsize`)
+    pub blocks: u64,
+    /// Free blocks in the filesystem
+    pub bfree: u64,
+    /// Free blocks for non-root users
+    pub bavail: u64,
+    /// Total number of file inodes
+    pub files: u64,
+    /// Free file inodes
+    pub ffree: u64,
+    /// Free file inodes for non-root users
+    pub favail: u64,
+    /// Filesystem id
+    pub fsid: u64,
+    /// Mount flags (read-only: `0x1`, no setuid: `0x2`)
+    pub flag: u64,
+    /// Maximal length of a filename
+    pub namemax: u64,
+}
+
+impl StatVfs {
+    /// Number of bytes available to non-root users.
+    pub fn free_space(&self) -> u64 {
+        self.bavail.saturating_mul(self.block_size())
+    }
+
+    /// Total size of the filesystem in bytes.
+    pub fn total_space(&self) -> u64 {
+        self.blocks.saturating_mul(self.block_size())
+    }
+
+    /// Size of the blocks counted by the statistics, falling back to `bsize` if `frsize` is not set.
+    fn block_size(&self) -> u64 {
+        match self.frsize {
+            0 => self.bsize,
+            frsize => frsize,
+        }
+    }
+}
+
+impl SftpClient {
+    /// Get the statistics of the filesystem containing a path.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn statvfs(&self, path: impl Into<Path>) -> Result<StatVfs, Error>;
+    /// ```
+    ///
+    /// The request uses the `statvfs@openssh.com` extension.
+    /// If the server does not support it, an `OpUnsupported` status is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of any file or directory on the filesystem
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn statvfs(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<StatVfs, Error>> + Send + Sync + 'static {
+        let path = self.resolve_path(path);
+
+        let mut data = BytesMut::with_capacity(4 + path.len());
+        data.put_u32(path.len() as u32);
+        data.put_slice(path.as_bytes());
+
+        let statvfs = self.extended_decode("statvfs@openssh.com", data.freeze());
+
+        async move {
+            match statvfs.await {
+                Err(Error::Sftp(Status {
+                    code: StatusCode::OpUnsupported,
+                    ..
+                })) => Err(StatusCode::OpUnsupported
+                    .to_status("The server does not support statvfs@openssh.com")
+                    .into()),
+                result => result,
+            }
+        }
+    }
+
+    /// Get the number of bytes available to non-root users on the filesystem containing a path.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn free_space(&self, path: impl Into<Path>) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// See [`SftpClient::statvfs`] and [`StatVfs::free_space`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of any file or directory on the filesystem
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn free_space(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + Sync + 'static {
+        let statvfs = self.statvfs(path);
+        async move { Ok(statvfs.await?.free_space()) }
+    }
+
+    /// Get the total size in bytes of the filesystem containing a path.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn total_space(&self, path: impl Into<Path>) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// See [`SftpClient::statvfs`] and [`StatVfs::total_space`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of any file or directory on the filesystem
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the request is actually sent before the future is returned.
+    pub fn total_space(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + Sync + 'static {
+        let statvfs = self.statvfs(path);
+        async move { Ok(statvfs.await?.total_space()) }
+    }
+}
@@ -18,9 +18,11 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::ready;
+use std::time::Duration;
 
 use tokio::task::JoinHandle;
 
+use crate::client::receiver::Command;
 use crate::client::SftpClient;
 
 impl SftpClient {
@@ -43,6 +45,30 @@ impl SftpClient {
     pub fn is_stopped(&self) -> bool {
         self.commands.is_none()
     }
+
+    /// Stop the SFTP session automatically once it has been idle for too long.
+    ///
+    /// The session is idle when no request is on-flight,
+    /// so the timeout never interrupts a pending request.
+    /// The idle time restarts with every request, including the ones answered from the
+    /// [metadata cache](SftpClient::with_metadata_cache).
+    /// Once the session is stopped, all the clones of the client fail with [`Error::Stopped`](crate::client::Error::Stopped).
+    ///
+    /// The timeout applies to the whole SFTP session, including all the clones of the client,
+    /// and replaces any previous timeout, including the one from [`SftpClientConfig::idle_timeout`](crate::client::SftpClientConfig::idle_timeout).
+    /// The configuration of the returned client is updated accordingly.
+    /// The idle time restarts when the timeout is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximal idle time of the session (never stopped if `None`)
+    pub fn with_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        if let Some(commands) = &self.commands {
+            _ = commands.send(Command::IdleTimeout(timeout));
+        }
+        self.config.idle_timeout = timeout;
+        self
+    }
 }
 
 impl Drop for SftpClient {
@@ -58,7 +84,7 @@ pub(super) struct SftpClientStopping<'a> {
 }
 
 impl<'a> SftpClientStopping<'a> {
-    pub(super) fn new(client: &'a mut SftpClient) -> SftpClientStopping<'_> {
+    pub(super) fn new(client: &'a mut SftpClient) -> SftpClientStopping<'a> {
         client.commands = None;
 
         // Try to unwrap the join handle into the future
@@ -0,0 +1,1545 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::{FuturesOrdered, FuturesUnordered};
+use futures::{Future, FutureExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::Instant;
+
+use crate::client::checksum::{Hasher, HashingReader, HashingWriter};
+use crate::client::{DirEntry, Error, FileType, SftpClient, VerifyOptions};
+use crate::message::{Attrs, Handle, PFlags, Path, Permisions, Status, StatusCode};
+
+/// What [`SftpClient::upload_dir_with_policy`] does with the remote entries that already exist.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverwritePolicy {
+    /// Replace the existing remote files and links, and reset the attributes of the existing directories
+    #[default]
+    Overwrite,
+    /// Leave the existing remote entries untouched
+    Skip,
+}
+
+impl SftpClient {
+    /// Upload the content of a reader into a remote file.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn upload_stream(&self, reader: impl AsyncRead + Unpin, remote: impl Into<Path>) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The remote file is created if it does not exist, and truncated otherwise.
+    /// The reader is consumed until its end, and its content is written to the remote file
+    /// with multiple write requests on-flight at the same time.
+    ///
+    /// Returns the total number of bytes written.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the data to upload
+    /// * `remote` - Path of the remote file to write
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the remote file is left partially written,
+    /// and its handle is not closed.
+    pub fn upload_stream<'a>(
+        &self,
+        reader: impl AsyncRead + Unpin + Send + 'a,
+        remote: impl Into<Path>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        self.upload_stream_with_progress(reader, remote, None, |_, _| ())
+    }
+
+    /// Upload the content of a reader into a remote file, and report the progress of the upload.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn upload_stream_with_progress(&self, reader: impl AsyncRead + Unpin, remote: impl Into<Path>, total: Option<u64>, progress: impl FnMut(u64, Option<u64>)) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The data is uploaded like with [`SftpClient::upload_stream`].
+    /// `progress` is called with the number of bytes acknowledged by the server so far, and `total`.
+    /// Calls are throttled to at most one every 100 milliseconds,
+    /// but `progress` is always called once the whole reader has been uploaded.
+    ///
+    /// Returns the total number of bytes written.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the data to upload
+    /// * `remote` - Path of the remote file to write
+    /// * `total` - Number of bytes to upload, if known (eg: the size of the local file)
+    /// * `progress` - Callback reporting the number of bytes uploaded, and `total`
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the remote file is left partially written,
+    /// and its handle is not closed.
+    pub fn upload_stream_with_progress<'a>(
+        &self,
+        mut reader: impl AsyncRead + Unpin + Send + 'a,
+        remote: impl Into<Path>,
+        total: Option<u64>,
+        progress: impl FnMut(u64, Option<u64>) + Send + 'a,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        let open = self.open_handle(
+            remote,
+            PFlags::WRITE | PFlags::CREATE | PFlags::TRUNCATE,
+            Attrs::default(),
+        );
+        let client = self.clone();
+
+        async move {
+            let mut progress = Progress::new(Some(progress), total);
+            let handle = open.await?;
+            let result = client.write_from(&handle, &mut reader, &mut progress).await;
+            let closed = client.close(handle).await;
+
+            let written = result?;
+            closed?;
+            progress.finish(written);
+            Ok(written)
+        }
+    }
+
+    /// Upload a local directory tree into a remote directory.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn upload_dir(&self, local: impl AsRef<std::path::Path>, remote: impl Into<Path>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// The remote directory is created if it does not exist, including its missing parents,
+    /// and the local tree is recreated inside it:
+    ///
+    /// * directories are created if they do not exist, and their attributes are set once their content is uploaded,
+    /// * regular files are uploaded, overwriting existing remote files, and their attributes are copied,
+    /// * symbolic links are recreated with [`SftpClient::symlink`], replacing existing remote links, without following them,
+    /// * other files (devices, FIFOs, sockets) are skipped.
+    ///
+    /// The attributes copied are the modification and access times, and the permissions on Unix.
+    /// Use [`SftpClient::upload_dir_with_policy`] to keep the existing remote entries instead.
+    /// If a remote entry exists with a different type (eg: a file instead of a directory),
+    /// the upload fails.
+    /// At most 8 local files or directories are read at the same time.
+    ///
+    /// If an entry cannot be uploaded, no other entry is started,
+    /// and the first error is returned once the on-flight entries are finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `local` - Path of the local directory to upload
+    /// * `remote` - Path of the remote directory to upload into
+    ///
+    /// # Cancel safety
+    ///
+    /// The stat request is sent before the future is returned.
+    /// If the future is dropped before completion, the remote tree is left partially uploaded,
+    /// and the remote handles are not closed.
+    pub fn upload_dir(
+        &self,
+        local: impl AsRef<std::path::Path>,
+        remote: impl Into<Path>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + 'static {
+        self.upload_dir_with_policy(local, remote, OverwritePolicy::Overwrite)
+    }
+
+    /// Upload a local directory tree into a remote directory, choosing what happens to the existing remote entries.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn upload_dir_with_policy(&self, local: impl AsRef<std::path::Path>, remote: impl Into<Path>, policy: OverwritePolicy) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Same as [`SftpClient::upload_dir`], but with [`OverwritePolicy::Skip`],
+    /// the remote files and links that already exist are left untouched, whatever their type,
+    /// and so are the attributes of the remote directories that already exist.
+    /// The content of existing directories is still uploaded into them.
+    ///
+    /// # Arguments
+    ///
+    /// * `local` - Path of the local directory to upload
+    /// * `remote` - Path of the remote directory to upload into
+    /// * `policy` - What to do with the remote entries that already exist
+    ///
+    /// # Cancel safety
+    ///
+    /// The stat request is sent before the future is returned.
+    /// If the future is dropped before completion, the remote tree is left partially uploaded,
+    /// and the remote handles are not closed.
+    pub fn upload_dir_with_policy(
+        &self,
+        local: impl AsRef<std::path::Path>,
+        remote: impl Into<Path>,
+        policy: OverwritePolicy,
+    ) -> impl Future<Output = Result<(), Error>> + Send + 'static {
+        let local = local.as_ref().to_path_buf();
+        let remote = self.resolve_path(remote);
+        let stat = self.stat(remote.clone());
+        let client = self.clone();
+
+        async move {
+            let metadata = tokio::fs::metadata(&local).await?;
+            if !metadata.is_dir() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Not a directory: {}", local.display()),
+                )
+                .into());
+            }
+
+            match stat.await {
+                Ok(attrs) => {
+                    if !DirEntry::new(remote.clone(), &attrs).file_type.is_dir() {
+                        return Err(StatusCode::Failure
+                            .to_status(format!("Not a directory: {}", remote.as_ref() as &str))
+                            .into());
+                    }
+                }
+                Err(Error::Sftp(Status {
+                    code: StatusCode::NoSuchFile,
+                    ..
+                })) => {
+                    if let Some(parent) = remote_parent(&remote) {
+                        client.create_dir_all(parent).await?;
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+
+            let mut queue = vec![UploadEntry {
+                local,
+                remote,
+                metadata,
+            }];
+            let mut dirs = Vec::new();
+            let mut running = FuturesUnordered::new();
+            let mut error = None;
+
+            loop {
+                while running.len() < UPLOAD_DIR_LIMIT {
+                    let Some(entry) = queue.pop() else {
+                        break;
+                    };
+                    running.push(client.upload_entry(entry, policy));
+                }
+
+                match running.next().await {
+                    Some(Ok((children, dir))) => {
+                        dirs.extend(dir);
+                        queue.extend(children);
+                    }
+                    Some(Err(err)) => {
+                        queue.clear();
+                        error.get_or_insert(err);
+                    }
+                    None => break,
+                }
+            }
+
+            if let Some(err) = error {
+                return Err(err);
+            }
+
+            // Children are set before their parent, in case the parent is not writable
+            for (remote, attrs) in dirs.into_iter().rev() {
+                client.setstat(remote, attrs).await?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Upload the content of a reader into a remote file, and verify the remote content.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn upload_verified(&self, reader: impl AsyncRead + Unpin, remote: impl Into<Path>, options: VerifyOptions) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The data is uploaded like with [`SftpClient::upload_stream`], and hashed while it is read.
+    /// Once the remote file is closed, its checksum is computed by the server with [`SftpClient::check_file`],
+    /// and compared against the local one.
+    ///
+    /// If the server does not support the `check-file` extension,
+    /// the `OpUnsupported` error is returned, unless `options.download_fallback` is set.
+    /// In that case, the remote file is downloaded to compute its checksum locally.
+    ///
+    /// If the checksums differ, [`Error::ChecksumMismatch`] is returned,
+    /// and the remote file is removed if `options.remove_on_mismatch` is set.
+    ///
+    /// Returns the total number of bytes written.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Source of the data to upload
+    /// * `remote` - Path of the remote file to write
+    /// * `options` - How the remote content is verified
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the remote file is left partially written and unverified,
+    /// and its handle might not be closed.
+    pub fn upload_verified<'a>(
+        &self,
+        reader: impl AsyncRead + Unpin + Send + 'a,
+        remote: impl Into<Path>,
+        options: VerifyOptions,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        let remote = self.resolve_path(remote);
+        let open = self.open_handle(
+            remote.clone(),
+            PFlags::WRITE | PFlags::CREATE | PFlags::TRUNCATE,
+            Attrs::default(),
+        );
+        let client = self.clone();
+
+        async move {
+            let mut reader = HashingReader {
+                inner: reader,
+                hasher: Hasher::new(options.algorithm),
+            };
+
+            let handle = open.await?;
+            let result = client
+                .write_from(&handle, &mut reader, &mut Progress::none())
+                .await;
+            let closed = client.close(handle).await;
+
+            let written = result?;
+            closed?;
+
+            let expected = reader.hasher.finalize();
+            let actual = match client.check_file(remote.clone(), options.algorithm).await {
+                Ok(actual) => actual,
+                Err(Error::Sftp(Status {
+                    code: StatusCode::OpUnsupported,
+                    ..
+                })) if options.download_fallback => {
+                    let mut writer = HashingWriter(Hasher::new(options.algorithm));
+                    client.download_stream(remote.clone(), &mut writer).await?;
+                    writer.0.finalize()
+                }
+                Err(err) => return Err(err),
+            };
+
+            if expected != actual {
+                if options.remove_on_mismatch {
+                    if let Err(err) = client.remove(remote.clone()).await {
+                        log::debug!("Could not remove corrupted upload {remote:?}: {err:?}");
+                    }
+                }
+                return Err(Error::ChecksumMismatch {
+                    algorithm: options.algorithm,
+                    expected,
+                    actual,
+                });
+            }
+
+            Ok(written)
+        }
+    }
+
+    /// Download a remote file into a writer.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn download_stream(&self, remote: impl Into<Path>, writer: impl AsyncWrite + Unpin) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The remote file is read until its end with multiple read requests on-flight at the same time,
+    /// and its content is written in order into the writer.
+    /// The writer is flushed once the whole file has been written.
+    ///
+    /// Returns the total number of bytes read.
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Path of the remote file to read
+    /// * `writer` - Destination of the downloaded data
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the writer is left partially written,
+    /// and the remote handle is not closed.
+    pub fn download_stream<'a>(
+        &self,
+        remote: impl Into<Path>,
+        writer: impl AsyncWrite + Unpin + Send + 'a,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        self.download(remote, writer, None::<fn(u64, Option<u64>)>)
+    }
+
+    /// Download a remote file into a writer, and report the progress of the download.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn download_stream_with_progress(&self, remote: impl Into<Path>, writer: impl AsyncWrite + Unpin, progress: impl FnMut(u64, Option<u64>)) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The file is downloaded like with [`SftpClient::download_stream`].
+    /// `progress` is called with the number of bytes written into the writer so far,
+    /// and the size of the remote file, if the server reports it.
+    /// Calls are throttled to at most one every 100 milliseconds,
+    /// but `progress` is always called once the whole file has been downloaded.
+    ///
+    /// Returns the total number of bytes read.
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Path of the remote file to read
+    /// * `writer` - Destination of the downloaded data
+    /// * `progress` - Callback reporting the number of bytes downloaded, and the size of the file
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the writer is left partially written,
+    /// and the remote handle is not closed.
+    pub fn download_stream_with_progress<'a>(
+        &self,
+        remote: impl Into<Path>,
+        writer: impl AsyncWrite + Unpin + Send + 'a,
+        progress: impl FnMut(u64, Option<u64>) + Send + 'a,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        self.download(remote, writer, Some(progress))
+    }
+
+    /// Read the whole content of a remote file as a UTF-8 string.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read_to_string(&self, path: impl Into<Path>) -> Result<String, Error>;
+    /// ```
+    ///
+    /// The whole file is buffered into memory, whatever its size.
+    /// Use [`SftpClient::read_to_string_capped`] to bound the memory used for files that might be large.
+    ///
+    /// The content is validated like with [`std::fs::read_to_string`]:
+    /// an `InvalidData` error is returned if the file is not valid UTF-8.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the remote file to read
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the file is closed in the background.
+    pub fn read_to_string(
+        &self,
+        path: impl Into<Path>,
+    ) -> impl Future<Output = Result<String, Error>> + Send + 'static {
+        self.read_to_string_capped(path, usize::MAX)
+    }
+
+    /// Read the whole content of a remote file as a UTF-8 string, if it is not too large.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read_to_string_capped(&self, path: impl Into<Path>, max_len: usize) -> Result<String, Error>;
+    /// ```
+    ///
+    /// The file is read like with [`SftpClient::read_to_string`],
+    /// but an `InvalidData` error is returned if it is longer than `max_len` bytes.
+    /// The size of the file is checked before reading it if the server reports it,
+    /// and at most `max_len + 1` bytes are buffered otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the remote file to read
+    /// * `max_len` - Maximal length of the file in bytes
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request is sent before the future is returned.
+    /// If the future is dropped before completion, the file is closed in the background.
+    pub fn read_to_string_capped(
+        &self,
+        path: impl Into<Path>,
+        max_len: usize,
+    ) -> impl Future<Output = Result<String, Error>> + Send + 'static {
+        let open = self.open_with_stat(path, PFlags::READ, Attrs::default());
+
+        async move {
+            let too_large = || {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("File is longer than {max_len} bytes"),
+                ))
+            };
+
+            let (mut file, attrs) = open.await?;
+            if attrs.size.is_some_and(|size| size > max_len as u64) {
+                file.close().await?;
+                return Err(too_large());
+            }
+
+            let mut content = Vec::new();
+            let result = (&mut file)
+                .take((max_len as u64).saturating_add(1))
+                .read_to_end(&mut content)
+                .await;
+            let closed = file.close().await;
+
+            result?;
+            closed?;
+            if content.len() > max_len {
+                return Err(too_large());
+            }
+
+            String::from_utf8(content)
+                .map_err(|err| Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err)))
+        }
+    }
+
+    /// Download a remote directory tree into a local directory.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn download_dir(&self, remote: impl Into<Path>, local: impl AsRef<std::path::Path>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// The local directory is created if it does not exist,
+    /// and the remote tree is recreated inside it, à-la `scp -r`:
+    ///
+    /// * directories are created, and their permissions are set once their content is downloaded,
+    /// * regular files are downloaded, replacing existing local files, and their permissions are copied,
+    /// * symbolic links are recreated with the target returned by [`SftpClient::readlink`], without following them,
+    /// * other files (devices, FIFOs, sockets, or entries whose type is not sent by the server) are skipped.
+    ///
+    /// Permissions and symbolic links are only recreated on Unix.
+    /// Only the `rwx` bits of the permissions are copied: setuid, setgid and sticky bits are dropped.
+    /// At most 8 remote files or directories are read at the same time.
+    ///
+    /// If an entry cannot be downloaded, no other entry is started,
+    /// and the first error is returned once the on-flight entries are finished.
+    ///
+    /// The listings of the server are not trusted:
+    /// entries whose name is not a single path component, or appears twice in a listing, are rejected.
+    /// Existing local files and symbolic links are removed before being replaced, instead of being written through,
+    /// and the download fails rather than descending into an existing local symbolic link.
+    /// The symbolic links recreated locally can still point anywhere, but they are never followed by the download.
+    /// This does not protect against other local processes modifying the local tree concurrently.
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Path of the remote directory to download
+    /// * `local` - Path of the local directory to download into
+    ///
+    /// # Cancel safety
+    ///
+    /// The stat request is sent before the future is returned.
+    /// If the future is dropped before completion, the local tree is left partially downloaded,
+    /// and the remote handles are not closed.
+    pub fn download_dir(
+        &self,
+        remote: impl Into<Path>,
+        local: impl AsRef<std::path::Path>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + 'static {
+        let remote = self.resolve_path(remote);
+        let local = local.as_ref().to_path_buf();
+        let stat = self.stat(remote.clone());
+        let client = self.clone();
+
+        async move {
+            let attrs = stat.await?;
+            let root = DirEntry::new(remote.clone(), &attrs);
+            if !root.file_type.is_dir() {
+                return Err(StatusCode::Failure
+                    .to_status(format!("Not a directory: {}", remote.as_ref() as &str))
+                    .into());
+            }
+
+            // The local directory itself may be a symbolic link chosen by the caller
+            tokio::fs::create_dir_all(&local).await?;
+            let mut queue = client.download_children(&remote, &local).await?;
+            let mut dirs = vec![(local, root.permissions)];
+            let mut running = FuturesUnordered::new();
+            let mut error = None;
+
+            loop {
+                while running.len() < DOWNLOAD_DIR_LIMIT {
+                    let Some(entry) = queue.pop() else {
+                        break;
+                    };
+                    if entry.entry.file_type.is_dir() {
+                        dirs.push((entry.local.clone(), entry.entry.permissions));
+                    }
+                    running.push(client.download_entry(entry));
+                }
+
+                match running.next().await {
+                    Some(Ok(children)) => queue.extend(children),
+                    Some(Err(err)) => {
+                        queue.clear();
+                        error.get_or_insert(err);
+                    }
+                    None => break,
+                }
+            }
+
+            if let Some(err) = error {
+                return Err(err);
+            }
+
+            // Children are set before their parent, in case the parent is not writable
+            for (local, permissions) in dirs.into_iter().rev() {
+                set_local_permissions(&local, permissions).await?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Download a remote file into a writer, querying its size only if there is a progress callback.
+    fn download<'a>(
+        &self,
+        remote: impl Into<Path>,
+        mut writer: impl AsyncWrite + Unpin + Send + 'a,
+        progress: Option<impl FnMut(u64, Option<u64>) + Send + 'a>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + 'a {
+        let open = self.open_handle(remote, PFlags::READ, Attrs::default());
+        let client = self.clone();
+
+        async move {
+            let handle = open.await?;
+            let mut progress = match progress {
+                Some(progress) => {
+                    let total = match client.fstat(handle.clone()).await {
+                        Ok(attrs) => attrs.size,
+                        Err(err) => {
+                            log::debug!("Could not get the size of the file to download: {err:?}");
+                            None
+                        }
+                    };
+                    Progress::new(Some(progress), total)
+                }
+                None => Progress::new(None, None),
+            };
+
+            let result = client.read_into(&handle, &mut writer, &mut progress).await;
+            let closed = client.close(handle).await;
+
+            let read = result?;
+            closed?;
+            writer.flush().await?;
+            progress.finish(read);
+            Ok(read)
+        }
+    }
+
+    /// Copy a remote file into another remote file.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn copy(&self, src: impl Into<Path>, dst: impl Into<Path>) -> Result<u64, Error>;
+    /// ```
+    ///
+    /// The destination file is created if it does not exist, and truncated otherwise.
+    /// Its permissions are set to the permissions of the source file.
+    ///
+    /// The data is not copied by the server: it is read from the source file
+    /// and written back to the destination file through the client,
+    /// with multiple requests on-flight at the same time.
+    /// Servers supporting the `copy-data` extension can copy files without this round-trip.
+    ///
+    /// Returns the total number of bytes copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Path of the remote file to read
+    /// * `dst` - Path of the remote file to write
+    ///
+    /// # Cancel safety
+    ///
+    /// The open request of the source file is sent before the future is returned.
+    /// If the future is dropped before completion, the destination file is left partially written,
+    /// and the handles are not closed.
+    pub fn copy(
+        &self,
+        src: impl Into<Path>,
+        dst: impl Into<Path>,
+    ) -> impl Future<Output = Result<u64, Error>> + Send + Sync + 'static {
+        let open_src = self.open_handle(src, PFlags::READ, Attrs::default());
+        let dst = dst.into();
+        let client = self.clone();
+
+        async move {
+            let src = open_src.await?;
+
+            let dst = match client
+                .open_handle(
+                    dst,
+                    PFlags::WRITE | PFlags::CREATE | PFlags::TRUNCATE,
+                    Attrs::default(),
+                )
+                .await
+            {
+                Ok(dst) => dst,
+                Err(err) => {
+                    _ = client.close(src).await;
+                    return Err(err);
+                }
+            };
+
+            let result = client.copy_handles(&src, &dst).await;
+            let closed_src = client.close(src).await;
+            let closed_dst = client.close(dst).await;
+
+            let copied = result?;
+            closed_src?;
+            closed_dst?;
+            Ok(copied)
+        }
+    }
+
+    /// Rename/move a file, copying it when it cannot be renamed.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn rename_or_copy(&self, old_path: impl Into<Path>, new_path: impl Into<Path>, fallback: bool) -> Result<(), Error>;
+    /// ```
+    ///
+    /// A regular `rename` is tried first.
+    /// Many servers cannot rename a file across filesystems, and reply with a `Failure` status.
+    /// In that case, and only if `fallback` is `true`, the file is copied with [`SftpClient::copy`],
+    /// its attributes are restored, and the original file is removed.
+    ///
+    /// The attributes restored on the copy are the ones given by `lstat` on the original file:
+    /// the permissions (including the setuid, setgid and sticky bits) are copied by [`SftpClient::copy`],
+    /// and the access and modification times are set once the content is copied.
+    /// The owner and group are also restored, but most servers only allow it to privileged users:
+    /// if the server refuses, the copy keeps the owner and group of the user of the session.
+    /// Everything SFTP does not expose (eg: extended attributes, ACLs, or the creation time) is lost.
+    /// Other errors (eg: `NoSuchFile` for a missing source) are returned as-is.
+    ///
+    /// Only regular files can be copied: the `Failure` of the rename is turned into a clearer `Failure`
+    /// if `old_path` is a directory or any other kind of file.
+    /// An existing destination is never overwritten by the copy: the error of the rename is returned instead.
+    ///
+    /// The fallback path is *not* atomic, and can be much slower than a rename,
+    /// as the whole content of the file goes through the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_path`: Current path of the file to rename/move
+    /// * `new_path`: New path where the file will be moved to
+    /// * `fallback`: Allow copying the file when the rename fails
+    ///
+    /// # Cancel safety
+    ///
+    /// The rename request is sent before the future is returned.
+    /// If the future is dropped while in the fallback path,
+    /// the destination might be left partially written, and the original file is kept.
+    pub fn rename_or_copy(
+        &self,
+        old_path: impl Into<Path>,
+        new_path: impl Into<Path>,
+        fallback: bool,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        let old_path = self.resolve_path(old_path);
+        let new_path = self.resolve_path(new_path);
+        let rename = self.rename(old_path.clone(), new_path.clone());
+        let client = self.clone();
+
+        async move {
+            let err = match rename.await {
+                Err(
+                    err @ Error::Sftp(Status {
+                        code: StatusCode::Failure,
+                        ..
+                    }),
+                ) if fallback => err,
+                result => return result,
+            };
+
+            let attrs = client.lstat(old_path.clone()).await?;
+            if let Some(perms) = attrs.perms {
+                if perms.bits() & 0xF000 != Permisions::REG.bits() {
+                    return Err(StatusCode::Failure
+                        .to_status(format!(
+                            "Cannot copy {}: not a regular file",
+                            old_path.as_ref() as &str
+                        ))
+                        .into());
+                }
+            }
+
+            // The rename might have failed because the destination exists
+            if client.lstat(new_path.clone()).await.is_ok() {
+                return Err(err);
+            }
+
+            client.copy(old_path.clone(), new_path.clone()).await?;
+            if attrs.time.is_some() {
+                client
+                    .setstat(
+                        new_path.clone(),
+                        Attrs {
+                            time: attrs.time,
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+            }
+            if attrs.owner.is_some() {
+                let owner = Attrs {
+                    owner: attrs.owner,
+                    ..Default::default()
+                };
+                if let Err(err) = client.setstat(new_path.clone(), owner).await {
+                    log::debug!("Could not restore the owner of {new_path:?}: {err:?}");
+                }
+            }
+
+            client.remove(old_path).await
+        }
+    }
+
+    /// Read a range of an opened file, with as many requests as needed.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn read_exact_at(&self, handle: Handle, offset: u64, length: usize) -> Result<Bytes, Error>;
+    /// ```
+    ///
+    /// Contrary to [`SftpClient::read`], the range is split into chunks that the server accepts,
+    /// and short reads are completed, so exactly `length` bytes are returned,
+    /// unless the end of the file is reached before.
+    /// `length` can exceed the size of the file (eg: `usize::MAX` to read until the end of the file):
+    /// the memory is allocated as the data is received.
+    /// Multiple read requests are on-flight at the same time.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: Handle of the file to read from
+    /// * `offset`: Byte offset where the read should start
+    /// * `length`: Number of bytes to read
+    ///
+    /// # Cancel safety
+    ///
+    /// It is safe to cancel the future.
+    /// However, the first read requests are sent before the future is returned.
+    pub fn read_exact_at(
+        &self,
+        handle: Handle,
+        offset: u64,
+        length: usize,
+    ) -> impl Future<Output = Result<Bytes, Error>> + Send + Sync + 'static {
+        let chunk_size = self.read_chunk_size() as usize;
+        let mut pending = FuturesOrdered::new();
+        let mut next = 0usize;
+
+        // Read ahead, starting from `next`
+        let ahead_handle = handle.clone();
+        let read_ahead =
+            move |client: &SftpClient, pending: &mut FuturesOrdered<_>, next: &mut usize| {
+                while pending.len() < client.config.read_ahead && *next < length {
+                    let (start, len) = (*next, chunk_size.min(length - *next));
+                    pending.push_back(
+                        client
+                            .read(ahead_handle.clone(), offset + start as u64, len as u32)
+                            .map(move |result| (start, len, result)),
+                    );
+                    *next += len;
+                }
+            };
+        read_ahead(self, &mut pending, &mut next);
+        let client = self.clone();
+
+        // The length can be far beyond the end of the file: only reserve what is read ahead
+        let capacity = length.min(chunk_size.saturating_mul(self.config.read_ahead.max(1)));
+
+        async move {
+            let mut data = BytesMut::with_capacity(capacity);
+
+            while let Some((start, len, mut result)) = pending.next().await {
+                let end = start + len;
+                let mut position = start;
+
+                // Short reads are completed before moving to the next chunk
+                loop {
+                    match result {
+                        Ok(chunk) if !chunk.is_empty() => {
+                            data.extend_from_slice(&chunk);
+                            position += chunk.len();
+                        }
+                        Ok(_)
+                        | Err(Error::Sftp(Status {
+                            code: StatusCode::Eof,
+                            ..
+                        })) => return Ok(data.freeze()),
+                        Err(err) => return Err(err),
+                    }
+
+                    if position >= end {
+                        break;
+                    }
+
+                    result = client
+                        .read(
+                            handle.clone(),
+                            offset + position as u64,
+                            (end - position) as u32,
+                        )
+                        .await;
+                }
+
+                read_ahead(&client, &mut pending, &mut next);
+            }
+
+            Ok(data.freeze())
+        }
+    }
+
+    /// Write a buffer into an opened file, with as many requests as needed.
+    ///
+    /// Equivalent to:
+    ///
+    /// ```ignore
+    /// async fn write_all_at(&self, handle: Handle, offset: u64, data: impl Into<Bytes>) -> Result<(), Error>;
+    /// ```
+    ///
+    /// Contrary to [`SftpClient::write`], the buffer is split into chunks that the server accepts.
+    /// Multiple write requests are on-flight at the same time.
+    ///
+    /// If a chunk cannot be written, no more chunks are sent, and the writes already on-flight are awaited.
+    /// The error is then [`Error::WriteFailed`], with the offset of the first chunk that failed:
+    /// all the data before this offset has been written.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle`: Handle of the file to write to
+    /// * `offset`: Byte offset where the write should start
+    /// * `data`: Bytes to be written to the file
+    ///
+    /// # Cancel safety
+    ///
+    /// The first write requests are sent before the future is returned.
+    /// If the future is dropped before completion, the range might be left partially written.
+    pub fn write_all_at(
+        &self,
+        handle: Handle,
+        offset: u64,
+        data: impl Into<Bytes>,
+    ) -> impl Future<Output = Result<(), Error>> + Send + Sync + 'static {
+        let data: Bytes = data.into();
+        let chunk_size = self.write_chunk_size() as usize;
+        let mut chunks = (0..data.len()).step_by(chunk_size).map(move |start| {
+            (
+                offset + start as u64,
+                data.slice(start..data.len().min(start + chunk_size)),
+            )
+        });
+
+        let mut pending = FuturesUnordered::new();
+        for (offset, chunk) in chunks.by_ref().take(self.config.write_pipeline) {
+            pending.push(write_chunk(self, handle.clone(), offset, chunk));
+        }
+        let client = self.clone();
+
+        async move {
+            // Lowest offset whose write has failed, and its error
+            let mut failure: Option<(u64, Error)> = None;
+
+            while let Some((offset, result)) = pending.next().await {
+                if let Err(err) = result {
+                    if failure.as_ref().is_none_or(|(first, _)| offset < *first) {
+                        failure = Some((offset, err));
+                    }
+                }
+
+                if failure.is_none() {
+                    if let Some((offset, chunk)) = chunks.next() {
+                        pending.push(write_chunk(&client, handle.clone(), offset, chunk));
+                    }
+                }
+            }
+
+            match failure {
+                Some((offset, err)) => Err(Error::WriteFailed {
+                    offset,
+                    source: Box::new(err),
+                }),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Copy the whole content and the permissions of the remote file `src` into the remote file `dst`.
+    async fn copy_handles(&self, src: &Handle, dst: &Handle) -> Result<u64, Error> {
+        let perms = self.fstat(src.clone()).await?.perms;
+
+        // The data read is written back as-is
+        let chunk_size = self.read_chunk_size().min(self.write_chunk_size());
+        let mut reads = FuturesOrdered::new();
+        let mut writes = FuturesUnordered::new();
+        let mut next_offset = 0u64;
+        let mut total = 0u64;
+
+        'copy: loop {
+            // Read ahead
+            while reads.len() < self.config.read_ahead {
+                let offset = next_offset;
+                reads.push_back(
+                    self.read(src.clone(), offset, chunk_size)
+                        .map(move |result| (offset, result)),
+                );
+                next_offset += chunk_size as u64;
+            }
+
+            let Some((mut offset, mut result)) = reads.next().await else {
+                break;
+            };
+            let end = offset + chunk_size as u64;
+
+            // Short reads are completed before moving to the next chunk
+            loop {
+                match result {
+                    Ok(data) if !data.is_empty() => {
+                        let len = data.len() as u64;
+                        writes.push(self.write(dst.clone(), offset, data));
+                        offset += len;
+                        total += len;
+                    }
+                    Ok(_)
+                    | Err(Error::Sftp(Status {
+                        code: StatusCode::Eof,
+                        ..
+                    })) => break 'copy,
+                    Err(err) => return Err(err),
+                }
+
+                // Limit the number of on-flight write requests
+                while writes.len() >= self.config.write_pipeline {
+                    if let Some(result) = writes.next().await {
+                        result?;
+                    }
+                }
+
+                if offset >= end {
+                    break;
+                }
+
+                result = self.read(src.clone(), offset, (end - offset) as u32).await;
+            }
+        }
+
+        while let Some(result) = writes.next().await {
+            result?;
+        }
+
+        if perms.is_some() {
+            self.fsetstat(
+                dst.clone(),
+                Attrs {
+                    perms,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        }
+
+        Ok(total)
+    }
+
+    /// Read the whole content of the remote file `handle` into `writer`, starting at offset 0.
+    async fn read_into(
+        &self,
+        handle: &Handle,
+        writer: &mut (impl AsyncWrite + Unpin),
+        progress: &mut Progress<impl FnMut(u64, Option<u64>)>,
+    ) -> Result<u64, Error> {
+        let chunk_size = self.read_chunk_size();
+        let mut pending = FuturesOrdered::new();
+        let mut next_offset = 0u64;
+        let mut total = 0u64;
+
+        loop {
+            // Read ahead
+            while pending.len() < self.config.read_ahead {
+                let offset = next_offset;
+                pending.push_back(
+                    self.read(handle.clone(), offset, chunk_size)
+                        .map(move |result| (offset, result)),
+                );
+                next_offset += chunk_size as u64;
+            }
+
+            let Some((mut offset, mut result)) = pending.next().await else {
+                break;
+            };
+            let end = offset + chunk_size as u64;
+
+            // Short reads are completed before moving to the next chunk
+            // to avoid leaving holes in the output
+            loop {
+                match result {
+                    Ok(data) if !data.is_empty() => {
+                        writer.write_all(&data).await?;
+                        offset += data.len() as u64;
+                        total += data.len() as u64;
+                        progress.update(total);
+                    }
+                    Ok(_)
+                    | Err(Error::Sftp(Status {
+                        code: StatusCode::Eof,
+                        ..
+                    })) => return Ok(total),
+                    Err(err) => return Err(err),
+                }
+
+                if offset >= end {
+                    break;
+                }
+
+                result = self
+                    .read(handle.clone(), offset, (end - offset) as u32)
+                    .await;
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Download a single entry of [`SftpClient::download_dir`].
+    ///
+    /// Returns the entries of the directory to download next, if the entry is a directory.
+    async fn download_entry(&self, entry: DownloadEntry) -> Result<Vec<DownloadEntry>, Error> {
+        let DownloadEntry {
+            remote,
+            local,
+            entry,
+        } = entry;
+
+        match entry.file_type {
+            FileType::Dir => {
+                // Never descend into an existing local symbolic link
+                match tokio::fs::create_dir(&local).await {
+                    Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                        if !tokio::fs::symlink_metadata(&local).await?.is_dir() {
+                            return Err(Error::Io(std::io::Error::new(
+                                std::io::ErrorKind::AlreadyExists,
+                                format!("Not a directory: {}", local.display()),
+                            )));
+                        }
+                    }
+                    result => result?,
+                }
+
+                self.download_children(&remote, &local).await
+            }
+            FileType::File => {
+                // The file is created anew, so that an existing local symbolic link is not followed
+                remove_local_file(&local).await?;
+                let file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&local)
+                    .await?;
+                self.download_stream(remote, file).await?;
+                set_local_permissions(&local, entry.permissions).await?;
+                Ok(Vec::new())
+            }
+            FileType::Symlink => {
+                let target = self.readlink(remote).await?;
+
+                #[cfg(unix)]
+                {
+                    remove_local_file(&local).await?;
+                    tokio::fs::symlink(target.as_ref() as &str, &local).await?;
+                }
+                #[cfg(not(unix))]
+                log::warn!(
+                    "Skipping symbolic link {} -> {}: not supported on this platform",
+                    remote.as_ref() as &str,
+                    target.as_ref() as &str
+                );
+
+                Ok(Vec::new())
+            }
+            file_type => {
+                log::warn!(
+                    "Skipping {}: unsupported file type {file_type:?}",
+                    remote.as_ref() as &str
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// List the entries of a remote directory to download into the local directory `local`.
+    ///
+    /// The names that are not a single path component, or that appear more than once, are rejected.
+    async fn download_children(
+        &self,
+        remote: &Path,
+        local: &std::path::Path,
+    ) -> Result<Vec<DownloadEntry>, Error> {
+        let mut names = HashSet::new();
+        let mut children = Vec::new();
+
+        for child in self.readdir(remote.clone()).await?.0 {
+            let child = DirEntry::from(child);
+            let name: &str = child.name.as_ref();
+            if name == "." || name == ".." {
+                continue;
+            }
+            if name.is_empty() || name.contains('/') || name.contains('\\') {
+                return Err(StatusCode::BadMessage
+                    .to_status(format!(
+                        "Invalid name in directory {}: {name:?}",
+                        remote.as_ref() as &str
+                    ))
+                    .into());
+            }
+            if !names.insert(name.to_owned()) {
+                return Err(StatusCode::BadMessage
+                    .to_status(format!(
+                        "Duplicate name in directory {}: {name:?}",
+                        remote.as_ref() as &str
+                    ))
+                    .into());
+            }
+
+            children.push(DownloadEntry {
+                remote: remote.clone() / &child.name,
+                local: local.join(name),
+                entry: child,
+            });
+        }
+
+        Ok(children)
+    }
+
+    /// Upload a single entry of [`SftpClient::upload_dir_with_policy`].
+    ///
+    /// Returns the entries of the directory to upload next, if the entry is a directory,
+    /// and the attributes to set on the remote directory once its content is uploaded.
+    async fn upload_entry(
+        &self,
+        entry: UploadEntry,
+        policy: OverwritePolicy,
+    ) -> Result<(Vec<UploadEntry>, Option<(Path, Attrs)>), Error> {
+        let UploadEntry {
+            local,
+            remote,
+            metadata,
+        } = entry;
+        let file_type = metadata.file_type();
+
+        if file_type.is_dir() {
+            let created = self.create_dir(remote.clone()).await?;
+            let attrs = (created || policy == OverwritePolicy::Overwrite)
+                .then(|| (remote.clone(), local_attrs(&metadata)));
+
+            let mut children = Vec::new();
+            let mut dir = tokio::fs::read_dir(&local).await?;
+            while let Some(child) = dir.next_entry().await? {
+                let name = child.file_name().into_string().map_err(|name| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("File name is not valid UTF-8: {name:?}"),
+                    )
+                })?;
+
+                children.push(UploadEntry {
+                    local: child.path(),
+                    remote: remote.clone() / &name,
+                    metadata: tokio::fs::symlink_metadata(child.path()).await?,
+                });
+            }
+
+            Ok((children, attrs))
+        } else if policy == OverwritePolicy::Skip && self.lexists(remote.clone()).await? {
+            log::debug!(
+                "Skipping {}: the remote entry already exists",
+                local.display()
+            );
+            Ok((Vec::new(), None))
+        } else if file_type.is_file() {
+            let file = tokio::fs::File::open(&local).await?;
+            self.upload_stream(file, remote.clone()).await?;
+            self.setstat(remote, local_attrs(&metadata)).await?;
+            Ok((Vec::new(), None))
+        } else if file_type.is_symlink() {
+            let target = tokio::fs::read_link(&local).await?;
+            let target = target.into_os_string().into_string().map_err(|target| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Link target is not valid UTF-8: {target:?}"),
+                )
+            })?;
+
+            if self.lstat(remote.clone()).await.is_ok() {
+                self.remove(remote.clone()).await?;
+            }
+            self.create_symlink(target, remote).await?;
+            Ok((Vec::new(), None))
+        } else {
+            log::warn!(
+                "Skipping {}: unsupported file type {file_type:?}",
+                local.display()
+            );
+            Ok((Vec::new(), None))
+        }
+    }
+
+    /// Check whether a remote entry exists, without following symlinks.
+    async fn lexists(&self, path: Path) -> Result<bool, Error> {
+        match self.lstat(path).await {
+            Ok(_) => Ok(true),
+            Err(Error::Sftp(Status {
+                code: StatusCode::NoSuchFile,
+                ..
+            })) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Create a remote directory, unless it already exists.
+    ///
+    /// Returns whether the directory has been created.
+    async fn create_dir(&self, path: Path) -> Result<bool, Error> {
+        match self.mkdir(path.clone()).await {
+            Ok(()) => Ok(true),
+            Err(err) => match self.lstat(path).await {
+                Ok(attrs) if DirEntry::new(Path::default(), &attrs).file_type.is_dir() => Ok(false),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Create a remote directory and all its missing parents.
+    async fn create_dir_all(&self, path: Path) -> Result<(), Error> {
+        let mut missing = Vec::new();
+        let mut current = Some(path);
+
+        while let Some(path) = current {
+            match self.stat(path.clone()).await {
+                Ok(_) => break,
+                Err(Error::Sftp(Status {
+                    code: StatusCode::NoSuchFile,
+                    ..
+                })) => {
+                    current = remote_parent(&path);
+                    missing.push(path);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        for path in missing.into_iter().rev() {
+            self.create_dir(path).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the whole content of `reader` into the remote file `handle`, starting at offset 0.
+    ///
+    /// The progress reports the number of bytes acknowledged by the server.
+    async fn write_from(
+        &self,
+        handle: &Handle,
+        reader: &mut (impl AsyncRead + Unpin),
+        progress: &mut Progress<impl FnMut(u64, Option<u64>)>,
+    ) -> Result<u64, Error> {
+        let mut pending = FuturesUnordered::new();
+        let mut offset = 0u64;
+        let mut written = 0u64;
+
+        loop {
+            let mut buffer = BytesMut::with_capacity(self.write_chunk_size() as usize);
+            let len = reader.read_buf(&mut buffer).await?;
+            if len == 0 {
+                break;
+            }
+
+            pending.push(
+                self.write(handle.clone(), offset, buffer)
+                    .map(move |result| result.map(|()| len as u64)),
+            );
+            offset += len as u64;
+
+            // Limit the number of on-flight requests
+            while pending.len() >= self.config.write_pipeline {
+                if let Some(result) = pending.next().await {
+                    written += result?;
+                    progress.update(written);
+                }
+            }
+        }
+
+        while let Some(result) = pending.next().await {
+            written += result?;
+            progress.update(written);
+        }
+
+        Ok(offset)
+    }
+}
+
+/// Maximal number of entries downloaded at the same time by [`SftpClient::download_dir`]
+const DOWNLOAD_DIR_LIMIT: usize = 8;
+
+/// Entry of a remote tree to download, with its local destination
+struct DownloadEntry {
+    remote: Path,
+    local: PathBuf,
+    entry: DirEntry,
+}
+
+/// Maximal number of entries uploaded at the same time by [`SftpClient::upload_dir`]
+const UPLOAD_DIR_LIMIT: usize = 8;
+
+/// Entry of a local tree to upload, with its remote destination
+struct UploadEntry {
+    local: PathBuf,
+    remote: Path,
+    metadata: std::fs::Metadata,
+}
+
+/// Attributes of a local file to copy on the remote side: its times, and its permissions on Unix.
+fn local_attrs(metadata: &std::fs::Metadata) -> Attrs {
+    let attrs = Attrs::from_metadata(metadata);
+    Attrs {
+        perms: attrs
+            .perms
+            .map(|perms| Permisions::from_bits_retain(perms.bits() & 0o7777)),
+        time: attrs.time,
+        ..Default::default()
+    }
+}
+
+/// Write a chunk of a file, and tag the result with the offset of the chunk.
+fn write_chunk(
+    client: &SftpClient,
+    handle: Handle,
+    offset: u64,
+    chunk: Bytes,
+) -> impl Future<Output = (u64, Result<(), Error>)> + Send + Sync + 'static {
+    client
+        .write(handle, offset, chunk)
+        .map(move |result| (offset, result))
+}
+
+/// Parent of a remote path, if it has one.
+pub(super) fn remote_parent(path: &Path) -> Option<Path> {
+    let path: &str = path.as_ref();
+    match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) if path.starts_with('/') => Some(Path::from("/")),
+        Some((parent, _)) if !parent.is_empty() => Some(Path::from(parent)),
+        _ => None,
+    }
+}
+
+/// Set the permission bits of a local file, if they are known and the platform supports them.
+///
+/// Only the `rwx` bits are set: the setuid, setgid and sticky bits sent by the server are dropped.
+async fn set_local_permissions(
+    path: &std::path::Path,
+    permissions: Option<u32>,
+) -> Result<(), Error> {
+    #[cfg(unix)]
+    if let Some(permissions) = permissions {
+        use std::os::unix::fs::PermissionsExt;
+
+        let permissions = std::fs::Permissions::from_mode(permissions & 0o777);
+        tokio::fs::set_permissions(path, permissions).await?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, permissions);
+
+    Ok(())
+}
+
+/// Remove a local file or symbolic link, if it exists, without following it.
+async fn remove_local_file(path: &std::path::Path) -> Result<(), Error> {
+    match tokio::fs::remove_file(path).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Progress callback of a transfer, throttled to avoid calling it for every request.
+struct Progress<F> {
+    callback: Option<F>,
+    total: Option<u64>,
+    last: Option<Instant>,
+}
+
+impl Progress<fn(u64, Option<u64>)> {
+    /// Progress of a transfer without any callback
+    fn none() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl<F: FnMut(u64, Option<u64>)> Progress<F> {
+    /// Minimal delay between two calls of the callback
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn new(callback: Option<F>, total: Option<u64>) -> Self {
+        Self {
+            callback,
+            total,
+            last: None,
+        }
+    }
+
+    /// Report `done` bytes transferred, unless the callback has been called too recently.
+    fn update(&mut self, done: u64) {
+        if let Some(callback) = &mut self.callback {
+            let now = Instant::now();
+            if self.last.is_none_or(|last| now - last >= Self::INTERVAL) {
+                self.last = Some(now);
+                callback(done, self.total);
+            }
+        }
+    }
+
+    /// Report the end of the transfer, with `done` bytes transferred.
+    fn finish(&mut self, done: u64) {
+        if let Some(callback) = &mut self.callback {
+            callback(done, self.total);
+        }
+    }
+}
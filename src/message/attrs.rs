@@ -50,6 +50,133 @@ impl Attrs {
             time: None,
         }
     }
+
+    /// Get the attributes of a local file, to compare it with a remote file, or to copy them.
+    ///
+    /// The size, and the access and modification times are always filled, when the platform supports them.
+    /// The permissions (including the file type bits) and the owner are only filled on Unix:
+    /// on other platforms, like Windows, they are left empty.
+    ///
+    /// The times are truncated to the second, as in SFTP.
+    /// If the access time is not available, the modification time is used instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` - Metadata of the local file
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (owner, perms) = {
+            use std::os::unix::fs::MetadataExt;
+
+            (
+                Some(Owner {
+                    uid: metadata.uid(),
+                    gid: metadata.gid(),
+                }),
+                Some(Permisions::from_bits_retain(metadata.mode())),
+            )
+        };
+        #[cfg(not(unix))]
+        let (owner, perms) = (None, None);
+
+        let time = metadata.modified().ok().and_then(|mtime| {
+            let atime = metadata.accessed().unwrap_or(mtime);
+            Time::from_system_times(atime, mtime)
+        });
+
+        Self {
+            size: Some(metadata.len()),
+            owner,
+            perms,
+            time,
+        }
+    }
+
+    /// Project the attributes on the terms of [`std::fs::Metadata`].
+    ///
+    /// The attributes not sent by the server are `None`.
+    pub fn to_metadata(&self) -> FileMetadata {
+        FileMetadata {
+            len: self.size,
+            modified: self.time.map(|time| time.modification_time()),
+            accessed: self.time.map(|time| time.access_time()),
+            mode: self.perms.map(|perms| perms.bits()),
+            uid: self.owner.map(|owner| owner.uid),
+            gid: self.owner.map(|owner| owner.gid),
+        }
+    }
+}
+
+/// Metadata of a file, in the terms of [`std::fs::Metadata`].
+///
+/// See [`Attrs::to_metadata`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileMetadata {
+    /// Size of the file in bytes, like [`std::fs::Metadata::len`]
+    pub len: Option<u64>,
+    /// Last modification time, like [`std::fs::Metadata::modified`]
+    pub modified: Option<std::time::SystemTime>,
+    /// Last access time, like [`std::fs::Metadata::accessed`]
+    pub accessed: Option<std::time::SystemTime>,
+    /// File type and permission bits, like `std::os::unix::fs::MetadataExt::mode`
+    pub mode: Option<u32>,
+    /// User ID of the owner, like `std::os::unix::fs::MetadataExt::uid`
+    pub uid: Option<u32>,
+    /// Group ID of the owner, like `std::os::unix::fs::MetadataExt::gid`
+    pub gid: Option<u32>,
+}
+
+/// Format the attributes à-la `ls -l`: `drwxr-xr-x  uid  gid  size  mtime`.
+///
+/// Missing attributes are replaced by `?` placeholders.
+/// The modification time is displayed in UTC.
+impl std::fmt::Display for Attrs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.perms {
+            Some(perms) => write!(f, "{perms}")?,
+            None => f.write_str("??????????")?,
+        }
+        match self.owner {
+            Some(Owner { uid, gid }) => write!(f, " {uid:>5} {gid:>5}")?,
+            None => write!(f, " {:>5} {:>5}", "?", "?")?,
+        }
+        match self.size {
+            Some(size) => write!(f, " {size:>10}")?,
+            None => write!(f, " {:>10}", "?")?,
+        }
+        match self.time {
+            Some(Time { mtime, .. }) => {
+                f.write_str(" ")?;
+                fmt_timestamp(mtime, f)
+            }
+            None => write!(f, " {:>16}", "?"),
+        }
+    }
+}
+
+/// Format a UNIX timestamp as `YYYY-MM-DD HH:MM` in UTC.
+fn fmt_timestamp(timestamp: u32, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let days = timestamp / 86400;
+    let seconds = timestamp % 86400;
+
+    // Convert days since epoch into a civil date
+    // See: <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u32::from(month <= 2);
+
+    write!(
+        f,
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60
+    )
 }
 
 bitflags! {
@@ -109,6 +236,51 @@ bitflags! {
     }
 }
 
+/// Format permissions as the conventional mode string: a file type character followed by `rwx` triplets.
+///
+/// Setuid, setgid and sticky bits are rendered as `s`/`S` and `t`/`T`, like `ls -l`.
+impl std::fmt::Display for Permisions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let perms = *self;
+
+        let file_type = match perms.bits() & 0xF000 {
+            0x1000 => 'p',
+            0x2000 => 'c',
+            0x4000 => 'd',
+            0x6000 => 'b',
+            0x8000 => '-',
+            0xA000 => 'l',
+            0xC000 => 's',
+            _ => '?',
+        };
+
+        let bit = |flag: Permisions, c: char| if perms.contains(flag) { c } else { '-' };
+        let special = |x: Permisions, s: Permisions, set: char| match (
+            perms.contains(x),
+            perms.contains(s),
+        ) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+
+        write!(
+            f,
+            "{file_type}{}{}{}{}{}{}{}{}{}",
+            bit(Permisions::UR, 'r'),
+            bit(Permisions::UW, 'w'),
+            special(Permisions::UX, Permisions::SR, 's'),
+            bit(Permisions::GR, 'r'),
+            bit(Permisions::GW, 'w'),
+            special(Permisions::GX, Permisions::SW, 's'),
+            bit(Permisions::OR, 'r'),
+            bit(Permisions::OW, 'w'),
+            special(Permisions::OX, Permisions::SX, 't'),
+        )
+    }
+}
+
 /// Owner information of the file.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Owner {
@@ -127,6 +299,57 @@ pub struct Time {
     pub atime: u32,
     /// Modification time
     pub mtime: u32,
+    /// Sub-second part of the times
+    ///
+    /// It is only serialized under SFTP version 4 and later, and is `None` when decoded from version 3.
+    #[serde(rename = "nanos_v4")]
+    pub nanos: Option<TimeNanos>,
+}
+
+/// Sub-second part of a [`Time`] attribute, in nanoseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TimeNanos {
+    /// Nanoseconds of the access time
+    pub atime: u32,
+    /// Nanoseconds of the modification time
+    pub mtime: u32,
+}
+
+impl Time {
+    /// Create a time attribute from [`SystemTime`](std::time::SystemTime)s.
+    ///
+    /// Sub-second precision is truncated.
+    /// Returns `None` if any of the times is before Jan 1, 1970 or after Feb 7, 2106.
+    ///
+    /// # Arguments
+    ///
+    /// * `atime` - Access time
+    /// * `mtime` - Modification time
+    pub fn from_system_times(
+        atime: std::time::SystemTime,
+        mtime: std::time::SystemTime,
+    ) -> Option<Self> {
+        let to_u32 = |time: std::time::SystemTime| {
+            let duration = time.duration_since(std::time::UNIX_EPOCH).ok()?;
+            u32::try_from(duration.as_secs()).ok()
+        };
+
+        Some(Self {
+            atime: to_u32(atime)?,
+            mtime: to_u32(mtime)?,
+            nanos: None,
+        })
+    }
+
+    /// Access time as a [`SystemTime`](std::time::SystemTime)
+    pub fn access_time(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.atime.into())
+    }
+
+    /// Modification time as a [`SystemTime`](std::time::SystemTime)
+    pub fn modification_time(&self) -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(self.mtime.into())
+    }
 }
 
 bitflags! {
@@ -248,7 +471,7 @@ mod test {
         wire::Error,
     };
 
-    use super::Attrs;
+    use super::{Attrs, Owner, Permisions, Time, TimeNanos};
 
     #[test]
     fn encode_success() {
@@ -270,4 +493,214 @@ mod test {
             Error::Custom("invalid attr".to_string())
         );
     }
+
+    #[test]
+    fn time_nanos() {
+        use crate::wire::{SftpDecoder, SftpEncoder, SftpSizer};
+        use serde::{Deserialize, Serialize};
+
+        let time = Time {
+            atime: 0xfdb97531,
+            mtime: 0xeca86420,
+            nanos: Some(TimeNanos {
+                atime: 999_999_999,
+                mtime: 1,
+            }),
+        };
+        let v3: &[u8] = b"\xfd\xb9\x75\x31\xec\xa8\x64\x20";
+        let v4: &[u8] = b"\xfd\xb9\x75\x31\xec\xa8\x64\x20\x3b\x9a\xc9\xff\0\0\0\x01";
+
+        for (version, encoded) in [(3, v3), (4, v4)] {
+            let mut encoder = SftpEncoder::new().with_version(version);
+            time.serialize(&mut encoder).unwrap();
+            assert_eq!(encoder.to_vec(), encoded);
+
+            let mut sizer = SftpSizer::new().with_version(version);
+            time.serialize(&mut sizer).unwrap();
+            assert_eq!(sizer.len(), encoded.len());
+        }
+
+        let decoded = Time::deserialize(&mut SftpDecoder::new(v3)).unwrap();
+        assert_eq!(
+            decoded,
+            Time {
+                nanos: None,
+                ..time
+            }
+        );
+        let decoded = Time::deserialize(&mut SftpDecoder::new(v4).with_version(4)).unwrap();
+        assert_eq!(decoded, time);
+
+        for i in 8..v4.len() {
+            assert_eq!(
+                Time::deserialize(&mut SftpDecoder::new(&v4[..i]).with_version(4)),
+                Err(Error::NotEnoughData)
+            );
+        }
+
+        // Attributes keep their version 3 layout
+        for (mut attrs, encoded) in ATTRS_VALID {
+            if let Some(time) = &mut attrs.time {
+                time.nanos = Some(TimeNanos { atime: 1, mtime: 2 });
+            }
+            let mut encoder = SftpEncoder::new();
+            attrs.serialize(&mut encoder).unwrap();
+            assert_eq!(encoder.to_vec(), encoded);
+        }
+    }
+
+    #[test]
+    fn system_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let time = Time::from_system_times(
+            UNIX_EPOCH + Duration::from_millis(1_500),
+            UNIX_EPOCH + Duration::from_secs(u32::MAX.into()),
+        );
+        assert_eq!(
+            time,
+            Some(Time {
+                atime: 1,
+                mtime: u32::MAX,
+                nanos: None,
+            })
+        );
+        let time = time.unwrap();
+        assert_eq!(time.access_time(), UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(
+            time.modification_time(),
+            UNIX_EPOCH + Duration::from_secs(u32::MAX.into())
+        );
+
+        assert_eq!(
+            Time::from_system_times(UNIX_EPOCH - Duration::from_secs(1), UNIX_EPOCH),
+            None
+        );
+        assert_eq!(
+            Time::from_system_times(
+                UNIX_EPOCH,
+                UNIX_EPOCH + Duration::from_secs(u64::from(u32::MAX) + 1)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn metadata() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let path = std::env::temp_dir().join(format!("rusftp-attrs-{}", std::process::id()));
+        std::fs::write(&path, b"Hello world!").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let attrs = Attrs::from_metadata(&metadata);
+        assert_eq!(attrs.size, Some(12));
+        let mtime = metadata
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(attrs.time.map(|time| time.mtime.into()), Some(mtime));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            let perms = attrs.perms.unwrap();
+            assert!(perms.contains(Permisions::REG));
+            assert_eq!(perms.bits(), metadata.mode());
+            assert_eq!(attrs.owner.map(|owner| owner.uid), Some(metadata.uid()));
+        }
+        #[cfg(not(unix))]
+        assert_eq!((attrs.owner, attrs.perms), (None, None));
+
+        let attrs = Attrs {
+            size: Some(42),
+            owner: Some(Owner {
+                uid: 1000,
+                gid: 100,
+            }),
+            perms: Some(Permisions::from_bits_retain(0o100644)),
+            time: Some(Time {
+                atime: 1,
+                mtime: 2,
+                nanos: None,
+            }),
+        };
+        let metadata = attrs.to_metadata();
+        assert_eq!(metadata.len, Some(42));
+        assert_eq!(metadata.accessed, Some(UNIX_EPOCH + Duration::from_secs(1)));
+        assert_eq!(metadata.modified, Some(UNIX_EPOCH + Duration::from_secs(2)));
+        assert_eq!(metadata.mode, Some(0o100644));
+        assert_eq!((metadata.uid, metadata.gid), (Some(1000), Some(100)));
+        assert_eq!(Attrs::default().to_metadata(), Default::default());
+    }
+
+    #[test]
+    fn permissions_display() {
+        for (mode, expected) in [
+            (0o040755, "drwxr-xr-x"),
+            (0o104755, "-rwsr-xr-x"),
+            (0o102750, "-rwxr-s---"),
+            (0o041777, "drwxrwxrwt"),
+            (0o104644, "-rwSr--r--"),
+            (0o120777, "lrwxrwxrwx"),
+            (0o100644, "-rw-r--r--"),
+            (0o000000, "?---------"),
+        ] {
+            assert_eq!(
+                Permisions::from_bits_retain(mode).to_string(),
+                expected,
+                "mode {mode:o}"
+            );
+        }
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            Attrs::new().to_string(),
+            "??????????     ?     ?          ?                ?"
+        );
+        assert_eq!(
+            Attrs {
+                size: Some(4096),
+                owner: Some(Owner {
+                    uid: 1000,
+                    gid: 100
+                }),
+                perms: Some(Permisions::from_bits_retain(0o40755)),
+                time: Some(Time {
+                    atime: 0,
+                    mtime: 1709251199,
+                    nanos: None,
+                }),
+            }
+            .to_string(),
+            "drwxr-xr-x  1000   100       4096 2024-02-29 23:59"
+        );
+        assert_eq!(
+            Attrs {
+                perms: Some(Permisions::from_bits_retain(0o107654)),
+                ..Attrs::new()
+            }
+            .to_string(),
+            "-rwSr-sr-T     ?     ?          ?                ?"
+        );
+        assert_eq!(
+            Attrs {
+                perms: Some(Permisions::from_bits_retain(0o120777)),
+                time: Some(Time {
+                    atime: 0,
+                    mtime: 0,
+                    nanos: None
+                }),
+                ..Attrs::new()
+            }
+            .to_string(),
+            "lrwxrwxrwx     ?     ?          ? 1970-01-01 00:00"
+        );
+    }
 }
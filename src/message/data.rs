@@ -16,9 +16,11 @@
 
 use std::{borrow::Borrow, ops::Deref};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
 
+use crate::utils::IntoBytes;
+
 /// Arbitrary byte string containing the requested data.
 ///
 /// The data string may be at most the number of bytes requested in a [`Read`](crate::message::Read) request,
@@ -28,9 +30,54 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Data(pub Bytes);
 
-impl<T: crate::utils::IntoBytes> From<T> for Data {
-    fn from(value: T) -> Self {
-        Data(value.into_bytes())
+impl Data {
+    /// Create data from a static slice, without copying it.
+    pub const fn from_static(bytes: &'static [u8]) -> Self {
+        Data(Bytes::from_static(bytes))
+    }
+
+    /// Get the inner buffer of the data.
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+macro_rules! data_from {
+    ($($(#[$doc:meta])* $ty:ty),* $(,)?) => {
+        $(
+            $(#[$doc])*
+            impl From<$ty> for Data {
+                fn from(value: $ty) -> Self {
+                    Data(IntoBytes::into_bytes(value))
+                }
+            }
+        )*
+    };
+}
+
+data_from!(
+    /// Use the buffer as-is.
+    Bytes,
+    /// Freeze the buffer, without copying it.
+    BytesMut,
+    /// Take ownership of the buffer, without copying it.
+    Vec<u8>,
+    /// Take ownership of the buffer, without copying it.
+    Box<[u8]>,
+    /// Take ownership of the buffer, without copying it.
+    String,
+    /// Take ownership of the buffer, without copying it.
+    Box<str>,
+    /// Copy the slice.
+    &[u8],
+    /// Copy the string.
+    &str,
+);
+
+/// Copy the array.
+impl<const N: usize> From<&[u8; N]> for Data {
+    fn from(value: &[u8; N]) -> Self {
+        Data(Bytes::copy_from_slice(value))
     }
 }
 
@@ -68,6 +115,20 @@ mod test {
         }
     }
 
+    #[test]
+    fn conversions() {
+        let expected = Data(Bytes::from_static(b"data"));
+
+        assert_eq!(Data::from_static(b"data"), expected);
+        assert_eq!(Data::from(b"data"), expected);
+        assert_eq!(Data::from(b"data".as_slice()), expected);
+        assert_eq!(Data::from(b"data".to_vec()), expected);
+        assert_eq!(Data::from("data"), expected);
+        assert_eq!(Data::from(String::from("data")), expected);
+        assert_eq!(Data::from(Bytes::from_static(b"data")), expected);
+        assert_eq!(expected.clone().into_bytes(), expected.0);
+    }
+
     #[test]
     fn decode_failure() {
         for (bytes, expected) in BYTES_INVALID {
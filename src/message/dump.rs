@@ -0,0 +1,155 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Write;
+
+use crate::wire::Error;
+
+use super::{Message, MessageKind};
+
+/// Number of bytes per line of the dump
+const LINE_LENGTH: usize = 16;
+
+impl Message {
+    /// Encode the message, and format the frame as annotated hexadecimal.
+    ///
+    /// The header fields (length, type, and id or version) are on their own annotated lines,
+    /// followed by the content of the message, 16 bytes per line with their ASCII representation.
+    /// Each line starts with the offset of its first byte in the frame.
+    ///
+    /// It gives a copy-pasteable representation of the exact bytes sent for a message,
+    /// to attach to reports about server incompatibilities.
+    /// Use [`SftpClient::on_frame`](crate::client::SftpClient::on_frame)
+    /// to capture the frames actually exchanged with a server.
+    ///
+    /// ```text
+    /// 00000000  00 00 00 0c                                       length: 12
+    /// 00000004  11                                                type: Stat (17)
+    /// 00000005  00 00 00 01                                       id: 1
+    /// 00000009  00 00 00 03 64 69 72                              |....dir|
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Id of the request, ignored for `Init` and `Version` messages
+    pub fn to_hex_dump(&self, id: u32) -> Result<String, Error> {
+        let frame = self.encode(id)?;
+        let mut dump = String::new();
+
+        let kind = match MessageKind::try_from(self.code()) {
+            Ok(MessageKind::Unknown) | Err(_) => String::from("Unknown"),
+            Ok(kind) => format!("{kind:?}"),
+        };
+        let id_field = match self {
+            Message::Init(_) | Message::Version(_) => "version",
+            _ => "id",
+        };
+
+        let length = (frame.len() - 4) as u32;
+        let fields = [
+            (0..4, format!("length: {length}")),
+            (4..5, format!("type: {kind} ({})", self.code())),
+            (5..9, format!("{id_field}: {}", read_u32(&frame[5..9]))),
+        ];
+        for (range, annotation) in fields {
+            write_line(&mut dump, range.start, &frame[range], &annotation);
+        }
+
+        for (i, chunk) in frame[9..].chunks(LINE_LENGTH).enumerate() {
+            let ascii = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            write_line(&mut dump, 9 + i * LINE_LENGTH, chunk, &format!("|{ascii}|"));
+        }
+
+        Ok(dump)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes.try_into().unwrap_or_default())
+}
+
+/// Write a line of the dump: the offset, the bytes in hexadecimal, and the annotation.
+fn write_line(dump: &mut String, offset: usize, bytes: &[u8], annotation: &str) {
+    _ = write!(dump, "{offset:08x} ");
+    for i in 0..LINE_LENGTH {
+        if i == LINE_LENGTH / 2 {
+            dump.push(' ');
+        }
+        match bytes.get(i) {
+            Some(byte) => _ = write!(dump, " {byte:02x}"),
+            None => dump.push_str("   "),
+        }
+    }
+    _ = writeln!(dump, "  {annotation}");
+}
+
+#[cfg(test)]
+mod test {
+    use crate::message::{Init, Message, Stat};
+
+    #[test]
+    fn dump_request() {
+        let message = Message::Stat(Stat { path: "dir".into() });
+        assert_eq!(
+            message.to_hex_dump(1).unwrap(),
+            "00000000  00 00 00 0c                                       length: 12\n\
+             00000004  11                                                type: Stat (17)\n\
+             00000005  00 00 00 01                                       id: 1\n\
+             00000009  00 00 00 03 64 69 72                              |....dir|\n"
+        );
+    }
+
+    #[test]
+    fn dump_long_content() {
+        let message = Message::Stat(Stat {
+            path: "a/very/long/path/name".into(),
+        });
+        let dump = message.to_hex_dump(0x01020304).unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(
+            lines[2].ends_with("01 02 03 04                                       id: 16909060")
+        );
+        assert_eq!(
+            lines[3],
+            "00000009  00 00 00 15 61 2f 76 65  72 79 2f 6c 6f 6e 67 2f  |....a/very/long/|"
+        );
+        assert_eq!(
+            lines[4],
+            "00000019  70 61 74 68 2f 6e 61 6d  65                       |path/name|"
+        );
+    }
+
+    #[test]
+    fn dump_init() {
+        let message = Message::Init(Init {
+            version: 3,
+            extensions: Default::default(),
+        });
+        let dump = message.to_hex_dump(42).unwrap();
+        assert!(dump.contains("type: Init (1)"));
+        assert!(dump.contains("version: 3"));
+    }
+}
@@ -15,7 +15,9 @@
 // limitations under the License.
 
 use bytes::Bytes;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::wire::{Error, SftpDecoder};
 
 /// Generic reply for an extension.
 ///
@@ -29,12 +31,43 @@ pub struct ExtendedReply {
     pub data: Bytes,
 }
 
+impl ExtendedReply {
+    /// Decode the extension-specific data of the reply.
+    ///
+    /// The data is decoded with the SFTP wire format.
+    /// Any data remaining after the decoded value is ignored.
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        T::deserialize(&mut SftpDecoder::new(&self.data))
+    }
+
+    /// Decode the data of the reply as a single `u64`.
+    pub fn decode_u64(&self) -> Result<u64, Error> {
+        self.decode()
+    }
+
+    /// Decode the data of the reply as a single string.
+    pub fn decode_string(&self) -> Result<Bytes, Error> {
+        self.decode()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::message::test_utils::{encode_decode, BYTES_VALID};
+    use crate::message::test_utils::{encode_decode, BYTES_INVALID, BYTES_VALID};
+    use crate::message::{Attrs, Owner};
+    use crate::wire::{Error, SftpEncoder};
 
     use super::ExtendedReply;
     use bytes::Bytes;
+    use serde::Serialize;
+
+    fn encode_reply(value: impl Serialize) -> ExtendedReply {
+        let mut encoder = SftpEncoder::new();
+        value.serialize(&mut encoder).unwrap();
+        ExtendedReply {
+            data: Bytes::from(encoder.to_vec()),
+        }
+    }
 
     #[test]
     fn encode_success() {
@@ -47,4 +80,50 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn decode_u64() {
+        for value in [0u64, 1, 0x0123456789abcdef, u64::MAX] {
+            assert_eq!(encode_reply(value).decode_u64(), Ok(value));
+        }
+
+        // Trailing data is ignored
+        assert_eq!(encode_reply((42u64, 7u32)).decode_u64(), Ok(42));
+
+        for data in [b"" as &[u8], b"\0\0\0\0\0\0\0"] {
+            let reply = ExtendedReply {
+                data: Bytes::from_static(data),
+            };
+            assert_eq!(reply.decode_u64(), Err(Error::NotEnoughData));
+        }
+    }
+
+    #[test]
+    fn decode_string() {
+        for (string, _) in BYTES_VALID {
+            let string = Bytes::from(string);
+            assert_eq!(encode_reply(&string).decode_string(), Ok(string));
+        }
+
+        for (bytes, expected) in BYTES_INVALID {
+            let reply = ExtendedReply {
+                data: Bytes::from_static(bytes),
+            };
+            assert_eq!(reply.decode_string(), Err(expected));
+        }
+    }
+
+    #[test]
+    fn decode_struct() {
+        let attrs = Attrs {
+            size: Some(1234),
+            owner: Some(Owner { uid: 12, gid: 34 }),
+            ..Attrs::new()
+        };
+        assert_eq!(encode_reply(&attrs).decode::<Attrs>(), Ok(attrs));
+        assert_eq!(
+            encode_reply((1u64, 2u64, 3u64)).decode::<(u64, u64, u64)>(),
+            Ok((1, 2, 3))
+        );
+    }
 }
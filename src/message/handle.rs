@@ -18,6 +18,7 @@ use std::{borrow::Borrow, ops::Deref};
 
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 /// Arbitrary string that identifies an open file or directory on the server.
 ///
@@ -25,10 +26,42 @@ use serde::{Deserialize, Serialize};
 /// the client MUST NOT attempt to interpret or modify it in any way.
 /// The length of the handle string MUST NOT exceed 256 data bytes.
 ///
+/// Handles longer than [`Handle::MAX_LENGTH`] are rejected when decoded.
+///
 /// internal: `SSH_FXP_HANDLE`
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "Bytes")]
 pub struct Handle(pub Bytes);
 
+/// Error returned when a handle is longer than [`Handle::MAX_LENGTH`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+#[error("Handle is {0} bytes long, but must not exceed 256 bytes")]
+pub struct HandleTooLong(pub usize);
+
+impl Handle {
+    /// Maximal length of a handle in bytes
+    pub const MAX_LENGTH: usize = 256;
+
+    /// Check that the handle does not exceed [`Handle::MAX_LENGTH`].
+    pub fn validate(&self) -> Result<(), HandleTooLong> {
+        if self.0.len() > Self::MAX_LENGTH {
+            Err(HandleTooLong(self.0.len()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl TryFrom<Bytes> for Handle {
+    type Error = HandleTooLong;
+
+    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
+        let handle = Handle(value);
+        handle.validate()?;
+        Ok(handle)
+    }
+}
+
 impl Deref for Handle {
     type Target = [u8];
 
@@ -52,15 +85,37 @@ impl Borrow<[u8]> for Handle {
 #[cfg(test)]
 mod test {
     use crate::message::test_utils::{encode_decode, fail_decode, BYTES_INVALID, BYTES_VALID};
+    use crate::wire::Error;
 
-    use super::Handle;
+    use super::{Handle, HandleTooLong};
     use bytes::Bytes;
 
     #[test]
     fn encode_success() {
-        for (bytes, encoded) in BYTES_VALID {
+        for (bytes, encoded) in BYTES_VALID
+            .into_iter()
+            .filter(|(bytes, _)| bytes.len() <= Handle::MAX_LENGTH)
+        {
             encode_decode(Handle(Bytes::from(bytes)), encoded);
         }
+
+        let mut encoded = b"\0\0\x01\0".to_vec();
+        encoded.extend([b'h'; 256]);
+        encode_decode(Handle(Bytes::from(vec![b'h'; 256])), &encoded);
+    }
+
+    #[test]
+    fn too_long() {
+        let handle = Handle(Bytes::from(vec![b'h'; 257]));
+        assert_eq!(handle.validate(), Err(HandleTooLong(257)));
+        assert_eq!(Handle::try_from(handle.0.clone()), Err(HandleTooLong(257)));
+
+        let mut encoded = b"\0\0\x01\x01".to_vec();
+        encoded.extend([b'h'; 257]);
+        assert_eq!(
+            fail_decode::<Handle>(&encoded),
+            Error::Custom(HandleTooLong(257).to_string())
+        );
     }
 
     #[test]
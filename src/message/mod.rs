@@ -27,11 +27,12 @@ use bytes::{Buf, BufMut, Bytes};
 use serde::{ser::SerializeTuple, Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::wire::{Error, SftpDecoder, SftpEncoder};
+use crate::wire::{Error, SftpDecoder, SftpEncoder, SftpSizer};
 
 mod attrs;
 mod close;
 mod data;
+mod dump;
 mod extended;
 mod extended_reply;
 mod fsetstat;
@@ -61,14 +62,14 @@ mod write;
 #[cfg(test)]
 mod test_utils;
 
-pub use attrs::{Attrs, Owner, Permisions, Time};
+pub use attrs::{Attrs, FileMetadata, Owner, Permisions, Time, TimeNanos};
 pub use close::Close;
 pub use data::Data;
 pub use extended::Extended;
 pub use extended_reply::ExtendedReply;
 pub use fsetstat::FSetStat;
 pub use fstat::FStat;
-pub use handle::Handle;
+pub use handle::{Handle, HandleTooLong};
 pub use init::Init;
 pub use lstat::LStat;
 pub use mkdir::MkDir;
@@ -97,6 +98,15 @@ macro_rules! messages {
         #[non_exhaustive]
         pub enum Message {
             $($name($name) = $discriminant,)*
+            /// Message whose type is not modelled by this crate
+            ///
+            /// It allows to receive vendor or newer protocol packets without failing the decoding.
+            Unknown {
+                /// Type code of the message
+                kind: u8,
+                /// Raw content of the message, after its id
+                data: Bytes,
+            } = 0,
         }
 
         #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -104,12 +114,15 @@ macro_rules! messages {
         #[non_exhaustive]
         pub enum MessageKind {
             $($name = $discriminant,)*
+            /// Type of a message not modelled by this crate (see [`Message::Unknown`])
+            Unknown = 0,
         }
 
         impl Message {
             pub fn kind(&self) -> MessageKind {
                 match self {
                     $(Self::$name(_) => MessageKind::$name,)*
+                    Self::Unknown { .. } => MessageKind::Unknown,
                 }
             }
         }
@@ -118,6 +131,7 @@ macro_rules! messages {
             pub fn code(&self) -> u8 {
                 match self {
                     $(Self::$name => $discriminant,)*
+                    Self::Unknown => 0,
                 }
             }
         }
@@ -149,6 +163,7 @@ macro_rules! messages {
 
                 match self {
                     $(Message::$name(value) => state.serialize_element(value)?,)*
+                    Message::Unknown { data, .. } => state.serialize_element(&RawData { data: data.clone() })?,
                 }
                 state.end()
             }
@@ -176,7 +191,10 @@ macro_rules! messages {
                         let code = seq.next_element::<u8>()?.ok_or_else(no_value)?;
                         let content = match code {
                             $($discriminant => seq.next_element::<$name>()?.ok_or_else(no_value)?.into(),)*
-                            _ => return Err(::serde::de::Error::custom("invalid message type")),
+                            kind => Message::Unknown {
+                                kind,
+                                data: seq.next_element::<RawData>()?.ok_or_else(no_value)?.data,
+                            },
                         };
                         Ok(content)
                     }
@@ -202,6 +220,7 @@ macro_rules! messages {
 
                 match self.message.as_ref() {
                     $(Message::$name(value) => state.serialize_element(&value)?,)*
+                    Message::Unknown { data, .. } => state.serialize_element(&RawData { data: data.clone() })?,
                 }
                 state.end()
             }
@@ -238,7 +257,10 @@ macro_rules! messages {
                             let id = seq.next_element()?.ok_or_else(no_value)?;
                             let message = match code {
                                 $($discriminant => seq.next_element::<$name>()?.ok_or_else(no_value)?.into(),)*
-                                _ => return Err(::serde::de::Error::custom("invalid message type")),
+                                kind => Message::Unknown {
+                                    kind,
+                                    data: seq.next_element::<RawData>()?.ok_or_else(no_value)?.data,
+                                },
                             };
                             (id, message)
                         };
@@ -328,12 +350,39 @@ pub struct MessageWithId<'a> {
     message: Cow<'a, Message>,
 }
 
+/// Raw content of a [`Message::Unknown`], without length prefix
+#[derive(Serialize, Deserialize)]
+struct RawData {
+    #[serde(rename = "data_implicit_length")]
+    data: Bytes,
+}
+
 impl Message {
     pub fn code(&self) -> u8 {
-        self.kind().code()
+        match self {
+            Message::Unknown { kind, .. } => *kind,
+            _ => self.kind().code(),
+        }
+    }
+
+    /// Get the length of the frame returned by [`Message::encode`], including its length prefix.
+    ///
+    /// It allows to allocate the buffers of the encoded frames once.
+    /// If the message cannot be encoded, the length is only a lower bound.
+    pub fn encoded_len(&self) -> usize {
+        let mut sizer = SftpSizer::new();
+        // The id does not change the length of the frame
+        _ = MessageWithId {
+            id: 0,
+            message: Cow::Borrowed(self),
+        }
+        .serialize(&mut sizer);
+
+        std::mem::size_of::<u32>() + sizer.len()
     }
+
     pub fn encode(&self, id: u32) -> Result<Bytes, Error> {
-        let mut encoder = SftpEncoder::with_vec(Vec::with_capacity(16));
+        let mut encoder = SftpEncoder::with_vec(Vec::with_capacity(self.encoded_len()));
 
         // Reserve space for frame length
         encoder.buf.put_u32(0);
@@ -353,10 +402,16 @@ impl Message {
     }
 
     pub fn decode(mut buf: &[u8]) -> Result<(u32, Self), DecodeError> {
+        if buf.remaining() < std::mem::size_of::<u32>() {
+            return Err(crate::wire::Error::NotEnoughData.into());
+        }
         let frame_length = buf.get_u32() as usize;
 
         // Limit the read to this very frame
-        Message::decode_raw(&buf[0..frame_length])
+        match buf.get(0..frame_length) {
+            Some(frame) => Message::decode_raw(frame),
+            None => Err(crate::wire::Error::NotEnoughData.into()),
+        }
     }
 
     pub fn decode_raw(mut buf: &[u8]) -> Result<(u32, Self), DecodeError> {
@@ -568,3 +623,120 @@ SSH_FXP_EXTENDED: 200
 SSH_FXP_EXTENDED_REPLY: 201
 | u32: id | u8[frame length - 5]: data |
  */
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::test_utils::ATTRS_VALID;
+    use super::*;
+
+    #[test]
+    fn unknown_message() {
+        for (kind, data) in [
+            (0u8, b"" as &[u8]),
+            (42, b"some data"),
+            (210, b"\0\0\0\x04data"),
+        ] {
+            let message = Message::Unknown {
+                kind,
+                data: Bytes::from_static(data),
+            };
+            assert_eq!(message.code(), kind);
+            assert_eq!(message.kind(), MessageKind::Unknown);
+
+            let encoded = message.encode(7).unwrap();
+            let mut expected = Vec::new();
+            expected.extend((5 + data.len() as u32).to_be_bytes());
+            expected.push(kind);
+            expected.extend(7u32.to_be_bytes());
+            expected.extend(data);
+            assert_eq!(encoded.as_ref(), expected.as_slice());
+
+            let (id, decoded) = Message::decode(&encoded).unwrap();
+            assert_eq!(id, 7);
+            assert_eq!(decoded, message);
+        }
+    }
+
+    #[test]
+    fn decode_truncated_frame() {
+        let encoded = Message::Close(Default::default()).encode(7).unwrap();
+
+        for len in 0..encoded.len() {
+            let err = Message::decode(&encoded[..len]).unwrap_err();
+            assert_eq!(err.inner, crate::wire::Error::NotEnoughData);
+        }
+    }
+
+    #[test]
+    fn encoded_len() {
+        let handle = Handle(Bytes::from_static(b"handle"));
+        let path = Path::from("some/path");
+        let mut messages = vec![
+            Message::Init(Init {
+                version: 3,
+                extensions: [(Bytes::from_static(b"ext"), Bytes::from_static(b"1"))].into(),
+            }),
+            Message::Open(Open {
+                filename: path.clone(),
+                pflags: PFlags::READ,
+                attrs: Attrs::default(),
+            }),
+            Message::Write(Write {
+                handle: handle.clone(),
+                offset: 42,
+                data: Data::from(vec![0u8; 1000]),
+            }),
+            Message::Rename(Rename {
+                old_path: path.clone(),
+                new_path: Path::from("new"),
+            }),
+            Message::Status(StatusCode::Eof.to_status("End of file")),
+            Message::Name(Name(vec![NameEntry {
+                filename: path,
+                long_name: Bytes::from_static(b"long name"),
+                attrs: ATTRS_VALID[19].0.clone(),
+            }])),
+            Message::Extended(Extended {
+                request: Bytes::from_static(b"limits@openssh.com"),
+                data: Bytes::from_static(b"data"),
+            }),
+            Message::Unknown {
+                kind: 42,
+                data: Bytes::from_static(b"some data"),
+            },
+        ];
+        messages.extend(
+            ATTRS_VALID
+                .into_iter()
+                .map(|(attrs, _)| Message::Attrs(attrs)),
+        );
+        messages.extend([
+            Message::Version(Default::default()),
+            Message::Close(Default::default()),
+            Message::Read(Default::default()),
+            Message::LStat(Default::default()),
+            Message::FStat(FStat { handle }),
+            Message::SetStat(Default::default()),
+            Message::FSetStat(Default::default()),
+            Message::OpenDir(Default::default()),
+            Message::ReadDir(Default::default()),
+            Message::Remove(Default::default()),
+            Message::MkDir(Default::default()),
+            Message::RmDir(Default::default()),
+            Message::RealPath(Default::default()),
+            Message::Stat(Default::default()),
+            Message::ReadLink(Default::default()),
+            Message::Symlink(Default::default()),
+            Message::Handle(Default::default()),
+            Message::Data(Default::default()),
+            Message::ExtendedReply(Default::default()),
+        ]);
+
+        for message in messages {
+            let encoded = message.encode(7).unwrap();
+            assert_eq!(message.encoded_len(), encoded.len(), "{message:?}");
+        }
+    }
+}
@@ -16,6 +16,8 @@
 
 use std::{
     borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    collections::HashSet,
     ops::{Deref, DerefMut, Index, IndexMut},
     slice::SliceIndex,
 };
@@ -29,6 +31,10 @@ use crate::message::{Attrs, Path};
 ///
 /// The data string may be at most the number of bytes requested in a [`Read`](crate::message::Read) request,
 /// but may also be shorter if end of file is reached or if the read is from something other than a regular file.
+///
+/// Entries are ordered by their filename (byte-wise), so a listing can be sorted with [`slice::sort`].
+/// Entries with the same filename are further ordered by their other fields,
+/// which keeps the order consistent with equality.
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct NameEntry {
     /// Path of the file or directory designated by this entry
@@ -56,10 +62,76 @@ pub struct NameEntry {
 /// The data string may be at most the number of bytes requested in a [`Read`](crate::message::Read) request,
 /// but may also be shorter if end of file is reached or if the read is from something other than a regular file.
 ///
+/// Some servers send data after the entries, like the end-of-list flag of later protocol versions.
+/// This trailing data is ignored when decoding, with a warning.
+///
 /// internal: `SSH_FXP_DATA`
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "NameFrame")]
 pub struct Name(pub Vec<NameEntry>);
 
+/// Decoded content of a [`Name`] reply, including the data after the entries
+#[derive(Deserialize)]
+struct NameFrame {
+    entries: Vec<NameEntry>,
+    #[serde(rename = "trailing_implicit_length")]
+    trailing: Bytes,
+}
+
+impl From<NameFrame> for Name {
+    fn from(value: NameFrame) -> Self {
+        if !value.trailing.is_empty() {
+            log::warn!(
+                "Ignoring {} bytes after the entries of a Name reply",
+                value.trailing.len()
+            );
+        }
+        Name(value.entries)
+    }
+}
+
+impl NameEntry {
+    /// Compare two entries by their filename only (byte-wise).
+    pub fn cmp_by_name(&self, other: &Self) -> Ordering {
+        self.filename.as_bytes().cmp(other.filename.as_bytes())
+    }
+
+    /// Key used to break ties between entries with the same filename.
+    fn tie_breaker(&self) -> impl Ord + '_ {
+        let attrs = &self.attrs;
+        (
+            &self.long_name,
+            attrs.size,
+            attrs.owner.map(|owner| (owner.uid, owner.gid)),
+            attrs.perms.map(|perms| perms.bits()),
+            attrs.time.map(|time| (time.atime, time.mtime)),
+        )
+    }
+}
+
+impl PartialOrd for NameEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NameEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_by_name(other)
+            .then_with(|| self.tie_breaker().cmp(&other.tie_breaker()))
+    }
+}
+
+impl Name {
+    /// Remove the entries whose filename has already been seen in a previous entry.
+    ///
+    /// The first entry of each filename is kept, and the order of the kept entries is preserved.
+    pub fn dedup_by_filename(&mut self) {
+        let mut seen = HashSet::with_capacity(self.0.len());
+        self.0.retain(|entry| seen.insert(entry.filename.clone()));
+    }
+}
+
 impl IntoIterator for Name {
     type Item = NameEntry;
 
@@ -149,12 +221,13 @@ impl BorrowMut<[NameEntry]> for Name {
 mod test {
     use crate::message::{
         test_utils::{encode_decode, fail_decode},
-        Attrs, Path,
+        Attrs, Message, Path,
     };
-    use crate::wire::Error;
+    use crate::wire::{Error, SftpDecoder};
 
-    use super::NameEntry;
+    use super::{Name, NameEntry};
     use bytes::Bytes;
+    use std::cmp::Ordering;
 
     const NAME_VALID: &[u8] =
         b"\0\0\0\x08filename\0\0\0\x09long name\0\0\0\x01\0\0\0\0\0\x0a\x77\x35";
@@ -183,4 +256,80 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn trailing_data() {
+        let entry = NameEntry {
+            filename: Path("filename".to_owned()),
+            long_name: Bytes::from_static(b"long name"),
+            attrs: Attrs {
+                size: Some(0xa7735),
+                ..Default::default()
+            },
+        };
+        let name = Name(vec![entry]);
+        encode_decode(name.clone(), &[b"\0\0\0\x01", NAME_VALID].concat());
+
+        // End-of-list flag, and arbitrary data
+        for trailing in [b"\x01" as &[u8], b"\0\0\0\x04junk"] {
+            let encoded = [b"\0\0\0\x01", NAME_VALID, trailing].concat();
+            let decoded = serde::Deserialize::deserialize(&mut SftpDecoder::new(&encoded));
+            assert_eq!(decoded, Ok(name.clone()));
+
+            let mut frame = [b"\x68\0\0\0\x07\0\0\0\x01", NAME_VALID, trailing].concat();
+            frame.splice(0..0, (frame.len() as u32).to_be_bytes());
+            assert_eq!(
+                Message::decode(&frame).unwrap(),
+                (7, Message::Name(name.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn dedup_by_filename() {
+        let entry = |filename: &str, size: u64| NameEntry {
+            filename: Path(filename.to_owned()),
+            long_name: Bytes::new(),
+            attrs: Attrs {
+                size: Some(size),
+                ..Default::default()
+            },
+        };
+
+        let mut name = Name(vec![
+            entry("b", 0),
+            entry("a", 1),
+            entry("b", 2),
+            entry("c", 3),
+            entry("a", 4),
+        ]);
+        name.dedup_by_filename();
+
+        assert_eq!(
+            name,
+            Name(vec![entry("b", 0), entry("a", 1), entry("c", 3)])
+        );
+    }
+
+    #[test]
+    fn ordering() {
+        let entry = |filename: &str, size: u64| NameEntry {
+            filename: Path(filename.to_owned()),
+            long_name: Bytes::new(),
+            attrs: Attrs {
+                size: Some(size),
+                ..Default::default()
+            },
+        };
+
+        let mut entries = vec![entry("b", 0), entry("a", 2), entry("B", 1), entry("a", 1)];
+        entries.sort();
+        assert_eq!(
+            entries,
+            [entry("B", 1), entry("a", 1), entry("a", 2), entry("b", 0)]
+        );
+
+        assert_eq!(entry("a", 1).cmp_by_name(&entry("a", 2)), Ordering::Equal);
+        assert_eq!(entry("a", 1).cmp(&entry("a", 2)), Ordering::Less);
+    }
 }
@@ -60,6 +60,29 @@ impl Borrow<str> for Path {
     }
 }
 
+/*
+ * Comparison
+ */
+macro_rules! impl_partial_eq {
+    ($($other:ty),*) => {
+        $(
+            impl PartialEq<$other> for Path {
+                fn eq(&self, other: &$other) -> bool {
+                    self.0 == *other
+                }
+            }
+
+            impl PartialEq<Path> for $other {
+                fn eq(&self, other: &Path) -> bool {
+                    *self == other.0
+                }
+            }
+        )*
+    };
+}
+
+impl_partial_eq!(str, &str, String);
+
 /*
  * Append
  */
@@ -149,6 +172,21 @@ mod test {
         }
     }
 
+    #[test]
+    fn path_comparison() {
+        let path = Path::from("abc");
+
+        assert_eq!(path, *"abc");
+        assert_eq!(path, "abc");
+        assert_eq!(path, String::from("abc"));
+        assert_eq!(*"abc", path);
+        assert_eq!("abc", path);
+        assert_eq!(String::from("abc"), path);
+
+        assert_ne!(path, "abc/");
+        assert_ne!("", path);
+    }
+
     #[test]
     fn path_concatenation() {
         assert_eq!(Path::from("abc"), Path::from("abc") / &"");
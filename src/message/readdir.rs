@@ -48,7 +48,11 @@ mod test {
 
     #[test]
     fn encode_success() {
-        for (bytes, encoded) in BYTES_VALID {
+        // Handles cannot exceed 256 bytes
+        for (bytes, encoded) in BYTES_VALID
+            .into_iter()
+            .filter(|(bytes, _)| bytes.len() <= Handle::MAX_LENGTH)
+        {
             encode_decode(
                 ReadDir {
                     handle: Handle(Bytes::from(bytes)),
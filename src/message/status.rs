@@ -139,18 +139,31 @@ impl std::fmt::Display for Status {
         if self.error.is_empty() {
             write!(f, "{}", self.code)
         } else {
-            write!(
-                f,
-                "{}: {}",
-                self.code,
-                String::from_utf8_lossy(self.error.as_ref())
-            )
+            write!(f, "{}: {}", self.code, self.error)
         }
     }
 }
 
 impl std::error::Error for Status {}
 
+impl From<Status> for std::io::Error {
+    fn from(value: Status) -> Self {
+        let kind = match value.code {
+            StatusCode::Ok => std::io::ErrorKind::Other,
+            StatusCode::Eof => std::io::ErrorKind::UnexpectedEof,
+            StatusCode::NoSuchFile => std::io::ErrorKind::NotFound,
+            StatusCode::PermissionDenied => std::io::ErrorKind::PermissionDenied,
+            StatusCode::Failure => std::io::ErrorKind::Other,
+            StatusCode::BadMessage => std::io::ErrorKind::InvalidData,
+            StatusCode::NoConnection => std::io::ErrorKind::NotConnected,
+            StatusCode::ConnectionLost => std::io::ErrorKind::ConnectionReset,
+            StatusCode::OpUnsupported => std::io::ErrorKind::Unsupported,
+        };
+
+        Self::new(kind, value)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -188,6 +201,31 @@ mod test {
         );
     }
 
+    #[test]
+    fn io_error_kind() {
+        use std::io::ErrorKind;
+
+        for (code, kind) in [
+            (StatusCode::Ok, ErrorKind::Other),
+            (StatusCode::Eof, ErrorKind::UnexpectedEof),
+            (StatusCode::NoSuchFile, ErrorKind::NotFound),
+            (StatusCode::PermissionDenied, ErrorKind::PermissionDenied),
+            (StatusCode::Failure, ErrorKind::Other),
+            (StatusCode::BadMessage, ErrorKind::InvalidData),
+            (StatusCode::NoConnection, ErrorKind::NotConnected),
+            (StatusCode::ConnectionLost, ErrorKind::ConnectionReset),
+            (StatusCode::OpUnsupported, ErrorKind::Unsupported),
+        ] {
+            let status = code.to_status("message");
+            let io = std::io::Error::from(status.clone());
+            assert_eq!(io.kind(), kind);
+            assert_eq!(io.to_string(), status.to_string());
+
+            let io = std::io::Error::from(crate::client::Error::Sftp(status));
+            assert_eq!(io.kind(), kind);
+        }
+    }
+
     #[test]
     fn decode_failure() {
         for i in 0..STATUS_VALID.len() {
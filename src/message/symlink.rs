@@ -22,7 +22,10 @@ use crate::message::Path;
 ///
 /// It is answered with [`Status`](crate::message::Status).
 ///
-/// internal: `SSH_FXP_RENAME`
+/// On the wire, the link path is sent before the target path,
+/// which is the reverse order of POSIX `ln -s target link`.
+///
+/// internal: `SSH_FXP_SYMLINK`
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Symlink {
     /// Path name of the symbolic link to be created
@@ -119,7 +119,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
     // time
     (
         Attrs {
-            time: Some(Time { atime: 0, mtime: 0 }),
+            time: Some(Time { atime: 0, mtime: 0, nanos: None }),
             ..Attrs::new()
         },
         b"\0\0\0\x08\0\0\0\0\0\0\0\0",
@@ -129,6 +129,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -162,6 +163,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -189,6 +191,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -201,6 +204,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -230,6 +234,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -243,6 +248,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -259,6 +265,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
             ..Attrs::new()
         },
@@ -276,6 +283,7 @@ pub(crate) const ATTRS_VALID: [(Attrs, &[u8]); 20] = [
             time: Some(Time {
                 atime: 0xfdb97531,
                 mtime: 0xeca86420,
+                nanos: None,
             }),
         },
         b"\0\0\0\x0f\xfe\xdc\xba\x98\x76\x54\x32\x10\xf7\xe6\xd5\xc4\xb3\xa2\x91\x80\0\0\x76\x32\xfd\xb9\x75\x31\xec\xa8\x64\x20",
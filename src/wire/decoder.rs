@@ -26,6 +26,7 @@ use crate::wire::Error;
 pub struct SftpDecoder<'de> {
     pub(crate) buf: &'de [u8],
     current_field: &'static str,
+    version: u32,
 }
 
 macro_rules! decode {
@@ -56,8 +57,18 @@ impl<'de> SftpDecoder<'de> {
         Self {
             buf,
             current_field: "",
+            version: 0,
         }
     }
+
+    /// Decode using the layout of the given SFTP version.
+    ///
+    /// Optional fields suffixed with `_v4` are decoded as `None` before version 4,
+    /// and are expected to be present from version 4.
+    pub fn with_version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
     fn decode_length(&self) -> bool {
         !self.current_field.ends_with("_implicit_length")
     }
@@ -89,7 +100,7 @@ impl<'de> SftpDecoder<'de> {
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut SftpDecoder<'de> {
+impl<'de> de::Deserializer<'de> for &mut SftpDecoder<'de> {
     type Error = Error;
 
     deserialize!(deserialize_any);
@@ -109,7 +120,16 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut SftpDecoder<'de> {
     deserialize!(deserialize_string, visit_borrowed_str, decode_str, _);
     deserialize!(deserialize_bytes, visit_borrowed_bytes, decode_bytes, _);
     deserialize!(deserialize_byte_buf, visit_borrowed_bytes, decode_bytes, _);
-    deserialize!(deserialize_option);
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if !self.current_field.ends_with("_v4") {
+            Err(Error::Unsupported)
+        } else if self.version < 4 {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
     deserialize!(deserialize_identifier);
     deserialize!(deserialize_ignored_any);
 
@@ -310,7 +330,7 @@ impl<'a, 'de> de::EnumAccess<'de> for SftpDecoderEnum<'a, 'de> {
         Ok((seed.deserialize(variant.into_deserializer())?, self.decoder))
     }
 }
-impl<'a, 'de> de::VariantAccess<'de> for &'a mut SftpDecoder<'de> {
+impl<'de> de::VariantAccess<'de> for &mut SftpDecoder<'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<(), Self::Error> {
@@ -24,6 +24,7 @@ use crate::wire::Error;
 pub struct SftpEncoder {
     pub(crate) buf: Vec<u8>,
     current_field: &'static str,
+    version: u32,
 }
 
 impl SftpEncoder {
@@ -37,9 +38,17 @@ impl SftpEncoder {
         Self {
             buf,
             current_field: "",
+            version: 0,
         }
     }
 
+    /// Encode using the layout of the given SFTP version.
+    ///
+    /// Fields suffixed with `_v4` are only encoded from version 4.
+    pub fn with_version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
     /// Get the encoded buffer from the SFTP encoder
     pub fn to_vec(self) -> Vec<u8> {
         self.buf
@@ -48,6 +57,10 @@ impl SftpEncoder {
     fn encode_length(&self) -> bool {
         !self.current_field.ends_with("_implicit_length")
     }
+
+    fn skip_field(&self) -> bool {
+        self.version < 4 && self.current_field.ends_with("_v4")
+    }
 }
 
 macro_rules! serialize {
@@ -70,7 +83,12 @@ macro_rules! serialize {
             where
                 T: serde::Serialize + ?Sized,
             {
-                $(self.current_field = $key;)?
+                $(
+                    self.current_field = $key;
+                    if SftpEncoder::skip_field(self) {
+                        return Ok(());
+                    }
+                )?
                 value.serialize(&mut **self)
             }
 
@@ -81,7 +99,7 @@ macro_rules! serialize {
     };
 }
 
-impl<'a> ser::Serializer for &'a mut SftpEncoder {
+impl ser::Serializer for &mut SftpEncoder {
     type Ok = ();
     type Error = Error;
 
@@ -274,7 +292,7 @@ serialize!(trait SerializeTupleVariant: serialize_field);
 serialize!(trait SerializeStruct: serialize_field key);
 serialize!(trait SerializeStructVariant: serialize_field key);
 
-impl<'a> ser::SerializeMap for &'a mut SftpEncoder {
+impl ser::SerializeMap for &mut SftpEncoder {
     type Ok = ();
     type Error = Error;
 
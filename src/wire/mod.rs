@@ -19,7 +19,74 @@
 mod decoder;
 mod encoder;
 mod error;
+mod size;
 
 pub use decoder::SftpDecoder;
 pub use encoder::SftpEncoder;
 pub use error::Error;
+pub use size::SftpSizer;
+
+#[cfg(test)]
+mod test {
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use std::fmt::Debug;
+
+    use super::{SftpDecoder, SftpEncoder};
+
+    fn encode_decode<T>(value: T, expected: &[u8])
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let mut encoder = SftpEncoder::new();
+        value.serialize(&mut encoder).unwrap();
+        assert_eq!(encoder.to_vec(), expected);
+
+        let decoded = T::deserialize(&mut SftpDecoder::new(expected)).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn signed() {
+        encode_decode(0i8, b"\0");
+        encode_decode(-1i8, b"\xff");
+        encode_decode(i16::MIN, b"\x80\0");
+        encode_decode(-2i32, b"\xff\xff\xff\xfe");
+        encode_decode(i32::MAX, b"\x7f\xff\xff\xff");
+        encode_decode(-1i64, b"\xff\xff\xff\xff\xff\xff\xff\xff");
+        encode_decode(i64::MIN, b"\x80\0\0\0\0\0\0\0");
+        encode_decode(i64::MAX, b"\x7f\xff\xff\xff\xff\xff\xff\xff");
+    }
+
+    #[test]
+    fn signed_time() {
+        // Times before the epoch, or after 2106, as encoded by SFTP version 4 and later
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Time {
+            seconds: i64,
+            nseconds: u32,
+        }
+
+        encode_decode(
+            Time {
+                seconds: -1,
+                nseconds: 999_999_999,
+            },
+            b"\xff\xff\xff\xff\xff\xff\xff\xff\x3b\x9a\xc9\xff",
+        );
+        encode_decode(
+            Time {
+                seconds: 1 << 33,
+                nseconds: 0,
+            },
+            b"\0\0\0\x02\0\0\0\0\0\0\0\0",
+        );
+    }
+
+    #[test]
+    fn signed_not_enough_data() {
+        assert_eq!(
+            i64::deserialize(&mut SftpDecoder::new(b"\xff\xff\xff\xff")),
+            Err(super::Error::NotEnoughData)
+        );
+    }
+}
@@ -0,0 +1,305 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::ser;
+
+use crate::wire::Error;
+
+/// Serde serializer computing the size of a value in the SFTP wire format.
+///
+/// It follows the same rules as [`SftpEncoder`](crate::wire::SftpEncoder), without writing anything.
+#[derive(Debug, Default)]
+pub struct SftpSizer {
+    len: usize,
+    current_field: &'static str,
+    version: u32,
+}
+
+impl SftpSizer {
+    /// Create a new SFTP sizer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute the size using the layout of the given SFTP version.
+    ///
+    /// Fields suffixed with `_v4` are only counted from version 4.
+    pub fn with_version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
+    /// Get the number of bytes the serialized values would take once encoded.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the serialized values would take no byte once encoded.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn encode_length(&self) -> bool {
+        !self.current_field.ends_with("_implicit_length")
+    }
+
+    fn skip_field(&self) -> bool {
+        self.version < 4 && self.current_field.ends_with("_v4")
+    }
+
+    fn add_length(&mut self) {
+        if self.encode_length() {
+            self.len += std::mem::size_of::<u32>();
+        }
+    }
+}
+
+macro_rules! size {
+    ($serialize:ident, $ty:ty) => {
+        fn $serialize(self, _v: $ty) -> Result<Self::Ok, Self::Error> {
+            self.len += std::mem::size_of::<$ty>();
+            Ok(())
+        }
+    };
+    (trait $trait:ident: $serialize:ident $($key:ident)?) => {
+        impl<'a> ser::$trait for &'a mut SftpSizer {
+            type Ok = ();
+            type Error = Error;
+
+            fn $serialize<T>(&mut self, $($key: &'static str,)? value: &T) -> Result<(), Self::Error>
+            where
+                T: serde::Serialize + ?Sized,
+            {
+                $(
+                    self.current_field = $key;
+                    if SftpSizer::skip_field(self) {
+                        return Ok(());
+                    }
+                )?
+                value.serialize(&mut **self)
+            }
+
+            fn end(self) -> Result<Self::Ok, Self::Error> {
+                Ok(())
+            }
+        }
+    };
+}
+
+impl ser::Serializer for &mut SftpSizer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    size!(serialize_i8, i8);
+    size!(serialize_i16, i16);
+    size!(serialize_i32, i32);
+    size!(serialize_i64, i64);
+    size!(serialize_i128, i128);
+    size!(serialize_u8, u8);
+    size!(serialize_u16, u16);
+    size!(serialize_u32, u32);
+    size!(serialize_u64, u64);
+    size!(serialize_u128, u128);
+    size!(serialize_f32, f32);
+    size!(serialize_f64, f64);
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u8(v as u8)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        if u32::try_from(v.len()).is_err() {
+            return Err(Error::Unsupported);
+        }
+
+        self.add_length();
+        self.len += v.len();
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.current_field = name;
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.current_field = variant;
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        if let Some(len) = len {
+            if u32::try_from(len).is_err() {
+                return Err(Error::Unsupported);
+            }
+            self.add_length();
+        }
+        self.current_field = "";
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.current_field = "";
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.current_field = name;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.current_field = variant;
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        if let Some(len) = len {
+            if u32::try_from(len).is_err() {
+                return Err(Error::Unsupported);
+            }
+            self.add_length();
+        }
+        self.current_field = "";
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.current_field = name;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.current_field = variant;
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+size!(trait SerializeSeq: serialize_element);
+size!(trait SerializeTuple: serialize_element);
+size!(trait SerializeTupleStruct: serialize_field);
+size!(trait SerializeTupleVariant: serialize_field);
+size!(trait SerializeStruct: serialize_field key);
+size!(trait SerializeStructVariant: serialize_field key);
+
+impl ser::SerializeMap for &mut SftpSizer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,558 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory SFTP server shared by the tests.
+
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use rusftp::client::{Limits, SftpClient, SftpClientConfig};
+use rusftp::message::{
+    Attrs, Data, ExtendedReply, Handle, Message, Name, NameEntry, PFlags, Path, Permisions,
+    StatusCode, Version,
+};
+
+/// Minimal SFTP server storing its files in memory.
+///
+/// It supports opening, reading, writing, renaming, removing and listing files,
+/// getting or setting their attributes, and creating or reading symbolic links.
+/// Directories are implicit: a directory exists if it has been created, or if it contains a file or a link.
+/// Each top-level directory behaves like a separate filesystem: renames across them fail.
+///
+/// Like OpenSSH, it rejects the reads and the writes on files that have not been opened with `READ` or `WRITE`.
+/// When it advertises `limits@openssh.com`, it also swaps the paths of `symlink` requests like OpenSSH.
+pub struct MockServer {
+    files: HashMap<String, Bytes>,
+    /// Permissions, owner and times set on the files
+    attrs: HashMap<String, Attrs>,
+    /// Symbolic links and their targets
+    links: HashMap<String, String>,
+    /// Directories created explicitly
+    dirs: HashSet<String>,
+    /// Listings replacing the actual content of some directories
+    listings: HashMap<String, Vec<(String, Attrs)>>,
+    handles: HashMap<Bytes, Opened>,
+    opened: usize,
+    reads: Arc<AtomicUsize>,
+    limits: Option<Limits>,
+}
+
+/// Maximal size of a file: writes beyond it fail, like on a full disk
+pub const MAX_FILE_SIZE: usize = 4 << 20;
+
+/// Target of a handle
+enum Opened {
+    File(String, PFlags),
+    /// Directory, and whether its entries have already been listed
+    Dir(String, bool),
+}
+
+impl MockServer {
+    /// Start a server with the given files, and connect a client to it.
+    ///
+    /// Returns the client, and the counter of `Read` requests received by the server.
+    pub async fn start(files: &[(&str, &[u8])]) -> (SftpClient, Arc<AtomicUsize>) {
+        Self::start_with_limits(files, None).await
+    }
+
+    /// Start a server with the given files, advertising `limits@openssh.com` if `limits` is set.
+    ///
+    /// The client uses the limits of the server.
+    pub async fn start_with_limits(
+        files: &[(&str, &[u8])],
+        limits: Option<Limits>,
+    ) -> (SftpClient, Arc<AtomicUsize>) {
+        let (client, reads) = Self::spawn(files, limits);
+        let config = SftpClientConfig {
+            use_server_limits: true,
+            ..Default::default()
+        };
+        (
+            SftpClient::with_stream_config(client, config)
+                .await
+                .unwrap(),
+            reads,
+        )
+    }
+
+    /// Start a server with the given files, whose directories in `listings` are listed with the given entries,
+    /// whatever their actual content, like a hostile server.
+    pub async fn start_with_listings(
+        files: &[(&str, &[u8])],
+        listings: HashMap<String, Vec<(String, Attrs)>>,
+    ) -> (SftpClient, Arc<AtomicUsize>) {
+        let (client, reads) = Self::spawn_with_listings(files, None, listings);
+        (SftpClient::with_stream(client).await.unwrap(), reads)
+    }
+
+    /// Start a server with the given files, and return the stream to connect a client to it.
+    pub fn spawn(
+        files: &[(&str, &[u8])],
+        limits: Option<Limits>,
+    ) -> (DuplexStream, Arc<AtomicUsize>) {
+        Self::spawn_with_listings(files, limits, HashMap::new())
+    }
+
+    fn spawn_with_listings(
+        files: &[(&str, &[u8])],
+        limits: Option<Limits>,
+        listings: HashMap<String, Vec<(String, Attrs)>>,
+    ) -> (DuplexStream, Arc<AtomicUsize>) {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let reads = Arc::new(AtomicUsize::new(0));
+
+        let server_state = MockServer {
+            files: files
+                .iter()
+                .map(|(path, content)| (path.to_string(), Bytes::copy_from_slice(content)))
+                .collect(),
+            attrs: HashMap::new(),
+            links: HashMap::new(),
+            dirs: HashSet::new(),
+            listings,
+            handles: HashMap::new(),
+            opened: 0,
+            reads: reads.clone(),
+            limits,
+        };
+        tokio::spawn(server_state.run(server));
+
+        (client, reads)
+    }
+
+    async fn run(mut self, mut stream: DuplexStream) {
+        loop {
+            let Ok(length) = stream.read_u32().await else {
+                return;
+            };
+            let mut frame = vec![0u8; length as usize];
+            stream.read_exact(&mut frame).await.unwrap();
+
+            let (id, request) = Message::decode_raw(&frame).unwrap();
+            let reply = self.reply(request);
+            stream.write_all(&reply.encode(id).unwrap()).await.unwrap();
+        }
+    }
+
+    fn reply(&mut self, request: Message) -> Message {
+        match request {
+            Message::Init(_) => Message::Version(Version {
+                version: 3,
+                extensions: self
+                    .limits
+                    .iter()
+                    .map(|_| {
+                        (
+                            Bytes::from_static(b"limits@openssh.com"),
+                            Bytes::from_static(b"1"),
+                        )
+                    })
+                    .collect(),
+            }),
+            Message::Extended(extended) if &*extended.request == b"limits@openssh.com" => {
+                match self.limits {
+                    Some(limits) => {
+                        let mut data = BytesMut::new();
+                        data.put_u64(limits.max_packet_length);
+                        data.put_u64(limits.max_read_length);
+                        data.put_u64(limits.max_write_length);
+                        data.put_u64(limits.max_open_handles);
+                        Message::ExtendedReply(ExtendedReply {
+                            data: data.freeze(),
+                        })
+                    }
+                    None => StatusCode::OpUnsupported.to_status("Unsupported").into(),
+                }
+            }
+            Message::Extended(extended) if &*extended.request == b"expand-path@openssh.com" => {
+                let path = String::from_utf8_lossy(&extended.data[4..]).into_owned();
+                let expanded = match path.strip_prefix('~') {
+                    Some(rest) => format!("/home/user{rest}"),
+                    None => path,
+                };
+                Message::Name(Name(vec![NameEntry {
+                    long_name: Bytes::from(expanded.clone()),
+                    filename: Path::from(expanded),
+                    attrs: Attrs::default(),
+                }]))
+            }
+            Message::Extended(extended) if &*extended.request == b"statvfs@openssh.com" => {
+                let path = String::from_utf8_lossy(&extended.data[4..]).into_owned();
+                if !self.files.contains_key(&path) && !self.is_dir(&path) {
+                    return StatusCode::NoSuchFile.to_status("No such file").into();
+                }
+                let mut data = BytesMut::new();
+                // bsize, frsize, blocks, bfree, bavail, files, ffree, favail, fsid, flag, namemax
+                for value in [4096, 1024, 1000, 600, 500, 100, 50, 40, 1, 0, 255] {
+                    data.put_u64(value);
+                }
+                Message::ExtendedReply(ExtendedReply {
+                    data: data.freeze(),
+                })
+            }
+            Message::Open(open) => {
+                let path = open.filename.to_string();
+                if self.is_dir(&path) {
+                    return StatusCode::Failure.to_status("Is a directory").into();
+                }
+                let exists = self.files.contains_key(&path);
+                if exists && open.pflags.contains(PFlags::CREATE | PFlags::EXCLUDE) {
+                    return StatusCode::Failure.to_status("File exists").into();
+                }
+                if !exists && !open.pflags.contains(PFlags::CREATE) {
+                    return StatusCode::NoSuchFile.to_status("No such file").into();
+                }
+                if !exists || open.pflags.contains(PFlags::TRUNCATE) {
+                    self.files.insert(path.clone(), Bytes::new());
+                }
+                self.open(Opened::File(path, open.pflags))
+            }
+            Message::OpenDir(opendir) => {
+                let path = opendir.path.to_string();
+                if !self.is_dir(&path) {
+                    return StatusCode::NoSuchFile.to_status("No such directory").into();
+                }
+                self.open(Opened::Dir(path, false))
+            }
+            Message::ReadDir(readdir) => match self.handles.get_mut(&readdir.handle.0) {
+                Some(Opened::Dir(_, true)) => StatusCode::Eof.to_status("End of directory").into(),
+                Some(Opened::Dir(path, listed)) => {
+                    *listed = true;
+                    let path = path.clone();
+                    let mut entries = vec![self.dir_entry("."), self.dir_entry("..")];
+                    let listing = match self.listings.get(&path) {
+                        Some(listing) => listing.clone(),
+                        None => self.list(&path),
+                    };
+                    entries.extend(listing.into_iter().map(|(name, attrs)| NameEntry {
+                        long_name: Bytes::from(name.clone()),
+                        filename: Path::from(name),
+                        attrs,
+                    }));
+                    Message::Name(Name(entries))
+                }
+                _ => StatusCode::Failure.to_status("Invalid handle").into(),
+            },
+            Message::Stat(stat) => match self.links.get(&*stat.path) {
+                Some(target) => self.stat(&target.clone()),
+                None => self.stat(&stat.path),
+            },
+            Message::LStat(lstat) => self.stat(&lstat.path),
+            Message::MkDir(mkdir) => {
+                let path = mkdir.path.to_string();
+                if self.files.contains_key(&path)
+                    || self.links.contains_key(&path)
+                    || self.is_dir(&path)
+                {
+                    return StatusCode::Failure.to_status("File exists").into();
+                }
+                match path.rsplit_once('/') {
+                    Some((parent, _)) if !parent.is_empty() && !self.is_dir(parent) => {
+                        StatusCode::NoSuchFile.to_status("No such directory").into()
+                    }
+                    _ => {
+                        self.dirs.insert(path);
+                        StatusCode::Ok.to_status("").into()
+                    }
+                }
+            }
+            Message::Symlink(symlink) => {
+                // Like OpenSSH, read the paths in the reverse order when advertising its extensions
+                let (link, target) = if self.limits.is_some() {
+                    (symlink.target_path, symlink.link_path)
+                } else {
+                    (symlink.link_path, symlink.target_path)
+                };
+                let link = link.to_string();
+                if self.files.contains_key(&link) || self.links.contains_key(&link) {
+                    return StatusCode::Failure.to_status("File exists").into();
+                }
+                self.links.insert(link, target.to_string());
+                StatusCode::Ok.to_status("").into()
+            }
+            Message::ReadLink(readlink) => match self.links.get(&*readlink.path) {
+                Some(target) => Message::Name(Name(vec![NameEntry {
+                    filename: Path::from(target.as_str()),
+                    long_name: Bytes::from(target.clone()),
+                    attrs: Attrs::default(),
+                }])),
+                None => StatusCode::NoSuchFile.to_status("Not a link").into(),
+            },
+            Message::RealPath(realpath) => {
+                let path = format!("/{}", realpath.path.trim_matches('/'));
+                Message::Name(Name(vec![NameEntry {
+                    filename: Path::from(path.as_str()),
+                    long_name: Bytes::from(path),
+                    attrs: Attrs::default(),
+                }]))
+            }
+            Message::FStat(fstat) => match self.handles.get(&fstat.handle.0) {
+                Some(Opened::File(path, _)) => self.stat(&path.clone()),
+                _ => StatusCode::Failure.to_status("Invalid handle").into(),
+            },
+            Message::SetStat(setstat) => self.setstat(&setstat.path, setstat.attrs),
+            Message::FSetStat(fsetstat) => match self.handles.get(&fsetstat.handle.0) {
+                Some(Opened::File(path, _)) => self.setstat(&path.clone(), fsetstat.attrs),
+                _ => StatusCode::Failure.to_status("Invalid handle").into(),
+            },
+            Message::Read(read) => {
+                self.reads.fetch_add(1, Ordering::SeqCst);
+                if let Some(limits) = self.limits {
+                    assert!(read.length as u64 <= limits.max_read_length);
+                }
+                if !self.allows(&read.handle, PFlags::READ) {
+                    return StatusCode::PermissionDenied
+                        .to_status("Not opened for reading")
+                        .into();
+                }
+                match self.content(&read.handle) {
+                    Some(content) if read.offset >= content.len() as u64 => {
+                        StatusCode::Eof.to_status("End of file").into()
+                    }
+                    Some(content) => {
+                        let start = read.offset as usize;
+                        let end = content.len().min(start + read.length as usize);
+                        Message::Data(Data(content.slice(start..end)))
+                    }
+                    None => StatusCode::Failure.to_status("Invalid handle").into(),
+                }
+            }
+            Message::Write(write) => {
+                if let Some(limits) = self.limits {
+                    assert!(write.data.len() as u64 <= limits.max_write_length);
+                }
+                if !self.allows(&write.handle, PFlags::WRITE) {
+                    return StatusCode::PermissionDenied
+                        .to_status("Not opened for writing")
+                        .into();
+                }
+                let Some(Opened::File(path, _)) = self.handles.get(&write.handle.0) else {
+                    return StatusCode::Failure.to_status("Invalid handle").into();
+                };
+                let Some(content) = self.files.get_mut(path) else {
+                    return StatusCode::NoSuchFile.to_status("No such file").into();
+                };
+
+                let start = write.offset as usize;
+                let end = start + write.data.len();
+                if end > MAX_FILE_SIZE {
+                    return StatusCode::Failure.to_status("No space left").into();
+                }
+                let mut new_content = content.to_vec();
+                if new_content.len() < end {
+                    new_content.resize(end, 0);
+                }
+                new_content[start..end].copy_from_slice(&write.data);
+                *content = Bytes::from(new_content);
+
+                StatusCode::Ok.to_status("").into()
+            }
+            Message::Remove(remove) => match self
+                .files
+                .remove(&*remove.path)
+                .or_else(|| self.links.remove(&*remove.path).map(Bytes::from))
+            {
+                Some(_) => {
+                    self.attrs.remove(&*remove.path);
+                    StatusCode::Ok.to_status("").into()
+                }
+                None => StatusCode::NoSuchFile.to_status("No such file").into(),
+            },
+            Message::Rename(rename) => {
+                let (old, new) = (rename.old_path.to_string(), rename.new_path.to_string());
+                if !self.files.contains_key(&old) && self.list(&old).is_empty() {
+                    return StatusCode::NoSuchFile.to_status("No such file").into();
+                }
+                if self.files.contains_key(&new) || !self.list(&new).is_empty() {
+                    return StatusCode::Failure.to_status("File exists").into();
+                }
+                // Each top-level directory is a separate filesystem
+                if old.split('/').next() != new.split('/').next() {
+                    return StatusCode::Failure.to_status("Cross-device link").into();
+                }
+
+                let dir_prefix = format!("{old}/");
+                let moved = self
+                    .files
+                    .keys()
+                    .filter(|file| **file == old || file.starts_with(&dir_prefix))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for file in moved {
+                    let renamed = format!("{new}{}", &file[old.len()..]);
+                    let content = self.files.remove(&file).unwrap_or_default();
+                    if let Some(attrs) = self.attrs.remove(&file) {
+                        self.attrs.insert(renamed.clone(), attrs);
+                    }
+                    self.files.insert(renamed, content);
+                }
+                StatusCode::Ok.to_status("").into()
+            }
+            Message::Close(close) => match self.handles.remove(&close.handle.0) {
+                Some(_) => StatusCode::Ok.to_status("").into(),
+                None => StatusCode::Failure.to_status("Invalid handle").into(),
+            },
+            _ => StatusCode::OpUnsupported.to_status("Unsupported").into(),
+        }
+    }
+
+    fn open(&mut self, opened: Opened) -> Message {
+        self.opened += 1;
+        let handle = Bytes::from(self.opened.to_string());
+        self.handles.insert(handle.clone(), opened);
+        Message::Handle(Handle(handle))
+    }
+
+    fn stat(&self, path: &str) -> Message {
+        if let Some(content) = self.files.get(path) {
+            Message::Attrs(Attrs {
+                size: Some(content.len() as u64),
+                ..self.attrs.get(path).cloned().unwrap_or_default()
+            })
+        } else if self.links.contains_key(path) {
+            Message::Attrs(link_attrs())
+        } else if self.is_dir(path) {
+            let mut attrs = dir_attrs();
+            if let Some(stored) = self.attrs.get(path) {
+                attrs.perms = stored
+                    .perms
+                    .map(|perms| Permisions::DIR | perms)
+                    .or(attrs.perms);
+                attrs.time = stored.time;
+            }
+            Message::Attrs(attrs)
+        } else {
+            StatusCode::NoSuchFile.to_status("No such file").into()
+        }
+    }
+
+    fn setstat(&mut self, path: &str, attrs: Attrs) -> Message {
+        if let Some(content) = self.files.get_mut(path) {
+            if let Some(size) = attrs.size {
+                let mut new_content = content.to_vec();
+                new_content.resize(size as usize, 0);
+                *content = Bytes::from(new_content);
+            }
+        } else if !self.is_dir(path) {
+            return StatusCode::NoSuchFile.to_status("No such file").into();
+        }
+
+        let stored = self.attrs.entry(path.to_owned()).or_default();
+        stored.perms = attrs.perms.or(stored.perms);
+        stored.owner = attrs.owner.or(stored.owner);
+        stored.time = attrs.time.or(stored.time);
+        StatusCode::Ok.to_status("").into()
+    }
+
+    /// Check whether the handle is a file opened with `pflags`.
+    ///
+    /// Unknown handles are considered allowed, so that they fail later as invalid.
+    fn allows(&self, handle: &Handle, pflags: PFlags) -> bool {
+        match self.handles.get(&handle.0) {
+            Some(Opened::File(_, opened)) => opened.contains(pflags),
+            _ => true,
+        }
+    }
+
+    fn content(&self, handle: &Handle) -> Option<Bytes> {
+        let Some(Opened::File(path, _)) = self.handles.get(&handle.0) else {
+            return None;
+        };
+        self.files.get(path).cloned()
+    }
+
+    /// Names and attributes of the files, links and directories directly in the directory `path`, sorted by name.
+    fn list(&self, path: &str) -> Vec<(String, Attrs)> {
+        let prefix = match path.trim_end_matches('/') {
+            "" | "." => String::new(),
+            path => format!("{path}/"),
+        };
+        let mut entries = HashMap::new();
+        for (file, content) in &self.files {
+            let Some(name) = file.strip_prefix(&prefix) else {
+                continue;
+            };
+            match name.split_once('/') {
+                Some((dir, _)) => entries.insert(dir.to_owned(), dir_attrs()),
+                None => {
+                    let stored = self.attrs.get(file).cloned().unwrap_or_default();
+                    entries.insert(
+                        name.to_owned(),
+                        Attrs {
+                            size: Some(content.len() as u64),
+                            perms: stored
+                                .perms
+                                .or(Some(Permisions::REG | Permisions::from_bits_retain(0o644))),
+                            ..stored
+                        },
+                    )
+                }
+            };
+        }
+        for link in self.links.keys() {
+            if let Some(name) = link.strip_prefix(&prefix) {
+                if !name.contains('/') {
+                    entries.insert(name.to_owned(), link_attrs());
+                }
+            }
+        }
+        for dir in &self.dirs {
+            if let Some(name) = dir.strip_prefix(&prefix) {
+                let name = name.split('/').next().unwrap_or_default();
+                if !name.is_empty() {
+                    entries.insert(name.to_owned(), dir_attrs());
+                }
+            }
+        }
+
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries
+    }
+
+    /// Check whether `path` is a directory, created explicitly or containing entries.
+    fn is_dir(&self, path: &str) -> bool {
+        self.dirs.contains(path.trim_end_matches('/')) || !self.list(path).is_empty()
+    }
+
+    fn dir_entry(&self, name: &str) -> NameEntry {
+        NameEntry {
+            filename: Path::from(name),
+            long_name: Bytes::from(name.to_owned()),
+            attrs: dir_attrs(),
+        }
+    }
+}
+
+fn dir_attrs() -> Attrs {
+    Attrs {
+        perms: Some(Permisions::DIR | Permisions::from_bits_retain(0o755)),
+        ..Default::default()
+    }
+}
+
+fn link_attrs() -> Attrs {
+    Attrs {
+        perms: Some(Permisions::LNK | Permisions::from_bits_retain(0o777)),
+        ..Default::default()
+    }
+}
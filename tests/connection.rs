@@ -0,0 +1,413 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the connection: its requests, and the errors reported when it is closed.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+use bytes::Bytes;
+use rusftp::client::{Error, SftpClient};
+use rusftp::message::{
+    Attrs, Data, Handle, Message, Open, PFlags, Path, Stat, StatusCode, Version,
+};
+
+mod common;
+use common::MockServer;
+
+/// Connect a client to a server that completes the handshake,
+/// then sends `reply` and closes the stream once it has received a request.
+async fn close_after(reply: &'static [u8]) -> SftpClient {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    tokio::spawn(async move {
+        read_frame(&mut server).await;
+        let version = Message::Version(Version {
+            version: 3,
+            extensions: Default::default(),
+        })
+        .encode(3)
+        .unwrap();
+        server.write_all(&version).await.unwrap();
+
+        read_frame(&mut server).await;
+        server.write_all(reply).await.unwrap();
+        server.shutdown().await.unwrap();
+    });
+
+    SftpClient::with_stream(client).await.unwrap()
+}
+
+/// Connect a client to a server that completes the handshake,
+/// then answers each request it receives with the next message of `replies`.
+async fn reply_with(replies: Vec<Message>) -> SftpClient {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    tokio::spawn(async move {
+        read_frame(&mut server).await;
+        let version = Message::Version(Version {
+            version: 3,
+            extensions: Default::default(),
+        })
+        .encode(3)
+        .unwrap();
+        server.write_all(&version).await.unwrap();
+
+        for reply in replies {
+            let id = read_frame(&mut server).await;
+            server.write_all(&reply.encode(id).unwrap()).await.unwrap();
+        }
+        read_frame(&mut server).await;
+    });
+
+    SftpClient::with_stream(client).await.unwrap()
+}
+
+/// Read a frame, and return the id of the request
+async fn read_frame(stream: &mut DuplexStream) -> u32 {
+    let length = stream.read_u32().await.unwrap();
+    let mut frame = vec![0u8; length as usize];
+    stream.read_exact(&mut frame).await.unwrap();
+    frame
+        .get(1..5)
+        .map_or(0, |id| u32::from_be_bytes(id.try_into().unwrap()))
+}
+
+fn status_code(err: Error) -> StatusCode {
+    assert!(err.is_connection_lost(), "{err:?}");
+    status_code_of(err)
+}
+
+fn status_code_of(err: Error) -> StatusCode {
+    match err {
+        Error::Sftp(status) => status.code,
+        err => panic!("Expected a status, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn closed_by_server() {
+    let sftp = close_after(b"").await;
+
+    let err = sftp.stat("file").await.unwrap_err();
+    assert_eq!(status_code(err), StatusCode::NoConnection);
+}
+
+#[tokio::test]
+async fn truncated_frame() {
+    let sftp = close_after(b"\0\0\0\x10\x65").await;
+
+    let err = sftp.stat("file").await.unwrap_err();
+    assert_eq!(status_code(err), StatusCode::ConnectionLost);
+}
+
+#[tokio::test]
+async fn idle_timeout() {
+    let (sftp, _) = MockServer::start(&[("/file", b"data")]).await;
+    let sftp = sftp.with_idle_timeout(Some(Duration::from_millis(200)));
+
+    // Requests keep the session alive
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        sftp.stat("/file").await.unwrap();
+    }
+
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let err = sftp.stat("/file").await.unwrap_err();
+    assert!(err.is_connection_lost(), "{err:?}");
+
+    // Requests answered from the metadata cache also keep the session alive
+    let (sftp, _) = MockServer::start(&[("/file", b"data")]).await;
+    let sftp = sftp
+        .with_metadata_cache(Duration::from_secs(60), 16)
+        .with_idle_timeout(Some(Duration::from_millis(200)));
+    assert_eq!(sftp.config().idle_timeout, Some(Duration::from_millis(200)));
+    sftp.stat("/file").await.unwrap();
+    let sent = sftp.metrics().requests_sent;
+    for _ in 0..4 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        sftp.stat("/file").await.unwrap();
+    }
+    assert_eq!(sftp.metrics().requests_sent, sent);
+
+    // Disabling the timeout keeps the session alive
+    let (sftp, _) = MockServer::start(&[("/file", b"data")]).await;
+    let sftp = sftp
+        .with_idle_timeout(Some(Duration::from_millis(50)))
+        .with_idle_timeout(None);
+    assert_eq!(sftp.config().idle_timeout, None);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    sftp.stat("/file").await.unwrap();
+}
+
+#[tokio::test]
+async fn request_with_id() {
+    let (sftp, _) = MockServer::start(&[("/file", b"data")]).await;
+
+    let (first, stat) = sftp.request_with_id(Stat {
+        path: Path::from("/file"),
+    });
+    let (second, missing) = sftp.clone().request_with_id(Stat {
+        path: Path::from("/missing"),
+    });
+    assert_ne!(first, 0);
+    assert_ne!(first, second);
+    assert_eq!(stat.await.unwrap().size, Some(4));
+    assert!(missing.await.is_err());
+
+    let (id, stopped) = SftpClient::new_stopped().request_with_id(Stat {
+        path: Path::from("/file"),
+    });
+    assert_eq!(id, 0);
+    assert!(matches!(stopped.await, Err(Error::Stopped)));
+}
+
+#[tokio::test]
+async fn request_cancellable() {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    tokio::spawn(async move {
+        read_frame(&mut server).await;
+        let version = Message::Version(Version {
+            version: 3,
+            extensions: Default::default(),
+        })
+        .encode(3)
+        .unwrap();
+        server.write_all(&version).await.unwrap();
+
+        // The reply to the cancelled request comes late, after the next request
+        let cancelled = read_frame(&mut server).await;
+        let next = read_frame(&mut server).await;
+        let late = Message::Status(StatusCode::Failure.to_status("Too late"));
+        server
+            .write_all(&late.encode(cancelled).unwrap())
+            .await
+            .unwrap();
+        let attrs = Message::Attrs(Attrs {
+            size: Some(4),
+            ..Default::default()
+        });
+        server
+            .write_all(&attrs.encode(next).unwrap())
+            .await
+            .unwrap();
+        read_frame(&mut server).await;
+    });
+
+    let sftp = SftpClient::with_stream(client).await.unwrap();
+    let (cancelled, token) = sftp.request_cancellable(Stat {
+        path: Path::from("/slow"),
+    });
+    token.cancel();
+    let err = cancelled.await.unwrap_err();
+    assert!(
+        matches!(&err, Error::Io(io) if io.kind() == std::io::ErrorKind::Interrupted),
+        "{err:?}"
+    );
+
+    // The late reply is discarded, and does not disturb the following requests
+    assert_eq!(sftp.stat("/file").await.unwrap().size, Some(4));
+
+    // Cancelling a completed request has no effect
+    token.cancel();
+    let (stopped, token) = SftpClient::new_stopped().request_cancellable(Stat {
+        path: Path::from("/file"),
+    });
+    token.cancel();
+    assert!(matches!(stopped.await, Err(Error::Stopped)));
+}
+
+#[tokio::test]
+async fn read_length() {
+    let sftp = reply_with(vec![
+        Message::Data(Data(Bytes::from_static(b"too long"))),
+        Message::Data(Data(Bytes::from_static(b"ok"))),
+        Message::Data(Data(Bytes::from_static(b"12345"))),
+    ])
+    .await;
+    let handle = Handle(Bytes::from_static(b"handle"));
+
+    // Servers must not send more data than requested
+    let err = sftp.read(handle.clone(), 0, 4).await.unwrap_err();
+    assert_eq!(status_code_of(err), StatusCode::BadMessage);
+
+    // Short reads are valid
+    assert_eq!(sftp.read(handle.clone(), 0, 4).await.unwrap(), "ok");
+    assert_eq!(sftp.read(handle, 0, 5).await.unwrap(), "12345");
+}
+
+#[tokio::test]
+async fn cancelled_open() {
+    let (client, mut server) = tokio::io::duplex(1024);
+    let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        read_frame(&mut server).await;
+        let version = Message::Version(Version {
+            version: 3,
+            extensions: Default::default(),
+        })
+        .encode(3)
+        .unwrap();
+        server.write_all(&version).await.unwrap();
+
+        // The handle is sent after the open request has been cancelled
+        let open = read_frame(&mut server).await;
+        let stat = read_frame(&mut server).await;
+        let handle = Message::Handle(Handle(Bytes::from_static(b"handle")));
+        server
+            .write_all(&handle.encode(open).unwrap())
+            .await
+            .unwrap();
+        let attrs = Message::Attrs(Attrs::default());
+        server
+            .write_all(&attrs.encode(stat).unwrap())
+            .await
+            .unwrap();
+
+        let length = server.read_u32().await.unwrap();
+        let mut frame = vec![0u8; length as usize];
+        server.read_exact(&mut frame).await.unwrap();
+        _ = closed_tx.send(Message::decode_raw(&frame).unwrap().1);
+    });
+
+    let sftp = SftpClient::with_stream(client).await.unwrap();
+    let (open, token) = sftp.request_cancellable(Open {
+        filename: Path::from("/file"),
+        pflags: PFlags::READ,
+        attrs: Attrs::default(),
+    });
+    token.cancel();
+    assert!(open.await.is_err());
+    sftp.stat("/file").await.unwrap();
+
+    let closed = closed_rx.await.unwrap();
+    assert!(
+        matches!(&closed, Message::Close(close) if close.handle.0 == "handle"),
+        "{closed:?}"
+    );
+}
+
+#[tokio::test]
+async fn rate_limit() {
+    let content = vec![0u8; 100_000];
+    let (sftp, _) = MockServer::start(&[("/file", &content)]).await;
+    let sftp = sftp.with_rate_limit(Some(100_000));
+    assert_eq!(sftp.config().rate_limit, Some(100_000));
+
+    let file = sftp.open("/file").await.unwrap();
+    let handle = file.handle().unwrap().clone();
+
+    // The first read is sent immediately, the following ones wait for the debt to be paid back
+    let start = std::time::Instant::now();
+    for offset in [0, 20_000, 40_000] {
+        sftp.read(handle.clone(), offset, 20_000).await.unwrap();
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(350), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+
+    // Cancellations are not delayed by the debt
+    sftp.read(handle.clone(), 0, 100_000).await.unwrap();
+    let start = std::time::Instant::now();
+    let (stat, token) = sftp.request_cancellable(Stat {
+        path: Path::from("/file"),
+    });
+    token.cancel();
+    let err = stat.await.unwrap_err();
+    assert!(
+        matches!(&err, Error::Io(io) if io.kind() == std::io::ErrorKind::Interrupted),
+        "{err:?}"
+    );
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    // Removing the limit also drops the debt
+    let sftp = sftp.with_rate_limit(None);
+    assert_eq!(sftp.config().rate_limit, None);
+    let start = std::time::Instant::now();
+    sftp.read(handle, 0, 20_000).await.unwrap();
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn open_exclusive() {
+    let sftp = reply_with(vec![
+        StatusCode::Failure.to_status("File exists").into(),
+        Message::Attrs(Attrs::default()),
+        StatusCode::Failure.to_status("Permission denied").into(),
+        StatusCode::NoSuchFile.to_status("No such file").into(),
+    ])
+    .await;
+
+    // A failure is reported as the file already existing only if the path exists
+    let err = sftp
+        .open_exclusive("lock", Attrs::default())
+        .await
+        .unwrap_err();
+    assert!(err.is_already_exists(), "{err:?}");
+
+    let err = sftp
+        .open_exclusive("lock", Attrs::default())
+        .await
+        .unwrap_err();
+    assert!(!err.is_already_exists(), "{err:?}");
+    assert_eq!(status_code_of(err), StatusCode::Failure);
+}
+
+#[tokio::test]
+async fn empty_reads() {
+    let sized = |size| {
+        Message::Attrs(Attrs {
+            size,
+            ..Default::default()
+        })
+    };
+    let data = |data: &'static [u8]| Message::Data(Data(Bytes::from_static(data)));
+    let sftp = reply_with(vec![
+        Message::Handle(Handle(Bytes::from_static(b"handle"))),
+        sized(Some(4)),
+        data(b""),
+        data(b"abcd"),
+        sized(Some(4)),
+        data(b""),
+        StatusCode::Eof.to_status("End of file").into(),
+        data(b""),
+        data(b""),
+        sized(None),
+        StatusCode::Ok.to_status("").into(),
+    ])
+    .await;
+    let mut file = sftp.open_read("file").await.unwrap();
+    let mut buf = [0u8; 16];
+
+    // An empty reply before the end of file is retried once
+    assert!(!file.at_eof().await.unwrap());
+    assert_eq!(AsyncReadExt::read(&mut file, &mut buf).await.unwrap(), 4);
+    assert_eq!(&buf[..4], b"abcd");
+    assert!(file.at_eof().await.unwrap());
+
+    // The end of file is reported after the retry
+    assert_eq!(AsyncReadExt::read(&mut file, &mut buf).await.unwrap(), 0);
+    assert_eq!(AsyncReadExt::read(&mut file, &mut buf).await.unwrap(), 0);
+
+    // The end of file cannot be checked without the size of the file
+    let err = file.at_eof().await.unwrap_err();
+    assert!(matches!(err, Error::Io(io) if io.kind() == std::io::ErrorKind::Unsupported));
+
+    file.close().await.unwrap();
+}
@@ -0,0 +1,645 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of directory listing and path resolution against an in-memory SFTP server.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use rusftp::client::{Error, Limits, OverwritePolicy, SftpClient, SftpClientConfig};
+use rusftp::message::{
+    Attrs, Handle, Message, Name, NameEntry, Path, Permisions, StatusCode, Time, Version,
+};
+
+use common::MockServer;
+
+const FILES: &[(&str, &[u8])] = &[
+    ("dir/a", b"a"),
+    ("dir/b", b"bb"),
+    ("dir/sub/c", b"ccc"),
+    ("other", b"other"),
+];
+
+#[tokio::test]
+async fn readdir() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let entries = sftp.readdir("dir").await.unwrap();
+    let names = entries
+        .iter()
+        .map(|entry| (entry.filename.to_string(), entry.attrs.size))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            (".".to_owned(), None),
+            ("..".to_owned(), None),
+            ("a".to_owned(), Some(1)),
+            ("b".to_owned(), Some(2)),
+            ("sub".to_owned(), None),
+        ]
+    );
+
+    let err = sftp.readdir("missing").await.unwrap_err();
+    assert_eq!(
+        std::io::Error::from(err).kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+#[tokio::test]
+async fn readdir_capped() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let entries = sftp.readdir_capped("dir", 3).await.unwrap();
+    let names = entries
+        .iter()
+        .map(|entry| entry.filename.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "a"]);
+
+    let entries = sftp.readdir_capped("dir", 100).await.unwrap();
+    assert_eq!(entries.len(), 5);
+}
+
+#[tokio::test]
+async fn opendir() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let dir = sftp.opendir("dir/sub").await.unwrap();
+    let names = dir
+        .map_ok(|entry| entry.filename.to_string())
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(names, [".", "..", "c"]);
+}
+
+#[tokio::test]
+async fn opendir_state() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let mut dir = sftp.opendir("dir/sub").await.unwrap();
+    assert!(!dir.is_exhausted());
+    assert_eq!(dir.remaining_buffered(), 0);
+
+    let entry = dir.try_next().await.unwrap().unwrap();
+    assert_eq!(entry.filename, ".");
+    assert!(!dir.is_exhausted());
+    assert_eq!(dir.remaining_buffered(), 2);
+
+    dir.try_next().await.unwrap().unwrap();
+    dir.try_next().await.unwrap().unwrap();
+    assert!(!dir.is_exhausted());
+    assert_eq!(dir.remaining_buffered(), 0);
+
+    assert!(dir.try_next().await.unwrap().is_none());
+    assert!(dir.is_exhausted());
+    assert_eq!(dir.remaining_buffered(), 0);
+}
+
+#[tokio::test]
+async fn opendir_prefetched() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let dir = sftp.opendir_prefetched("dir").await.unwrap();
+    let names = dir
+        .map_ok(|entry| entry.filename.to_string())
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    assert_eq!(names, [".", "..", "a", "b", "sub"]);
+}
+
+#[tokio::test]
+async fn expand_path() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    assert_eq!(
+        sftp.expand_path("~/dir").await.unwrap().as_ref() as &str,
+        "/home/user/dir"
+    );
+    assert_eq!(
+        sftp.expand_path("/absolute").await.unwrap().as_ref() as &str,
+        "/absolute"
+    );
+}
+
+#[tokio::test]
+async fn stat_many() {
+    let (sftp, _) = MockServer::start(FILES).await;
+    sftp.create_symlink("dir/b", "dir/link").await.unwrap();
+
+    // Symbolic links are followed
+    let paths = ["dir/a", "missing", "dir/link"].map(Path::from).to_vec();
+    let attrs = sftp.stat_many(paths, 2).await;
+    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs[0].as_ref().unwrap().size, Some(1));
+    assert!(attrs[1].is_err());
+    assert_eq!(attrs[2].as_ref().unwrap().size, Some(2));
+}
+
+#[tokio::test]
+async fn stat_many_map() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let paths = ["dir/a", "dir/b", "dir/a", "missing"]
+        .map(Path::from)
+        .to_vec();
+    let attrs = sftp.stat_many_map(paths, 2).await;
+    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs[&Path::from("dir/a")].as_ref().unwrap().size, Some(1));
+    assert_eq!(attrs[&Path::from("dir/b")].as_ref().unwrap().size, Some(2));
+    assert!(attrs[&Path::from("missing")].is_err());
+
+    let attrs = sftp.lstat_many_map(vec![Path::from("dir/sub/c")], 2).await;
+    assert_eq!(
+        attrs[&Path::from("dir/sub/c")].as_ref().unwrap().size,
+        Some(3)
+    );
+
+    // Only `stat_many_map()` follows symbolic links
+    sftp.create_symlink("dir/b", "dir/link").await.unwrap();
+    let link = Path::from("dir/link");
+    let attrs = sftp.stat_many_map(vec![link.clone()], 2).await;
+    assert_eq!(attrs[&link].as_ref().unwrap().size, Some(2));
+    let attrs = sftp.lstat_many_map(vec![link.clone()], 2).await;
+    let perms = attrs[&link].as_ref().unwrap().perms.unwrap();
+    assert!(perms.to_string().starts_with('l'));
+}
+
+#[tokio::test]
+async fn create_symlink() {
+    let limits = Limits {
+        max_packet_length: 1 << 20,
+        max_read_length: 1 << 16,
+        max_write_length: 1 << 16,
+        max_open_handles: 16,
+    };
+    let (standard, _) = MockServer::start(&[("dir/file", b"data")]).await;
+    let (openssh, _) = MockServer::start_with_limits(&[("dir/file", b"data")], Some(limits)).await;
+
+    // The link is created at the same place, whatever the order of the server
+    for sftp in [standard, openssh] {
+        sftp.create_symlink("file", "dir/link").await.unwrap();
+        assert_eq!(&*sftp.readlink("dir/link").await.unwrap(), "file");
+        assert!(sftp.readlink("file").await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn download_dir() {
+    let (sftp, _) = MockServer::start(FILES).await;
+    let perms = Permisions::REG | Permisions::from_bits_retain(0o600);
+    sftp.setstat(
+        "dir/b",
+        Attrs {
+            perms: Some(perms),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    sftp.symlink("dir/sub/link", "../a").await.unwrap();
+
+    let local = std::env::temp_dir().join(format!("rusftp-download-dir-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&local);
+
+    sftp.download_dir("dir", &local).await.unwrap();
+    assert_eq!(std::fs::read(local.join("a")).unwrap(), b"a");
+    assert_eq!(std::fs::read(local.join("b")).unwrap(), b"bb");
+    assert_eq!(std::fs::read(local.join("sub/c")).unwrap(), b"ccc");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(local.join("b"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = std::fs::metadata(local.join("sub"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(
+            std::fs::read_link(local.join("sub/link")).unwrap(),
+            std::path::Path::new("../a")
+        );
+        assert_eq!(std::fs::read(local.join("sub/link")).unwrap(), b"a");
+    }
+
+    // Downloading again overwrites the local tree
+    sftp.download_dir("dir", &local).await.unwrap();
+    std::fs::remove_dir_all(&local).unwrap();
+
+    let err = sftp.download_dir("other", &local).await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::Failure));
+    assert!(sftp.download_dir("missing", &local).await.is_err());
+    assert!(!local.exists());
+}
+
+#[tokio::test]
+async fn download_dir_hostile() {
+    let base = std::env::temp_dir().join(format!(
+        "rusftp-download-dir-hostile-{}",
+        std::process::id()
+    ));
+    _ = std::fs::remove_dir_all(&base);
+    let outside = base.join("outside");
+    std::fs::create_dir_all(&outside).unwrap();
+
+    // A symbolic link and a file with the same name would write through the link
+    let link = Attrs {
+        perms: Some(Permisions::LNK | Permisions::from_bits_retain(0o777)),
+        ..Default::default()
+    };
+    let file = Attrs {
+        size: Some(5),
+        perms: Some(Permisions::REG | Permisions::from_bits_retain(0o644)),
+        ..Default::default()
+    };
+    let listings = HashMap::from([(
+        "dir".to_owned(),
+        vec![("x".to_owned(), link), ("x".to_owned(), file)],
+    )]);
+    let (sftp, _) = MockServer::start_with_listings(
+        &[
+            ("dir/x", b"pwned"),
+            ("safe/x", b"data"),
+            ("tree/sub/f", b"pwned"),
+        ],
+        listings,
+    )
+    .await;
+
+    let local = base.join("duplicate");
+    let err = sftp.download_dir("dir", &local).await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::BadMessage));
+    assert!(!local.join("x").exists());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        // Existing local links are replaced, not written through
+        std::fs::write(outside.join("target"), b"original").unwrap();
+        let local = base.join("file-link");
+        std::fs::create_dir_all(&local).unwrap();
+        symlink(outside.join("target"), local.join("x")).unwrap();
+        sftp.setstat(
+            "safe/x",
+            Attrs {
+                perms: Some(Permisions::REG | Permisions::from_bits_retain(0o4755)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        sftp.download_dir("safe", &local).await.unwrap();
+        assert_eq!(std::fs::read(outside.join("target")).unwrap(), b"original");
+        let metadata = std::fs::symlink_metadata(local.join("x")).unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+        assert_eq!(std::fs::read(local.join("x")).unwrap(), b"data");
+
+        // Existing local links to directories are not descended into
+        let local = base.join("dir-link");
+        std::fs::create_dir_all(&local).unwrap();
+        symlink(&outside, local.join("sub")).unwrap();
+
+        let err = sftp.download_dir("tree", &local).await.unwrap_err();
+        assert!(matches!(err, Error::Io(io) if io.kind() == std::io::ErrorKind::AlreadyExists));
+        assert!(!outside.join("f").exists());
+    }
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[tokio::test]
+async fn upload_dir() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let local = std::env::temp_dir().join(format!("rusftp-upload-dir-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&local);
+    std::fs::create_dir_all(local.join("sub/empty")).unwrap();
+    std::fs::write(local.join("a"), b"new a").unwrap();
+    std::fs::write(local.join("sub/d"), b"dddd").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(local.join("a"), std::fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink("../a", local.join("sub/link")).unwrap();
+    }
+
+    // Missing parents are created, and existing files are overwritten
+    sftp.upload_dir(&local, "up/load").await.unwrap();
+    sftp.upload_dir(&local, "dir").await.unwrap();
+    std::fs::remove_dir_all(&local).unwrap();
+
+    for root in ["up/load", "dir"] {
+        assert_eq!(read(&sftp, format!("{root}/a")).await, b"new a");
+        assert_eq!(read(&sftp, format!("{root}/sub/d")).await, b"dddd");
+        assert!(sftp.stat(format!("{root}/sub/empty")).await.is_ok());
+
+        #[cfg(unix)]
+        {
+            let attrs = sftp.stat(format!("{root}/a")).await.unwrap();
+            assert_eq!(attrs.perms.unwrap().bits() & 0o777, 0o640);
+            assert!(attrs.time.is_some());
+            assert_eq!(
+                sftp.readlink(format!("{root}/sub/link")).await.unwrap(),
+                "../a"
+            );
+        }
+    }
+    assert_eq!(read(&sftp, "dir/b").await, b"bb");
+
+    assert!(sftp.upload_dir(&local, "dir").await.is_err());
+    let err = sftp
+        .upload_dir(std::env::temp_dir(), "other")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::Failure));
+}
+
+#[tokio::test]
+async fn upload_dir_policy() {
+    let (sftp, _) = MockServer::start(FILES).await;
+    let kept = Attrs {
+        perms: Some(Permisions::from_bits_retain(0o600)),
+        time: Some(Time {
+            atime: 1_000,
+            mtime: 2_000,
+            nanos: None,
+        }),
+        ..Default::default()
+    };
+    sftp.setstat("dir/a", kept.clone()).await.unwrap();
+    sftp.setstat("dir/sub", kept.clone()).await.unwrap();
+
+    let local = std::env::temp_dir().join(format!("rusftp-upload-policy-{}", std::process::id()));
+    _ = std::fs::remove_dir_all(&local);
+    std::fs::create_dir_all(local.join("sub")).unwrap();
+    std::fs::write(local.join("a"), b"new a").unwrap();
+    std::fs::write(local.join("sub/c"), b"new c").unwrap();
+    std::fs::write(local.join("sub/d"), b"dddd").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("a", local.join("b")).unwrap();
+
+    // Existing entries are left untouched, and the missing ones are uploaded
+    sftp.upload_dir_with_policy(&local, "dir", OverwritePolicy::Skip)
+        .await
+        .unwrap();
+    assert_eq!(read(&sftp, "dir/a").await, b"a");
+    assert_eq!(read(&sftp, "dir/b").await, b"bb");
+    assert_eq!(read(&sftp, "dir/sub/c").await, b"ccc");
+    assert_eq!(read(&sftp, "dir/sub/d").await, b"dddd");
+    for path in ["dir/a", "dir/sub"] {
+        let attrs = sftp.lstat(path).await.unwrap();
+        assert_eq!(attrs.perms.unwrap().bits() & 0o777, 0o600);
+        assert_eq!(attrs.time, kept.time);
+    }
+
+    // Existing entries are replaced
+    sftp.upload_dir_with_policy(&local, "dir", OverwritePolicy::Overwrite)
+        .await
+        .unwrap();
+    std::fs::remove_dir_all(&local).unwrap();
+    assert_eq!(read(&sftp, "dir/a").await, b"new a");
+    assert_eq!(read(&sftp, "dir/sub/c").await, b"new c");
+    assert_eq!(read(&sftp, "dir/sub/d").await, b"dddd");
+    for path in ["dir/a", "dir/sub"] {
+        assert_ne!(sftp.lstat(path).await.unwrap().time, kept.time);
+    }
+    #[cfg(unix)]
+    assert_eq!(sftp.readlink("dir/b").await.unwrap(), "a");
+}
+
+async fn read(sftp: &SftpClient, path: impl Into<Path>) -> Vec<u8> {
+    let mut content = Vec::new();
+    sftp.download_stream(path, &mut content).await.unwrap();
+    content
+}
+
+#[tokio::test]
+async fn statvfs() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    let statvfs = sftp.statvfs("dir").await.unwrap();
+    assert_eq!(
+        (statvfs.frsize, statvfs.blocks, statvfs.bavail),
+        (1024, 1000, 500)
+    );
+    assert_eq!(sftp.free_space("dir/a").await.unwrap(), 500 * 1024);
+    assert_eq!(sftp.total_space("dir").await.unwrap(), 1000 * 1024);
+
+    let err = sftp.free_space("missing").await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::NoSuchFile));
+}
+
+#[tokio::test]
+async fn metadata_cache() {
+    let (sftp, _) = MockServer::start(FILES).await;
+    let sftp = sftp.with_metadata_cache(Duration::from_secs(60), 16);
+    let sent = || sftp.metrics().requests_sent;
+
+    // Repeated requests are answered from the cache
+    let attrs = sftp.stat("dir/a").await.unwrap();
+    sftp.stat("dir").await.unwrap();
+    sftp.stat("other").await.unwrap();
+    assert_eq!(sftp.realpath("dir/").await.unwrap(), "/dir");
+    let before = sent();
+    assert_eq!(sftp.stat("dir/a").await.unwrap(), attrs);
+    assert_eq!(sftp.realpath("dir/").await.unwrap(), "/dir");
+    assert_eq!(sent(), before);
+
+    // Errors are not cached
+    sftp.stat("missing").await.unwrap_err();
+    sftp.stat("missing").await.unwrap_err();
+    assert_eq!(sent(), before + 2);
+
+    // A setstat busts the path and its parent, but not the other paths
+    let attrs = Attrs {
+        perms: Some(Permisions::from_bits_retain(0o600)),
+        ..Default::default()
+    };
+    sftp.setstat("dir/a", attrs).await.unwrap();
+    let before = sent();
+    let attrs = sftp.stat("dir/a").await.unwrap();
+    assert_eq!(attrs.perms.unwrap().bits() & 0o777, 0o600);
+    sftp.stat("dir").await.unwrap();
+    sftp.stat("other").await.unwrap();
+    assert_eq!(sent(), before + 2);
+
+    // A remove busts the path
+    sftp.remove("dir/a").await.unwrap();
+    let err = sftp.stat("dir/a").await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::NoSuchFile));
+
+    // A rename busts everything
+    sftp.rename("dir/b", "dir/c").await.unwrap();
+    let before = sent();
+    sftp.stat("other").await.unwrap();
+    sftp.realpath("dir/").await.unwrap();
+    assert_eq!(sent(), before + 2);
+}
+
+#[tokio::test]
+async fn metadata_cache_eviction() {
+    let (sftp, _) = MockServer::start(FILES).await;
+
+    // Least recently used entries are evicted
+    let sftp = sftp.with_metadata_cache(Duration::from_secs(60), 2);
+    sftp.stat("dir/a").await.unwrap();
+    sftp.stat("dir/b").await.unwrap();
+    sftp.stat("dir/a").await.unwrap();
+    sftp.stat("other").await.unwrap();
+    let before = sftp.metrics().requests_sent;
+    sftp.stat("dir/a").await.unwrap();
+    assert_eq!(sftp.metrics().requests_sent, before);
+    sftp.stat("dir/b").await.unwrap();
+    assert_eq!(sftp.metrics().requests_sent, before + 1);
+
+    // Entries expire
+    let sftp = sftp.with_metadata_cache(Duration::from_millis(50), 2);
+    sftp.stat("dir/a").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let before = sftp.metrics().requests_sent;
+    sftp.stat("dir/a").await.unwrap();
+    assert_eq!(sftp.metrics().requests_sent, before + 1);
+
+    // The cache can be disabled
+    let sftp = sftp.with_metadata_cache(Duration::from_secs(60), 0);
+    assert_eq!(sftp.config().metadata_cache_capacity, 0);
+    sftp.stat("dir/a").await.unwrap();
+    sftp.stat("dir/a").await.unwrap();
+    assert_eq!(sftp.metrics().requests_sent, before + 3);
+}
+
+#[tokio::test]
+async fn metadata_cache_config() {
+    let (stream, _) = MockServer::spawn(FILES, None);
+    let config = SftpClientConfig {
+        metadata_cache_ttl: Duration::from_secs(60),
+        metadata_cache_capacity: 16,
+        ..Default::default()
+    };
+    let sftp = SftpClient::with_stream_config(stream, config.clone())
+        .await
+        .unwrap();
+    assert_eq!(sftp.config(), &config);
+
+    sftp.stat("dir/a").await.unwrap();
+    let before = sftp.metrics().requests_sent;
+    sftp.stat("dir/a").await.unwrap();
+    assert_eq!(sftp.metrics().requests_sent, before);
+}
+
+#[tokio::test]
+async fn read_dir_all_with_stat_concurrency() {
+    let (client, mut server) = tokio::io::duplex(1 << 16);
+    let max_pending = Arc::new(AtomicUsize::new(0));
+
+    // Server listing entries without attributes, and holding the `lstat` requests
+    // until no more of them are received
+    tokio::spawn({
+        let max_pending = max_pending.clone();
+        async move {
+            let mut listed = false;
+            let mut pending = Vec::new();
+            loop {
+                let read = async {
+                    let length = server.read_u32().await?;
+                    let mut frame = vec![0u8; length as usize];
+                    server.read_exact(&mut frame).await?;
+                    Ok::<_, std::io::Error>(frame)
+                };
+                let frame = match tokio::time::timeout(Duration::from_millis(50), read).await {
+                    Ok(Ok(frame)) => frame,
+                    Ok(Err(_)) => break,
+                    Err(_) => {
+                        max_pending.fetch_max(pending.len(), Ordering::SeqCst);
+                        for id in pending.drain(..) {
+                            let reply = Message::Attrs(Attrs {
+                                size: Some(1),
+                                ..Default::default()
+                            });
+                            server.write_all(&reply.encode(id).unwrap()).await.unwrap();
+                        }
+                        continue;
+                    }
+                };
+                if frame.first() == Some(&1) {
+                    let version = Message::Version(Version {
+                        version: 3,
+                        extensions: Default::default(),
+                    });
+                    server.write_all(&version.encode(3).unwrap()).await.unwrap();
+                    continue;
+                }
+                let (id, message) = Message::decode_raw(&frame).unwrap();
+                let reply = match message {
+                    Message::OpenDir(_) => Message::Handle(Handle("dir".into())),
+                    Message::ReadDir(_) if !listed => {
+                        listed = true;
+                        Message::Name(Name(
+                            (0..5)
+                                .map(|i| NameEntry {
+                                    filename: Path::from(format!("file{i}")),
+                                    long_name: Default::default(),
+                                    attrs: Attrs::default(),
+                                })
+                                .collect(),
+                        ))
+                    }
+                    Message::ReadDir(_) => StatusCode::Eof.to_status("").into(),
+                    Message::LStat(_) => {
+                        pending.push(id);
+                        continue;
+                    }
+                    _ => StatusCode::Ok.to_status("").into(),
+                };
+                server.write_all(&reply.encode(id).unwrap()).await.unwrap();
+            }
+        }
+    });
+
+    let config = SftpClientConfig {
+        read_ahead: 2,
+        ..Default::default()
+    };
+    let sftp = SftpClient::with_stream_config(client, config)
+        .await
+        .unwrap();
+
+    // At most `read_ahead` requests are on-flight
+    let entries = sftp.read_dir_all_with_stat("dir").await.unwrap();
+    assert_eq!(entries.len(), 5);
+    assert!(entries.iter().all(|entry| entry.attrs.size == Some(1)));
+    assert_eq!(max_pending.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,1071 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of [`File`](rusftp::client::File) against an in-memory SFTP server.
+
+mod common;
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{
+    AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf,
+};
+
+use rusftp::client::{
+    Error, File, FrameDirection, HandleCache, Limits, ReconnectingClient, SftpClient, SftpPool,
+};
+use rusftp::message::{Attrs, Handle, Owner, PFlags, Permisions, StatusCode, Time};
+
+use common::{MockServer, MAX_FILE_SIZE};
+
+#[tokio::test]
+async fn read_known_size() {
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let (sftp, reads) = MockServer::start(&[("file", &content)]).await;
+    let chunk_size = sftp.config().chunk_size as usize;
+
+    let (mut file, attrs) = sftp
+        .open_with_stat("file", PFlags::READ, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(attrs.size, Some(content.len() as u64));
+
+    // Read chunk by chunk, so the number of requests does not depend on the growth of the buffer
+    let mut read = Vec::new();
+    let mut chunk = vec![0u8; chunk_size];
+    loop {
+        let n = AsyncReadExt::read(&mut file, &mut chunk).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        read.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(read, content);
+
+    // No trailing read is needed to detect the end of file
+    assert_eq!(
+        reads.load(Ordering::SeqCst),
+        content.len().div_ceil(chunk_size)
+    );
+
+    let stats = file.stats();
+    assert_eq!(stats.reads, reads.load(Ordering::SeqCst) as u64);
+    assert_eq!(stats.bytes_read, content.len() as u64);
+    assert_eq!(stats.writes, 0);
+    assert_eq!(stats.bytes_written, 0);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_with_server_limits() {
+    let limits = Limits {
+        max_packet_length: 34000,
+        max_read_length: 4096,
+        max_write_length: 4096,
+        max_open_handles: 16,
+    };
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let (sftp, reads) = MockServer::start_with_limits(&[("file", &content)], Some(limits)).await;
+    assert_eq!(sftp.limits(), Some(&limits));
+    assert_eq!(sftp.read_chunk_size(), 4096);
+    assert_eq!(sftp.write_chunk_size(), 4096);
+
+    // The limits are only queried on demand
+    let (stream, _) = MockServer::spawn(&[], Some(limits));
+    let default = SftpClient::with_stream(stream).await.unwrap();
+    assert_eq!(default.limits(), None);
+    assert_eq!(default.read_chunk_size(), 32768);
+
+    let (mut file, _) = sftp
+        .open_with_stat("file", PFlags::READ, Default::default())
+        .await
+        .unwrap();
+    let mut buffer = Vec::new();
+    let mut chunk = vec![0; 1 << 16];
+    loop {
+        let n = AsyncReadExt::read(&mut file, &mut chunk).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        assert!(n <= 4096);
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+    assert_eq!(buffer, content);
+    assert_eq!(reads.load(Ordering::SeqCst), 3);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_larger_than_default_chunk() {
+    let limits = Limits {
+        max_packet_length: 1 << 20,
+        max_read_length: 1 << 18,
+        max_write_length: 1 << 18,
+        max_open_handles: 16,
+    };
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let (sftp, reads) = MockServer::start_with_limits(&[("file", &content)], Some(limits)).await;
+    assert!(sftp.read_chunk_size() > sftp.config().chunk_size);
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    let mut buffer = vec![0; content.len()];
+    let n = AsyncReadExt::read(&mut file, &mut buffer).await.unwrap();
+    assert_eq!(n, content.len());
+    assert_eq!(buffer, content);
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn raw_ranges_with_server_limits() {
+    let limits = Limits {
+        max_packet_length: 34000,
+        max_read_length: 1000,
+        max_write_length: 1000,
+        max_open_handles: 16,
+    };
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let (sftp, reads) = MockServer::start_with_limits(&[], Some(limits)).await;
+
+    let mut file = sftp
+        .open_with_flags("file", PFlags::READ | PFlags::WRITE | PFlags::CREATE)
+        .await
+        .unwrap();
+    let handle = file.handle().unwrap().clone();
+
+    sftp.write_all_at(handle.clone(), 5, content.clone())
+        .await
+        .unwrap();
+
+    let data = sftp.read_exact_at(handle.clone(), 5, 10_000).await.unwrap();
+    assert_eq!(data, content);
+    assert_eq!(reads.load(Ordering::SeqCst), 10);
+
+    // The end of the file is reached before the end of the range
+    let data = sftp
+        .read_exact_at(handle.clone(), 9005, 2000)
+        .await
+        .unwrap();
+    assert_eq!(data, content[9000..]);
+
+    // Huge lengths are not allocated upfront
+    let data = sftp
+        .read_exact_at(handle.clone(), 8005, usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(data, content[8000..]);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn raw_write_failure_offset() {
+    let limits = Limits {
+        max_packet_length: 34000,
+        max_read_length: 1000,
+        max_write_length: 1000,
+        max_open_handles: 16,
+    };
+    let (sftp, _) = MockServer::start_with_limits(&[], Some(limits)).await;
+
+    let mut file = sftp
+        .open_with_flags("file", PFlags::WRITE | PFlags::CREATE)
+        .await
+        .unwrap();
+    let handle = file.handle().unwrap().clone();
+
+    // The chunks starting at MAX_FILE_SIZE - 500 and after cannot be written
+    let start = (MAX_FILE_SIZE - 2500) as u64;
+    let err = sftp
+        .write_all_at(handle.clone(), start, vec![1u8; 5000])
+        .await
+        .unwrap_err();
+    match err {
+        Error::WriteFailed { offset, source } => {
+            assert_eq!(offset, start + 2000);
+            assert!(matches!(*source, Error::Sftp(status) if status.code == StatusCode::Failure));
+        }
+        err => panic!("Unexpected error: {err:?}"),
+    }
+    assert_eq!(sftp.fstat(handle).await.unwrap().size, Some(start + 2000));
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_to_end_after_seek_from_end() {
+    let content = b"Hello world!";
+    let (sftp, reads) = MockServer::start(&[("file", content)]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    file.seek(std::io::SeekFrom::End(-6)).await.unwrap();
+
+    let mut read = Vec::new();
+    file.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, b"world!");
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+    // The end of file is reported from the cache only once
+    assert_eq!(
+        AsyncReadExt::read(&mut file, &mut [0; 16]).await.unwrap(),
+        0
+    );
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_growing_file() {
+    let (sftp, reads) = MockServer::start(&[("file", b"Hello")]).await;
+
+    let (mut file, attrs) = sftp
+        .open_with_stat("file", PFlags::READ, Default::default())
+        .await
+        .unwrap();
+    assert_eq!(attrs.size, Some(5));
+    let mut read = Vec::new();
+    file.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, b"Hello");
+    assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+    // The file grows once its known size has been reached
+    let mut writer = sftp.open_with_flags("file", PFlags::WRITE).await.unwrap();
+    writer.seek(std::io::SeekFrom::Start(5)).await.unwrap();
+    writer.write_all(b" world!").await.unwrap();
+    writer.close().await.unwrap();
+
+    // The cached end of file has been reported once, so the new content is read from the server
+    let mut read = Vec::new();
+    file.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, b" world!");
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_to_end_unknown_size() {
+    let content = b"Hello world!";
+    let (sftp, reads) = MockServer::start(&[("file", content)]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+
+    let mut read = Vec::new();
+    file.read_to_end(&mut read).await.unwrap();
+    assert_eq!(read, content);
+
+    // The end of file can only be detected by the server
+    assert_eq!(reads.load(Ordering::SeqCst), 2);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn read_into() {
+    let content = b"Hello world!";
+    let (sftp, _) = MockServer::start(&[("file", content)]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    let mut buf = BytesMut::from(&b">"[..]);
+
+    assert_eq!(file.read_into(0, &mut buf, 5).await.unwrap(), 5);
+    assert_eq!(&buf[..], b">Hello");
+
+    // Partial read at the end of the file
+    assert_eq!(file.read_into(6, &mut buf, 100).await.unwrap(), 6);
+    assert_eq!(&buf[..], b">Helloworld!");
+
+    // End of file
+    assert_eq!(file.read_into(12, &mut buf, 100).await.unwrap(), 0);
+    assert_eq!(&buf[..], b">Helloworld!");
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn is_valid() {
+    let (sftp, _) = MockServer::start(&[("file", b"content")]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    assert!(file.is_valid().await.unwrap());
+
+    let stale = File::from_handle(sftp.clone(), Handle(Bytes::from_static(b"stale")));
+    assert!(!stale.is_valid().await.unwrap());
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn position() {
+    let content = b"0123456789";
+    let (sftp, _) = MockServer::start(&[("file", content)]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    assert_eq!(file.position(), 0);
+
+    let mut buffer = [0; 4];
+    file.read_exact(&mut buffer).await.unwrap();
+    assert_eq!(&buffer, b"0123");
+    assert_eq!(file.position(), 4);
+
+    file.set_position(7).unwrap();
+    assert_eq!(file.position(), 7);
+    assert_eq!(file.stream_position().await.unwrap(), 7);
+
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(buffer, b"789");
+    assert_eq!(file.position(), 10);
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn stopped() {
+    let (mut sftp, _) = MockServer::start(&[("file", b"content")]).await;
+    let file = sftp.open_read("file").await.unwrap();
+    drop(file);
+
+    sftp.stop().await;
+    assert!(matches!(sftp.stat("file").await, Err(Error::Stopped)));
+
+    let err = std::io::Error::from(sftp.open_read("file").await.unwrap_err());
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+}
+
+#[tokio::test]
+async fn reconnect() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let sftp = ReconnectingClient::new({
+        let connections = connections.clone();
+        move || {
+            let connections = connections.clone();
+            async move {
+                // The first connection is already lost
+                if connections.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Ok(SftpClient::new_stopped())
+                } else {
+                    Ok(MockServer::start(&[("file", b"content")]).await.0)
+                }
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+    let (a, b) = tokio::join!(sftp.stat("file"), sftp.stat("file"));
+    assert_eq!(a.unwrap().size, Some(7));
+    assert_eq!(b.unwrap().size, Some(7));
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+    assert_eq!(sftp.reconnections(), 1);
+
+    // Errors unrelated to the connection are not retried
+    assert!(matches!(sftp.stat("missing").await, Err(Error::Sftp(_))));
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn reconnect_commands() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let sftp = ReconnectingClient::new({
+        let connections = connections.clone();
+        move || {
+            connections.fetch_add(1, Ordering::SeqCst);
+            async {
+                // The session is lost as soon as it is idle
+                let (sftp, _) = MockServer::start(&[("dir/file", b"content")]).await;
+                Ok(sftp.with_idle_timeout(Some(Duration::from_millis(50))))
+            }
+        }
+    })
+    .await
+    .unwrap();
+    let lose_connection = || tokio::time::sleep(Duration::from_millis(200));
+
+    // Each command is retried on a new connection
+    lose_connection().await;
+    assert_eq!(sftp.readdir("dir").await.unwrap().len(), 3);
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    lose_connection().await;
+    let name = sftp.read_dir_all_with_stat("dir").await.unwrap();
+    assert!(name.iter().any(|entry| entry.attrs.size == Some(7)));
+    assert_eq!(connections.load(Ordering::SeqCst), 3);
+
+    lose_connection().await;
+    assert_eq!(sftp.read_to_string("dir/file").await.unwrap(), "content");
+    assert_eq!(connections.load(Ordering::SeqCst), 4);
+
+    lose_connection().await;
+    sftp.statvfs("dir").await.unwrap();
+    assert_eq!(connections.load(Ordering::SeqCst), 5);
+
+    lose_connection().await;
+    assert_eq!(
+        sftp.expand_path("~/dir").await.unwrap().as_ref() as &str,
+        "/home/user/dir"
+    );
+    assert_eq!(connections.load(Ordering::SeqCst), 6);
+    assert_eq!(sftp.reconnections(), 5);
+}
+
+#[tokio::test]
+async fn handle_cache() {
+    let (sftp, _) = MockServer::start(&[("a", b"a"), ("b", b"b"), ("c", b"c")]).await;
+    let mut cache = HandleCache::new(sftp.clone(), 2);
+
+    let a = cache.open("a", PFlags::READ).await.unwrap();
+    let b = cache.open("b", PFlags::READ).await.unwrap();
+    assert_eq!(cache.open("a", PFlags::READ).await.unwrap(), a);
+    assert_eq!(cache.len(), 2);
+
+    // "b" is the least recently used
+    let c = cache.open("c", PFlags::READ).await.unwrap();
+    assert_eq!(cache.len(), 2);
+    assert!(sftp.fstat(b.clone()).await.is_err());
+    assert_eq!(sftp.read(a.clone(), 0, 16).await.unwrap(), "a");
+    assert_eq!(sftp.read(c.clone(), 0, 16).await.unwrap(), "c");
+
+    // "b" is opened again, evicting "a"
+    assert_ne!(cache.open("b", PFlags::READ).await.unwrap(), b);
+    assert!(sftp.fstat(a).await.is_err());
+
+    assert!(cache.close("c", PFlags::READ).await.unwrap());
+    assert!(!cache.close("c", PFlags::READ).await.unwrap());
+    assert!(sftp.fstat(c).await.is_err());
+
+    cache.clear().await.unwrap();
+    assert!(cache.is_empty());
+}
+
+#[cfg(feature = "futures-io")]
+#[tokio::test]
+async fn futures_io() {
+    use futures::io::{AsyncReadExt, AsyncSeekExt};
+
+    let content = b"0123456789";
+    let (sftp, _) = MockServer::start(&[("file", content)]).await;
+    let mut file = sftp.open_read("file").await.unwrap();
+
+    assert_eq!(
+        AsyncSeekExt::seek(&mut file, std::io::SeekFrom::End(-4))
+            .await
+            .unwrap(),
+        6
+    );
+    let mut buffer = Vec::new();
+    AsyncReadExt::read_to_end(&mut file, &mut buffer)
+        .await
+        .unwrap();
+    assert_eq!(buffer, b"6789");
+
+    AsyncSeekExt::seek(&mut file, std::io::SeekFrom::Start(2))
+        .await
+        .unwrap();
+    let mut buffer = [0; 3];
+    AsyncReadExt::read_exact(&mut file, &mut buffer)
+        .await
+        .unwrap();
+    assert_eq!(&buffer, b"234");
+
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn open_read() {
+    let (sftp, _) = MockServer::start(&[("file", b"content")]).await;
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(buffer, b"content");
+    file.close().await.unwrap();
+
+    // `open` opens the file for reading as well
+    let mut file = sftp.open("file").await.unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(buffer, b"content");
+    file.close().await.unwrap();
+
+    // Empty flags do not allow reading
+    let mut file = sftp
+        .open_with_flags("file", PFlags::default())
+        .await
+        .unwrap();
+    let err = file.read_to_end(&mut Vec::new()).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    file.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn write_read_back() {
+    let (sftp, _) = MockServer::start(&[]).await;
+
+    let mut file = sftp
+        .open_with_flags("file", PFlags::WRITE | PFlags::CREATE)
+        .await
+        .unwrap();
+    file.write_all(b"Hello, world!").await.unwrap();
+    file.seek(std::io::SeekFrom::Start(7)).await.unwrap();
+    file.write_all(b"SFTP!").await.unwrap();
+    file.close().await.unwrap();
+
+    let mut file = sftp.open_read("file").await.unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(buffer, b"Hello, SFTP!!");
+    file.close().await.unwrap();
+
+    // Writing requires `WRITE`
+    let mut file = sftp.open_read("file").await.unwrap();
+    let err = file.write_all(b"content").await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    file.close().await.unwrap();
+
+    // `TRUNCATE` discards the previous content
+    let mut file = sftp
+        .open_with_flags("file", PFlags::WRITE | PFlags::TRUNCATE)
+        .await
+        .unwrap();
+    file.close().await.unwrap();
+    assert_eq!(sftp.stat("file").await.unwrap().size, Some(0));
+
+    sftp.remove("file").await.unwrap();
+    let err = sftp.stat("file").await.unwrap_err();
+    assert_eq!(
+        std::io::Error::from(err).kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+#[tokio::test]
+async fn copy() {
+    let large = vec![7u8; MAX_FILE_SIZE + 1];
+    let (sftp, _) = MockServer::start(&[
+        ("src", b"content"),
+        ("large", &large),
+        ("dir/file", b""),
+        ("dst", b"previous longer content"),
+    ])
+    .await;
+    let closes = Arc::new(AtomicUsize::new(0));
+    let counter = closes.clone();
+    sftp.on_frame(move |direction, frame| {
+        // SSH_FXP_CLOSE
+        if direction == FrameDirection::Sent && frame.first() == Some(&4) {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    })
+    .unwrap();
+    let perms = Permisions::from_bits_retain(0o640);
+    sftp.setstat(
+        "src",
+        Attrs {
+            perms: Some(perms),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    // The destination is truncated, and gets the permissions of the source
+    assert_eq!(sftp.copy("src", "dst").await.unwrap(), 7);
+    assert_eq!(sftp.read_to_string("dst").await.unwrap(), "content");
+    assert_eq!(sftp.stat("dst").await.unwrap().perms, Some(perms));
+    let closes_before = closes.load(Ordering::SeqCst);
+
+    // The source is closed when the destination cannot be opened
+    let err = sftp.copy("src", "dir").await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.error == "Is a directory"));
+    assert_eq!(closes.load(Ordering::SeqCst), closes_before + 1);
+
+    // Both files are closed when the copy fails
+    let err = sftp.copy("large", "full").await.unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.error == "No space left"));
+    assert_eq!(closes.load(Ordering::SeqCst), closes_before + 3);
+}
+
+#[tokio::test]
+async fn rename_overwrite() {
+    let (sftp, _) = MockServer::start(&[
+        ("dir/new", b"new"),
+        ("dir/old", b"old content"),
+        ("other/file", b"other"),
+    ])
+    .await;
+
+    // The server refuses to rename onto an existing file, unless the fallback is allowed
+    let err = sftp
+        .rename_overwrite("dir/new", "dir/old", false)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::Failure));
+    assert_eq!(sftp.stat("dir/old").await.unwrap().size, Some(11));
+
+    // The destination is removed, then the rename is tried again
+    sftp.rename_overwrite("dir/new", "dir/old", true)
+        .await
+        .unwrap();
+    assert_eq!(sftp.stat("dir/old").await.unwrap().size, Some(3));
+    assert!(sftp.stat("dir/new").await.is_err());
+
+    // Missing sources are reported without removing the destination
+    let err = sftp
+        .rename_overwrite("dir/missing", "dir/old", true)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::NoSuchFile));
+    assert_eq!(sftp.stat("dir/old").await.unwrap().size, Some(3));
+
+    // The destination is already removed when the second rename fails
+    let err = sftp
+        .rename_overwrite("dir/old", "other/file", true)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::Failure));
+    assert!(sftp.stat("other/file").await.is_err());
+    assert_eq!(sftp.stat("dir/old").await.unwrap().size, Some(3));
+}
+
+#[tokio::test]
+async fn rename_or_copy() {
+    let (sftp, _) = MockServer::start(&[
+        ("a/file", b"content"),
+        ("a/dir/file", b""),
+        ("b/existing", b"existing"),
+    ])
+    .await;
+
+    // Renames across filesystems fail, unless the copy is allowed
+    let err = sftp
+        .rename_or_copy("a/file", "b/file", false)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::Failure));
+
+    let attrs = Attrs {
+        owner: Some(Owner {
+            uid: 1001,
+            gid: 1002,
+        }),
+        perms: Some(Permisions::from_bits_retain(0o104751)),
+        time: Some(Time {
+            atime: 1_000,
+            mtime: 2_000,
+            nanos: None,
+        }),
+        ..Default::default()
+    };
+    sftp.setstat("a/file", attrs.clone()).await.unwrap();
+    sftp.rename_or_copy("a/file", "b/file", true).await.unwrap();
+
+    // The attributes of the original file are restored on the copy
+    let copied = sftp.lstat("b/file").await.unwrap();
+    assert_eq!(copied.time, attrs.time);
+    assert_eq!(copied.owner, attrs.owner);
+    assert_eq!(copied.perms.unwrap().bits() & 0o7777, 0o4751);
+    assert_eq!(copied.size, Some(7));
+
+    let mut file = sftp.open_read("b/file").await.unwrap();
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).await.unwrap();
+    assert_eq!(buffer, b"content");
+    file.close().await.unwrap();
+    assert!(sftp.stat("a/file").await.is_err());
+
+    // Renames within a filesystem do not copy
+    sftp.rename_or_copy("b/file", "b/renamed", true)
+        .await
+        .unwrap();
+    assert_eq!(sftp.stat("b/renamed").await.unwrap().size, Some(7));
+
+    // Existing destinations are not overwritten
+    let err = sftp
+        .rename_or_copy("b/renamed", "a/dir/file", true)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.error == "File exists"));
+
+    // Directories cannot be copied
+    let err = sftp
+        .rename_or_copy("a/dir", "b/dir", true)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.error.contains("not a regular file")));
+}
+
+#[tokio::test]
+async fn transfer_progress() {
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let (sftp, _) = MockServer::start(&[]).await;
+
+    let mut reports = Vec::new();
+    let written = sftp
+        .upload_stream_with_progress(
+            content.as_slice(),
+            "file",
+            Some(content.len() as u64),
+            |done, total| reports.push((done, total)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(written, content.len() as u64);
+    assert_eq!(
+        reports.last(),
+        Some(&(content.len() as u64, Some(content.len() as u64)))
+    );
+    assert!(reports.windows(2).all(|w| w[0].0 <= w[1].0));
+
+    let mut reports = Vec::new();
+    let mut downloaded = Vec::new();
+    let read = sftp
+        .download_stream_with_progress("file", &mut downloaded, |done, total| {
+            reports.push((done, total))
+        })
+        .await
+        .unwrap();
+    assert_eq!(read, content.len() as u64);
+    assert_eq!(downloaded, content);
+    assert_eq!(
+        reports.last(),
+        Some(&(content.len() as u64, Some(content.len() as u64)))
+    );
+    assert!(reports.windows(2).all(|w| w[0].0 <= w[1].0));
+}
+
+#[tokio::test]
+async fn copy_permissions() {
+    let (sftp, _) = MockServer::start(&[("from", b""), ("to", b""), ("other", b"")]).await;
+
+    let attrs = Attrs {
+        perms: Some(Permisions::REG | Permisions::from_bits_retain(0o640)),
+        owner: Some(Owner {
+            uid: 1000,
+            gid: 100,
+        }),
+        ..Default::default()
+    };
+    sftp.setstat("from", attrs.clone()).await.unwrap();
+
+    sftp.copy_permissions("from", "to").await.unwrap();
+    let copied = sftp.stat("to").await.unwrap();
+    assert_eq!(copied.perms, attrs.perms);
+    assert_eq!(copied.owner, attrs.owner);
+
+    let from = sftp.open_read("from").await.unwrap();
+    let other = sftp.open_read("other").await.unwrap();
+    sftp.copy_permissions_handle(
+        from.handle().unwrap().clone(),
+        other.handle().unwrap().clone(),
+    )
+    .await
+    .unwrap();
+    let copied = sftp.stat("other").await.unwrap();
+    assert_eq!(copied.perms, attrs.perms);
+    assert_eq!(copied.owner, attrs.owner);
+
+    assert!(sftp.copy_permissions("missing", "to").await.is_err());
+}
+
+/// Stream counting the writes of the client.
+struct CountingStream {
+    inner: DuplexStream,
+    writes: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for CountingStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CountingStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test]
+async fn batched_writes() {
+    let (inner, _) = MockServer::spawn(&[("file", b"content")], None);
+    let writes = Arc::new(AtomicUsize::new(0));
+    let sftp = SftpClient::with_stream(CountingStream {
+        inner,
+        writes: writes.clone(),
+    })
+    .await
+    .unwrap();
+    writes.store(0, Ordering::SeqCst);
+
+    // All the requests are queued before the client gets a chance to send them
+    let requests = (0..200).map(|_| sftp.stat("file")).collect::<Vec<_>>();
+    for attrs in futures::future::join_all(requests).await {
+        assert_eq!(attrs.unwrap().size, Some(7));
+    }
+
+    let writes = writes.load(Ordering::SeqCst);
+    assert!(writes < 10, "{writes} writes for 200 requests");
+}
+
+#[tokio::test]
+async fn handle_for_session() {
+    let (a, _) = MockServer::start(&[("dir/file", b"content")]).await;
+    let (b, _) = MockServer::start(&[("dir/file", b"content")]).await;
+    assert_ne!(a.session_id(), b.session_id());
+    assert_eq!(a.session_id(), a.clone().session_id());
+    assert_eq!(SftpClient::new_stopped().session_id(), 0);
+
+    let mut file = a.open("dir/file").await.unwrap();
+    let handle = file.handle_for(&a.clone()).unwrap();
+    assert_eq!(Some(&handle), file.handle());
+    assert_eq!(a.read(handle, 0, 16).await.unwrap(), "content");
+
+    let err = file.handle_for(&b).unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::BadMessage));
+
+    let mut dir = a.opendir("dir").await.unwrap();
+    assert!(dir.handle_for(&a).is_ok());
+    let err = dir.handle_for(&b).unwrap_err();
+    assert!(matches!(err, Error::Sftp(status) if status.code == StatusCode::BadMessage));
+
+    file.close().await.unwrap();
+    dir.close().await.unwrap();
+    assert!(matches!(file.handle_for(&a), Err(Error::Io(_))));
+    assert!(matches!(dir.handle_for(&a), Err(Error::Io(_))));
+}
+
+#[tokio::test]
+async fn handle_origin() {
+    let (a, _) = MockServer::start(&[("dir/file", b"content")]).await;
+    let (b, _) = MockServer::start(&[("dir/file", b"content")]).await;
+    let is_bad_message =
+        |err: Error| matches!(err, Error::Sftp(status) if status.code == StatusCode::BadMessage);
+
+    let file = a.open("dir/file").await.unwrap();
+    let dir = a.opendir("dir").await.unwrap();
+
+    // The origin is kept by the file and the directory, and checked before giving their handle away
+    let sent = b.metrics().requests_sent;
+    assert!(is_bad_message(file.handle_for(&b).unwrap_err()));
+    assert!(is_bad_message(dir.handle_for(&b).unwrap_err()));
+    assert!(is_bad_message(file.clone().handle_for(&b).unwrap_err()));
+    assert_eq!(b.metrics().requests_sent, sent);
+
+    // Any client of the same session can use the handles
+    let file_handle = file.handle_for(&a.clone()).unwrap();
+    let dir_handle = dir.handle_for(&a.clone()).unwrap();
+    assert_eq!(&file_handle, file.handle().unwrap());
+    assert_eq!(&dir_handle, dir.handle().unwrap());
+    assert_eq!(a.read(file_handle, 0, 16).await.unwrap(), "content");
+    assert!(!a.readdir_handle(dir_handle).await.unwrap().is_empty());
+
+    // Handles are plain bytes: wrapping them again is up to the caller
+    let handle = file.handle().unwrap().clone();
+    assert_eq!(handle, Handle(handle.0.clone()));
+}
+
+#[tokio::test]
+async fn pool_round_robin() {
+    let mut clients = Vec::new();
+    for _ in 0..3 {
+        let (sftp, _) = MockServer::start(&[("dir/file", b"content")]).await;
+        clients.push(sftp);
+    }
+    let pool = SftpPool::with_clients(clients.clone());
+    let sent = |client: &SftpClient| client.metrics().requests_sent;
+    let before = clients.iter().map(sent).collect::<Vec<_>>();
+
+    // Each command of the pool is sent by the next client
+    for _ in 0..2 {
+        pool.stat("dir/file").await.unwrap();
+        pool.lstat("dir/file").await.unwrap();
+        pool.realpath("dir").await.unwrap();
+    }
+    let after = clients.iter().map(sent).collect::<Vec<_>>();
+    for (before, after) in before.iter().zip(&after) {
+        assert_eq!(after - before, 2);
+    }
+
+    pool.mkdir("other").await.unwrap();
+    pool.rename("dir/file", "dir/moved").await.unwrap();
+    pool.remove("dir/file").await.unwrap();
+    assert!(clients[0].stat("other").await.is_ok());
+    assert!(clients[1].stat("dir/moved").await.is_ok());
+    assert!(clients[2].stat("dir/file").await.is_err());
+}
+
+#[tokio::test]
+async fn pool_routing() {
+    let (a, _) = MockServer::start(&[("dir/file", b"first")]).await;
+    let (b, _) = MockServer::start(&[("dir/file", b"second")]).await;
+    let pool = SftpPool::with_clients([a.clone(), b.clone()]);
+    let is_bad_message =
+        |err: Error| matches!(err, Error::Sftp(status) if status.code == StatusCode::BadMessage);
+
+    let first = pool
+        .open_handle("dir/file", PFlags::READ | PFlags::WRITE, Attrs::default())
+        .await
+        .unwrap();
+    let second = pool
+        .open_handle("dir/file", PFlags::READ | PFlags::WRITE, Attrs::default())
+        .await
+        .unwrap();
+    assert_eq!(first.session_id(), a.session_id());
+    assert_eq!(second.session_id(), b.session_id());
+    // Both servers give the same bytes: only the session tells the handles apart
+    assert_eq!(first.handle(), second.handle());
+    assert_ne!(first, second);
+
+    // Handle commands go to the session that opened the handle, whatever the next client of the pool
+    let (sent_a, sent_b) = (a.metrics().requests_sent, b.metrics().requests_sent);
+    for _ in 0..3 {
+        assert_eq!(pool.read(&first, 0, 16).await.unwrap(), "first");
+        assert_eq!(pool.read(&second, 0, 16).await.unwrap(), "second");
+    }
+    assert_eq!(a.metrics().requests_sent - sent_a, 3);
+    assert_eq!(b.metrics().requests_sent - sent_b, 3);
+
+    pool.write(&second, 6, "!").await.unwrap();
+    assert_eq!(pool.fstat(&second).await.unwrap().size, Some(7));
+    assert_eq!(pool.fstat(&first).await.unwrap().size, Some(5));
+    assert_eq!(
+        b.read(second.handle().clone(), 0, 16).await.unwrap(),
+        "second!"
+    );
+
+    // Files and directories are not interchangeable
+    let dir = pool.opendir_handle("dir").await.unwrap();
+    assert!(!pool.readdir_handle(&dir).await.unwrap().is_empty());
+    assert!(is_bad_message(pool.read(&dir, 0, 16).await.unwrap_err()));
+    assert!(is_bad_message(
+        pool.readdir_handle(&first).await.unwrap_err()
+    ));
+
+    // Handles of another pool are rejected without being sent
+    let (c, _) = MockServer::start(&[("dir/file", b"third")]).await;
+    let other = SftpPool::with_clients([c.clone()])
+        .open_handle("dir/file", PFlags::READ, Attrs::default())
+        .await
+        .unwrap();
+    let (sent_a, sent_b) = (a.metrics().requests_sent, b.metrics().requests_sent);
+    assert!(is_bad_message(pool.read(&other, 0, 16).await.unwrap_err()));
+    assert!(is_bad_message(pool.close(other).await.unwrap_err()));
+    assert_eq!(a.metrics().requests_sent, sent_a);
+    assert_eq!(b.metrics().requests_sent, sent_b);
+
+    pool.close(first.clone()).await.unwrap();
+    pool.close(second).await.unwrap();
+    pool.close(dir).await.unwrap();
+    assert!(pool.read(&first, 0, 16).await.is_err());
+
+    // Files keep the client that opened them
+    let mut file = pool.open("dir/file").await.unwrap();
+    let owner = if file.handle_for(&a).is_ok() { &a } else { &b };
+    assert_ne!(file.handle_for(&a).is_ok(), file.handle_for(&b).is_ok());
+    let mut content = String::new();
+    file.read_to_string(&mut content).await.unwrap();
+    assert_eq!(
+        owner
+            .read(file.handle_for(owner).unwrap(), 0, 16)
+            .await
+            .unwrap(),
+        content.as_str()
+    );
+}
+
+#[tokio::test]
+async fn dropped_open() {
+    let (sftp, _) = MockServer::start(&[("file", b"content")]).await;
+    let closes = Arc::new(AtomicUsize::new(0));
+    let counter = closes.clone();
+    sftp.on_frame(move |direction, frame| {
+        // SSH_FXP_CLOSE
+        if direction == FrameDirection::Sent && frame.first() == Some(&4) {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    })
+    .unwrap();
+
+    // The handles are closed as soon as they are received
+    drop(sftp.open("file"));
+    drop(sftp.open_with_stat("file", PFlags::READ, Attrs::default()));
+    drop(sftp.open_create_mode("new", Permisions::from_bits_retain(0o600), true));
+
+    sftp.stat("file").await.unwrap();
+    assert_eq!(closes.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn read_to_string() {
+    let (sftp, _) = MockServer::start(&[
+        ("text", "héllo wörld".as_bytes()),
+        ("binary", b"\xff\xfe"),
+        ("empty", b""),
+    ])
+    .await;
+
+    assert_eq!(sftp.read_to_string("text").await.unwrap(), "héllo wörld");
+    assert_eq!(sftp.read_to_string("empty").await.unwrap(), "");
+
+    let err = sftp.read_to_string("binary").await.unwrap_err();
+    assert_eq!(
+        std::io::Error::from(err).kind(),
+        std::io::ErrorKind::InvalidData
+    );
+
+    let len = "héllo wörld".len();
+    assert_eq!(
+        sftp.read_to_string_capped("text", len).await.unwrap(),
+        "héllo wörld"
+    );
+    let err = sftp
+        .read_to_string_capped("text", len - 1)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        std::io::Error::from(err).kind(),
+        std::io::ErrorKind::InvalidData
+    );
+
+    assert!(sftp.read_to_string("missing").await.is_err());
+}
@@ -0,0 +1,233 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the SFTP handshake, and of the errors reported when it fails.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use rusftp::client::{Error, IntoSftpStream, SftpClient, SftpClientConfig};
+use rusftp::message::{Attrs, Message, StatusCode, Version};
+
+/// Connect a client to a server that replies `reply` to the `Init` request.
+async fn handshake(reply: impl AsRef<[u8]> + Send + 'static) -> Error {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    tokio::spawn(async move {
+        let length = server.read_u32().await.unwrap();
+        let mut init = vec![0u8; length as usize];
+        server.read_exact(&mut init).await.unwrap();
+        server.write_all(reply.as_ref()).await.unwrap();
+        server.shutdown().await.unwrap();
+    });
+
+    SftpClient::with_stream(client).await.unwrap_err()
+}
+
+fn bad_message(err: Error) -> String {
+    match err {
+        Error::Sftp(status) if status.code == StatusCode::BadMessage => status.error,
+        err => panic!("Expected a BadMessage status, got {err:?}"),
+    }
+}
+
+#[tokio::test]
+async fn not_sftp_server() {
+    let error = bad_message(handshake(b"Welcome to the shell\n").await);
+    assert!(error.contains("might not be a SFTP server"), "{error}");
+    assert!(error.contains("Welc"), "{error}");
+}
+
+#[tokio::test]
+async fn wrong_version() {
+    let reply = Message::Version(Version {
+        version: 6,
+        extensions: Default::default(),
+    })
+    .encode(6)
+    .unwrap();
+
+    let error = bad_message(handshake(reply).await);
+    assert!(error.contains("version 6"), "{error}");
+}
+
+#[tokio::test]
+async fn not_version() {
+    let reply = Message::Status(StatusCode::Failure.to_status("Nope"))
+        .encode(3)
+        .unwrap();
+
+    let error = bad_message(handshake(reply).await);
+    assert!(error.contains("Status"), "{error}");
+}
+
+#[tokio::test]
+async fn prebuffered() {
+    let (mut client, mut server) = tokio::io::duplex(1024);
+
+    tokio::spawn(async move {
+        // The version is sent upfront, so the client can peek at it
+        let version = Message::Version(Version {
+            version: 3,
+            extensions: Default::default(),
+        })
+        .encode(3)
+        .unwrap();
+        server.write_all(&version).await.unwrap();
+
+        for expected in [1, 17] {
+            let length = server.read_u32().await.unwrap();
+            let mut frame = vec![0u8; length as usize];
+            server.read_exact(&mut frame).await.unwrap();
+            let (id, request) = Message::decode_raw(&frame).unwrap();
+            assert_eq!(request.code(), expected);
+
+            if let Message::Stat(_) = request {
+                let reply = Message::Attrs(Attrs {
+                    size: Some(42),
+                    ..Default::default()
+                });
+                server.write_all(&reply.encode(id).unwrap()).await.unwrap();
+            }
+        }
+    });
+
+    // Peek at the beginning of the version frame
+    let mut peeked = [0u8; 6];
+    client.read_exact(&mut peeked).await.unwrap();
+
+    let sftp = SftpClient::with_stream_config_prebuffered(
+        client,
+        SftpClientConfig::default(),
+        Bytes::copy_from_slice(&peeked),
+    )
+    .await
+    .unwrap();
+    assert_eq!(sftp.stat("file").await.unwrap().size, Some(42));
+}
+
+#[tokio::test]
+async fn handshake_timeout() {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    // The server receives the Init request, but never replies
+    let server = tokio::spawn(async move {
+        let length = server.read_u32().await.unwrap();
+        let mut init = vec![0u8; length as usize];
+        server.read_exact(&mut init).await.unwrap();
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        rest
+    });
+
+    let config = SftpClientConfig {
+        handshake_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    let err = SftpClient::with_stream_config(client, config)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Io(io) if io.kind() == std::io::ErrorKind::TimedOut));
+
+    // The stream has been dropped with the handshake
+    assert!(server.await.unwrap().is_empty());
+}
+
+/// Connection to a SFTP server, whose stream is a local pipe
+struct Pipe(tokio::io::DuplexStream);
+
+#[async_trait::async_trait]
+impl IntoSftpStream for Pipe {
+    type Stream = tokio::io::DuplexStream;
+    async fn into_sftp_stream(self) -> Result<Self::Stream, Error> {
+        Ok(self.0)
+    }
+}
+
+#[tokio::test]
+async fn new_with_handshake_timeout() {
+    let (client, mut server) = tokio::io::duplex(1024);
+
+    // The server receives the Init request, but never sends its Version
+    let server = tokio::spawn(async move {
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).await.unwrap();
+        received
+    });
+
+    let start = tokio::time::Instant::now();
+    let err = SftpClient::new_with_handshake_timeout(Pipe(client), Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Io(io) if io.kind() == std::io::ErrorKind::TimedOut));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // Only the Init request was sent, and the stream has been dropped
+    let received = server.await.unwrap();
+    assert_eq!(received[4], 1);
+    assert_eq!(
+        received.len(),
+        4 + u32::from_be_bytes(received[..4].try_into().unwrap()) as usize
+    );
+}
+
+#[cfg(feature = "diagnostics")]
+#[tokio::test]
+async fn sniff_frames() {
+    use futures::StreamExt;
+    use rusftp::client::SftpSniffer;
+
+    let (sniffed, mut server) = tokio::io::duplex(1024);
+
+    let version = Message::Version(Version {
+        version: 3,
+        extensions: Default::default(),
+    });
+    let status = Message::from(StatusCode::Ok.to_status("done"));
+    let attrs = Message::Attrs(Attrs::default());
+    tokio::spawn(async move {
+        server.write_all(&version.encode(0).unwrap()).await.unwrap();
+        server.write_all(&status.encode(3).unwrap()).await.unwrap();
+        // Status without its content
+        server
+            .write_all(&[0, 0, 0, 5, 101, 0, 0, 0, 7])
+            .await
+            .unwrap();
+        server.write_all(&attrs.encode(4).unwrap()).await.unwrap();
+        server.shutdown().await.unwrap();
+    });
+
+    let mut sniffer = SftpSniffer::new(sniffed);
+    let (id, message) = sniffer.next().await.unwrap().unwrap();
+    assert_eq!(id, 0);
+    assert!(matches!(
+        message,
+        Message::Version(Version { version: 3, .. })
+    ));
+    let (id, message) = sniffer.next().await.unwrap().unwrap();
+    assert_eq!(id, 3);
+    assert!(matches!(message, Message::Status(status) if status.error == "done"));
+    assert!(matches!(
+        sniffer.next().await.unwrap(),
+        Err(Error::WireFormat(_))
+    ));
+    let (id, message) = sniffer.next().await.unwrap().unwrap();
+    assert_eq!(id, 4);
+    assert_eq!(message, Message::Attrs(Attrs::default()));
+    assert!(sniffer.next().await.is_none());
+}
@@ -0,0 +1,244 @@
+// This file is part of the rusftp project
+//
+// Copyright (C) ANEO, 2024-2024. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License")
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! End-to-end tests against a real SFTP server.
+//!
+//! The tests are ignored by default, as they require a running server.
+//! You can start a sftp server configured for these tests with the following command:
+//!
+//! ```sh
+//! docker run -p 2222:22 --rm atmoz/sftp:alpine user:pass:1000
+//! cargo test --test integration -- --ignored
+//! ```
+//!
+//! The server can be configured with the following environment variables:
+//! - `RUSFTP_TEST_ADDR`: address of the ssh server (default: `127.0.0.1:2222`)
+//! - `RUSFTP_TEST_USER`: user name (default: `user`)
+//! - `RUSFTP_TEST_PASS`: password (default: `pass`)
+//! - `RUSFTP_TEST_DIR`: writable directory where the tests create their files (default: `/tmp`)
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use rusftp::client::{Error, SftpClient};
+use rusftp::message::{PFlags, Path, Permisions, StatusCode};
+
+struct Handler;
+
+#[async_trait]
+impl russh::client::Handler for Handler {
+    type Error = russh::Error;
+    async fn check_server_key(
+        &mut self,
+        _server_public_key: &russh_keys::key::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_owned())
+}
+
+/// Connect and authenticate to the test server.
+async fn ssh_connect() -> russh::client::Handle<Handler> {
+    let addr = env_or("RUSFTP_TEST_ADDR", "127.0.0.1:2222");
+    let user = env_or("RUSFTP_TEST_USER", "user");
+    let pass = env_or("RUSFTP_TEST_PASS", "pass");
+
+    let config = Arc::new(russh::client::Config::default());
+    let mut ssh = russh::client::connect(config, addr, Handler).await.unwrap();
+    assert!(ssh.authenticate_password(user, pass).await.unwrap());
+    ssh
+}
+
+/// Connect to the test server, and create a fresh directory for the test.
+async fn connect(test: &str) -> (SftpClient, Path) {
+    let root = env_or("RUSFTP_TEST_DIR", "/tmp");
+    let sftp = SftpClient::new(ssh_connect().await).await.unwrap();
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = Path::from(root) / format!("rusftp-{test}-{}-{nanos}", std::process::id());
+    sftp.mkdir(dir.clone()).await.unwrap();
+
+    (sftp, dir)
+}
+
+/// Remove the directory of the test, and all the files it contains.
+async fn cleanup(sftp: &SftpClient, dir: Path) {
+    for entry in sftp.readdir(dir.clone()).await.unwrap() {
+        if &*entry.filename != "." && &*entry.filename != ".." {
+            sftp.remove(dir.clone() / entry.filename).await.unwrap();
+        }
+    }
+    sftp.rmdir(dir).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn mkdir_readdir_rmdir() {
+    let (mut sftp, dir) = connect("mkdir").await;
+
+    let sub = dir.clone() / Path::from("sub");
+    sftp.mkdir(sub.clone()).await.unwrap();
+    assert!(sftp.stat(sub.clone()).await.unwrap().perms.is_some());
+
+    for name in ["a", "b", "c"] {
+        let file = sftp.open_with_flags(
+            sub.clone() / Path::from(name),
+            PFlags::CREATE | PFlags::WRITE,
+        );
+        file.await.unwrap().close().await.unwrap();
+    }
+
+    // Dir stream
+    let mut dir_stream = sftp.opendir(sub.clone()).await.unwrap();
+    let mut names = Vec::new();
+    while let Some(entry) = dir_stream.next().await {
+        names.push(entry.unwrap().filename.to_string());
+    }
+    dir_stream.close().await.unwrap();
+    names.retain(|name| name != "." && name != "..");
+    names.sort();
+    assert_eq!(names, ["a", "b", "c"]);
+
+    // Non-empty directory cannot be removed
+    assert!(sftp.rmdir(sub.clone()).await.is_err());
+
+    cleanup(&sftp, sub).await;
+    cleanup(&sftp, dir).await;
+    sftp.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn upload_download() {
+    let (mut sftp, dir) = connect("transfer").await;
+
+    // Larger than a chunk, and not a multiple of it
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let path = dir.clone() / Path::from("data.bin");
+
+    let written = sftp
+        .upload_stream(content.as_slice(), path.clone())
+        .await
+        .unwrap();
+    assert_eq!(written, content.len() as u64);
+    assert_eq!(
+        sftp.stat(path.clone()).await.unwrap().size,
+        Some(content.len() as u64)
+    );
+
+    let mut downloaded = Vec::new();
+    let read = sftp
+        .download_stream(path.clone(), &mut downloaded)
+        .await
+        .unwrap();
+    assert_eq!(read, content.len() as u64);
+    assert_eq!(downloaded, content);
+
+    cleanup(&sftp, dir).await;
+    sftp.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn file_seek() {
+    let (mut sftp, dir) = connect("seek").await;
+    let path = dir.clone() / Path::from("file.txt");
+
+    let mut file = sftp
+        .open_with_flags(path, PFlags::CREATE | PFlags::READ | PFlags::WRITE)
+        .await
+        .unwrap();
+    file.write_all(b"Hello world!").await.unwrap();
+
+    let mut content = String::new();
+    file.seek(std::io::SeekFrom::Start(6)).await.unwrap();
+    file.read_to_string(&mut content).await.unwrap();
+    assert_eq!(content, "world!");
+
+    content.clear();
+    file.seek(std::io::SeekFrom::End(-12)).await.unwrap();
+    file.read_to_string(&mut content).await.unwrap();
+    assert_eq!(content, "Hello world!");
+
+    file.close().await.unwrap();
+
+    cleanup(&sftp, dir).await;
+    sftp.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn rename_remove() {
+    let (mut sftp, dir) = connect("rename").await;
+    let old = dir.clone() / Path::from("old");
+    let new = dir.clone() / Path::from("new");
+
+    sftp.upload_stream(b"data".as_slice(), old.clone())
+        .await
+        .unwrap();
+    sftp.rename(old.clone(), new.clone()).await.unwrap();
+
+    match sftp.stat(old.clone()).await {
+        Err(Error::Sftp(status)) => assert_eq!(status.code, StatusCode::NoSuchFile),
+        other => panic!("Expected NoSuchFile, got {other:?}"),
+    }
+    assert_eq!(sftp.stat(new.clone()).await.unwrap().size, Some(4));
+
+    sftp.remove(new.clone()).await.unwrap();
+    assert!(!sftp.remove_if_exists(new).await.unwrap());
+
+    cleanup(&sftp, dir).await;
+    sftp.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn create_mode() {
+    let (mut sftp, dir) = connect("mode").await;
+    let path = dir.clone() / Path::from("file");
+    let mode = Permisions::from_bits_retain(0o666);
+
+    let file = sftp.open_create_mode(path.clone(), mode, true);
+    file.await.unwrap().close().await.unwrap();
+
+    let perms = sftp.stat(path).await.unwrap().perms.unwrap();
+    assert_eq!(perms.bits() & 0o7777, 0o666);
+
+    cleanup(&sftp, dir).await;
+    sftp.stop().await;
+}
+
+#[tokio::test]
+#[ignore = "requires a running SFTP server"]
+async fn session_with_env() {
+    let ssh = ssh_connect().await;
+
+    // The server may ignore the variables, but the session must still start
+    let sftp = SftpClient::from_session_with_env(&ssh, [("LANG", "C"), ("RUSFTP_TEST", "1")])
+        .await
+        .unwrap();
+    assert!(sftp.stat(".").await.is_ok());
+}